@@ -0,0 +1,49 @@
+//! Times variable lookups through deeply nested scopes, in the style of
+//! `examples/scopes.lox`. Run with `cargo bench`.
+
+use std::time::Instant;
+
+use lox_lib::{run, run_with, Interpreter};
+
+const DEPTH: usize = 50;
+const ITERATIONS: usize = 2000;
+
+/// Builds a program that declares a variable in each of `DEPTH` nested
+/// blocks, then repeatedly reads variables from the outermost, middle and
+/// innermost of them, as well as a global.
+fn nested_scopes() -> String {
+    let mut source = String::from("var global = 0;\n");
+    for depth in 0..DEPTH {
+        source.push_str(&format!("{{ var v{} = {};\n", depth, depth));
+    }
+    source.push_str(&format!(
+        "var i = 0; while (i < {}) {{ global = global + v0 + v{} + v{}; i = i + 1; }}\n",
+        ITERATIONS,
+        DEPTH / 2,
+        DEPTH - 1
+    ));
+    source.push_str(&"}".repeat(DEPTH));
+    source.push_str("\nprint global;\n");
+    source
+}
+
+fn main() {
+    let source = nested_scopes();
+    let expected = ITERATIONS * (DEPTH / 2 + DEPTH - 1);
+    assert_eq!(run(&source).unwrap(), format!("{}\n", expected));
+
+    let runs = 20;
+    let start = Instant::now();
+    for _ in 0..runs {
+        let mut interpreter = Interpreter::default();
+        run_with(&mut interpreter, &source).unwrap();
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "nested scopes: {:?} per run ({} runs of {} lookups through {} scopes)",
+        elapsed / runs,
+        runs,
+        ITERATIONS * 4,
+        DEPTH
+    );
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+
+use crate::{
+    expr::{Expr, Logical, Ternary},
+    stmt::{Class, DoWhile, For, ForIn, Function, If, Stmt, Switch, While},
+    visitor::{self, Visit},
+};
+
+/// Counts the statements and expressions in the program, including every
+/// statement and expression nested inside them.
+pub fn node_count(stmts: &[Stmt]) -> usize {
+    let mut visitor = NodeCountVisitor::default();
+    for stmt in stmts {
+        visitor.visit_stmt(stmt);
+    }
+    visitor.count
+}
+
+/// Finds how deeply the statements and expressions in the program are
+/// nested. Top-level statements are at depth 1, so `print 1;` has a depth
+/// of 2, and an empty program has a depth of 0.
+pub fn max_depth(stmts: &[Stmt]) -> usize {
+    let mut visitor = DepthVisitor::default();
+    for stmt in stmts {
+        visitor.visit_stmt(stmt);
+    }
+    visitor.max_depth
+}
+
+#[derive(Default)]
+struct NodeCountVisitor {
+    count: usize,
+}
+
+impl<'ast> Visit<'ast> for NodeCountVisitor {
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        self.count += 1;
+        visitor::visit_expr(self, e);
+    }
+
+    fn visit_stmt(&mut self, s: &'ast Stmt) {
+        self.count += 1;
+        visitor::visit_stmt(self, s);
+    }
+}
+
+#[derive(Default)]
+struct DepthVisitor {
+    depth: usize,
+    max_depth: usize,
+}
+
+impl<'ast> Visit<'ast> for DepthVisitor {
+    fn visit_expr(&mut self, e: &'ast Expr) {
+        self.depth += 1;
+        self.max_depth = self.max_depth.max(self.depth);
+        visitor::visit_expr(self, e);
+        self.depth -= 1;
+    }
+
+    fn visit_stmt(&mut self, s: &'ast Stmt) {
+        self.depth += 1;
+        self.max_depth = self.max_depth.max(self.depth);
+        visitor::visit_stmt(self, s);
+        self.depth -= 1;
+    }
+}
+
+/// Computes the cyclomatic complexity of every function in the program.
+///
+/// Each function starts with a score of 1, and every decision point inside
+/// it (`if`, `while`, `do`-`while`, `for`, `for`-`in`, `and`, `or`, `?:`,
+/// and each `case` of a `switch`) adds one. Decision points inside a nested
+/// function only count towards the nested function. Methods are reported as
+/// `Class.method`. When several functions share a name, such as a `helper`
+/// nested in each of two functions, the ones after the first are reported
+/// with the line they're declared on, as `helper (line 7)`.
+pub fn complexity(stmts: &[Stmt]) -> HashMap<String, u32> {
+    let mut visitor = ComplexityVisitor::default();
+    for stmt in stmts {
+        visitor.visit_stmt(stmt);
+    }
+    visitor.scores
+}
+
+#[derive(Default)]
+struct ComplexityVisitor {
+    scores: HashMap<String, u32>,
+    // the name and running score of each function we are currently inside
+    functions: Vec<(String, u32)>,
+    class: Option<String>,
+}
+
+impl ComplexityVisitor {
+    fn add_decision_point(&mut self) {
+        if let Some((_, score)) = self.functions.last_mut() {
+            *score += 1;
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for ComplexityVisitor {
+    fn visit_stmt_class(&mut self, s: &'ast Class) {
+        let enclosing_class = self.class.replace(s.name.to_string());
+        visitor::visit_stmt_class(self, s);
+        self.class = enclosing_class;
+    }
+
+    fn visit_stmt_function(&mut self, s: &'ast Function) {
+        // a class's methods are visited directly, while functions nested
+        // inside a method are not methods themselves
+        let enclosing_class = self.class.take();
+        let name = match &enclosing_class {
+            Some(class) => format!("{}.{}", class, s.name),
+            None => s.name.to_string(),
+        };
+        self.functions.push((name, 1));
+        visitor::visit_stmt_function(self, s);
+        if let Some((name, score)) = self.functions.pop() {
+            let name = if self.scores.contains_key(&name) {
+                format!("{} (line {})", name, s.span.start_line)
+            } else {
+                name
+            };
+            self.scores.insert(name, score);
+        }
+        self.class = enclosing_class;
+    }
+
+    fn visit_stmt_if(&mut self, s: &'ast If) {
+        self.add_decision_point();
+        visitor::visit_stmt_if(self, s);
+    }
+
+    fn visit_stmt_while(&mut self, s: &'ast While) {
+        self.add_decision_point();
+        visitor::visit_stmt_while(self, s);
+    }
+
+    fn visit_stmt_do_while(&mut self, s: &'ast DoWhile) {
+        self.add_decision_point();
+        visitor::visit_stmt_do_while(self, s);
+    }
+
+    fn visit_stmt_for(&mut self, s: &'ast For) {
+        self.add_decision_point();
+        visitor::visit_stmt_for(self, s);
+    }
+
+    fn visit_stmt_for_in(&mut self, s: &'ast ForIn) {
+        self.add_decision_point();
+        visitor::visit_stmt_for_in(self, s);
+    }
+
+    fn visit_stmt_switch(&mut self, s: &'ast Switch) {
+        for _ in &s.cases {
+            self.add_decision_point();
+        }
+        visitor::visit_stmt_switch(self, s);
+    }
+
+    fn visit_expr_logical(&mut self, e: &'ast Logical) {
+        self.add_decision_point();
+        visitor::visit_expr_logical(self, e);
+    }
+
+    fn visit_expr_ternary(&mut self, e: &'ast Ternary) {
+        self.add_decision_point();
+        visitor::visit_expr_ternary(self, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    #[test]
+    fn node_count_and_depth_of_examples() {
+        // three prints of a literal or an addition of literals
+        let stmts = parse(&read_to_string("examples/stmts.lox").unwrap());
+        assert_eq!(node_count(&stmts), 8);
+        assert_eq!(max_depth(&stmts), 3);
+
+        // the deepest nodes are the operands of the subtractions in fib,
+        // e.g. function > return > binary > call > binary > variable
+        let stmts = parse(&read_to_string("examples/fibonacci-rec.lox").unwrap());
+        assert_eq!(node_count(&stmts), 34);
+        assert_eq!(max_depth(&stmts), 6);
+    }
+
+    #[test]
+    fn node_count_and_depth_of_empty_program() {
+        assert_eq!(node_count(&[]), 0);
+        assert_eq!(max_depth(&[]), 0);
+    }
+
+    #[test]
+    fn complexity_counts_decision_points() {
+        let stmts = parse(
+            "fun check(a, b) {
+               if (a) print a;
+               if (a and b) print b;
+             }",
+        );
+        assert_eq!(complexity(&stmts), HashMap::from([("check".into(), 4)]));
+    }
+
+    #[test]
+    fn complexity_counts_conditional_expressions() {
+        let stmts = parse("fun sign(x) { return x < 0 ? -1 : x > 0 ? 1 : 0; }");
+        assert_eq!(complexity(&stmts), HashMap::from([("sign".into(), 3)]));
+    }
+
+    #[test]
+    fn complexity_counts_loops() {
+        let stmts = parse(
+            "fun count(n) {
+               while (n > 0) n = n - 1;
+               do n = n + 1; while (n < 3);
+               for (x in [1, 2]) print x;
+             }",
+        );
+        assert_eq!(complexity(&stmts), HashMap::from([("count".into(), 4)]));
+    }
+
+    #[test]
+    fn complexity_counts_switch_cases() {
+        // the default case is taken when no other is, so it adds nothing
+        let stmts = parse(
+            "fun name(n) {
+               switch (n) {
+                 case 1: return \"one\";
+                 case 2: return \"two\";
+                 case 3: if (n) return \"three\";
+                 default: return \"many\";
+               }
+             }",
+        );
+        assert_eq!(complexity(&stmts), HashMap::from([("name".into(), 5)]));
+    }
+
+    #[test]
+    fn complexity_of_nested_functions_and_methods() {
+        let stmts = parse(
+            "fun outer() {
+               fun inner(x) {
+                 while (x) x = false;
+               }
+               return inner;
+             }
+             class Foo {
+               bar(x) {
+                 for (var i = 0; i < x; i = i + 1) print i;
+               }
+             }",
+        );
+        assert_eq!(
+            complexity(&stmts),
+            HashMap::from([
+                ("outer".into(), 1),
+                ("inner".into(), 2),
+                ("Foo.bar".into(), 2)
+            ])
+        );
+    }
+
+    #[test]
+    fn complexity_of_functions_with_the_same_name() {
+        let stmts = parse(
+            "fun a() {
+               fun helper() {}
+             }
+             fun b() {
+               fun helper(x) { if (x) print x; }
+             }",
+        );
+        assert_eq!(
+            complexity(&stmts),
+            HashMap::from([
+                ("a".into(), 1),
+                ("b".into(), 1),
+                ("helper".into(), 1),
+                ("helper (line 5)".into(), 2)
+            ])
+        );
+    }
+}
@@ -0,0 +1,411 @@
+use crate::{
+    expr::{
+        Assign, Binary, Call, Expr, Get, Grouping, Index, Interpolation, ListLiteral, Literal,
+        LiteralExpr, Logical, MapLiteral, Set, SetIndex, StringPart, Super, Ternary, This, Unary,
+        Variable,
+    },
+    stmt::{
+        Block, Class, DoWhile, Expression, For, ForIn, Function, If, Print, Return, Stmt, Switch,
+        Throw, Try, Var, While,
+    },
+    token::TokenKind,
+    visitor::{ExprVisitor, StmtVisitor},
+};
+
+/// Renders syntax trees as S-expressions, such as `(print (+ 1 2))`, which
+/// makes the structure the parser produced (e.g. the precedence of
+/// operators) easy to see.
+pub struct AstPrinter;
+
+impl AstPrinter {
+    /// Renders each statement of a program on its own line.
+    pub fn print_program(&mut self, stmts: &[Stmt]) -> String {
+        stmts
+            .iter()
+            .map(|stmt| self.visit_stmt(stmt) + "\n")
+            .collect()
+    }
+
+    fn parenthesize(&mut self, name: &str, exprs: &[&Expr]) -> String {
+        let mut result = format!("({}", name);
+        for expr in exprs {
+            result.push(' ');
+            result.push_str(&self.visit_expr(expr));
+        }
+        result.push(')');
+        result
+    }
+
+    fn print_function(&mut self, function: &Function) -> String {
+        let Function {
+            name, params, body, ..
+        } = function;
+        let params: Vec<String> = params.iter().map(|param| param.to_string()).collect();
+        let mut result = format!("(fun {} ({})", name, params.join(" "));
+        for stmt in body.iter() {
+            result.push(' ');
+            result.push_str(&self.visit_stmt(stmt));
+        }
+        result.push(')');
+        result
+    }
+}
+
+impl ExprVisitor for AstPrinter {
+    type ExprResult = String;
+
+    fn visit_expr_assign(&mut self, assign: &Assign) -> Self::ExprResult {
+        let value = self.visit_expr(&assign.value);
+        format!(
+            "({} {} {})",
+            assignment_operator(&assign.operator),
+            assign.name,
+            value
+        )
+    }
+
+    fn visit_expr_binary(&mut self, binary: &Binary) -> Self::ExprResult {
+        self.parenthesize(&binary.operator.to_string(), &[&binary.left, &binary.right])
+    }
+
+    fn visit_expr_call(&mut self, call: &Call) -> Self::ExprResult {
+        let mut exprs = vec![call.callee.as_ref()];
+        exprs.extend(&call.arguments);
+        self.parenthesize("call", &exprs)
+    }
+
+    fn visit_expr_get(&mut self, get: &Get) -> Self::ExprResult {
+        let object = self.visit_expr(&get.object);
+        format!("(. {} {})", object, get.name)
+    }
+
+    fn visit_expr_grouping(&mut self, grouping: &Grouping) -> Self::ExprResult {
+        self.parenthesize("group", &[&grouping.expression])
+    }
+
+    fn visit_expr_index(&mut self, index: &Index) -> Self::ExprResult {
+        self.parenthesize("index", &[&index.object, &index.index])
+    }
+
+    fn visit_expr_interpolation(&mut self, interpolation: &Interpolation) -> Self::ExprResult {
+        let mut result = "(interpolate".to_string();
+        for part in &interpolation.parts {
+            result.push(' ');
+            match part {
+                StringPart::Text(text) => result.push_str(&format!("{:?}", text)),
+                StringPart::Expr(expr) => result.push_str(&self.visit_expr(expr)),
+            }
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_expr_list_literal(&mut self, list_literal: &ListLiteral) -> Self::ExprResult {
+        let exprs: Vec<&Expr> = list_literal.elements.iter().collect();
+        self.parenthesize("list", &exprs)
+    }
+
+    fn visit_expr_literal(&mut self, literal: &LiteralExpr) -> Self::ExprResult {
+        match &literal.value {
+            Literal::Number(x) => x.to_string(),
+            Literal::String(x) => format!("{:?}", x),
+            Literal::Bool(x) => x.to_string(),
+            Literal::Nil => "nil".into(),
+        }
+    }
+
+    fn visit_expr_logical(&mut self, logical: &Logical) -> Self::ExprResult {
+        self.parenthesize(
+            &logical.operator.to_string(),
+            &[&logical.left, &logical.right],
+        )
+    }
+
+    fn visit_expr_map_literal(&mut self, map_literal: &MapLiteral) -> Self::ExprResult {
+        let mut result = "(map".to_owned();
+        for (key, value) in &map_literal.entries {
+            let key = self.visit_expr(key);
+            let value = self.visit_expr(value);
+            result.push_str(&format!(" ({} {})", key, value));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_expr_set(&mut self, set: &Set) -> Self::ExprResult {
+        let object = self.visit_expr(&set.object);
+        let value = self.visit_expr(&set.value);
+        format!(
+            "({} (. {} {}) {})",
+            assignment_operator(&set.operator),
+            object,
+            set.name,
+            value
+        )
+    }
+
+    fn visit_expr_set_index(&mut self, set_index: &SetIndex) -> Self::ExprResult {
+        let index = self.parenthesize("index", &[&set_index.object, &set_index.index]);
+        let value = self.visit_expr(&set_index.value);
+        format!(
+            "({} {} {})",
+            assignment_operator(&set_index.operator),
+            index,
+            value
+        )
+    }
+
+    fn visit_expr_super(&mut self, super_: &Super) -> Self::ExprResult {
+        format!("(. super {})", super_.method)
+    }
+
+    fn visit_expr_ternary(&mut self, ternary: &Ternary) -> Self::ExprResult {
+        self.parenthesize(
+            "?:",
+            &[&ternary.condition, &ternary.then_expr, &ternary.else_expr],
+        )
+    }
+
+    fn visit_expr_this(&mut self, _: &This) -> Self::ExprResult {
+        "this".into()
+    }
+
+    fn visit_expr_variable(&mut self, variable: &Variable) -> Self::ExprResult {
+        variable.name.to_string()
+    }
+
+    fn visit_expr_unary(&mut self, unary: &Unary) -> Self::ExprResult {
+        self.parenthesize(&unary.operator.to_string(), &[&unary.right])
+    }
+}
+
+impl StmtVisitor for AstPrinter {
+    type StmtResult = String;
+
+    fn visit_stmt_block(&mut self, block: &Block) -> Self::StmtResult {
+        let mut result = "(block".to_owned();
+        for stmt in &block.statements {
+            result.push(' ');
+            result.push_str(&self.visit_stmt(stmt));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_stmt_class(&mut self, class: &Class) -> Self::StmtResult {
+        let mut result = format!("(class {}", class.name);
+        if let Some(superclass) = &class.superclass {
+            result.push_str(&format!(" < {}", superclass.name));
+        }
+        for method in &class.methods {
+            result.push(' ');
+            result.push_str(&self.print_function(method));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_stmt_do_while(&mut self, do_while: &DoWhile) -> Self::StmtResult {
+        let body = self.visit_stmt(&do_while.body);
+        let condition = self.visit_expr(&do_while.condition);
+        format!("(do {} while {})", body, condition)
+    }
+
+    fn visit_stmt_expression(&mut self, expression: &Expression) -> Self::StmtResult {
+        self.parenthesize(";", &[&expression.expression])
+    }
+
+    fn visit_stmt_for(&mut self, for_: &For) -> Self::StmtResult {
+        // a missing clause is shown as "()"
+        let initializer = match &for_.initializer {
+            Some(initializer) => self.visit_stmt(initializer),
+            None => "()".into(),
+        };
+        let condition = match &for_.condition {
+            Some(condition) => self.visit_expr(condition),
+            None => "()".into(),
+        };
+        let increment = match &for_.increment {
+            Some(increment) => self.visit_expr(increment),
+            None => "()".into(),
+        };
+        let body = self.visit_stmt(&for_.body);
+        format!("(for {} {} {} {})", initializer, condition, increment, body)
+    }
+
+    fn visit_stmt_for_in(&mut self, for_in: &ForIn) -> Self::StmtResult {
+        let iterable = self.visit_expr(&for_in.iterable);
+        let body = self.visit_stmt(&for_in.body);
+        format!("(for {} in {} {})", for_in.name, iterable, body)
+    }
+
+    fn visit_stmt_function(&mut self, function: &Function) -> Self::StmtResult {
+        self.print_function(function)
+    }
+
+    fn visit_stmt_if(&mut self, if_: &If) -> Self::StmtResult {
+        let condition = self.visit_expr(&if_.condition);
+        let then_branch = self.visit_stmt(&if_.then_branch);
+        match &if_.else_branch {
+            Some(else_branch) => {
+                let else_branch = self.visit_stmt(else_branch);
+                format!("(if {} {} {})", condition, then_branch, else_branch)
+            }
+            None => format!("(if {} {})", condition, then_branch),
+        }
+    }
+
+    fn visit_stmt_print(&mut self, print: &Print) -> Self::StmtResult {
+        let expressions: Vec<&Expr> = print.expressions.iter().collect();
+        self.parenthesize("print", &expressions)
+    }
+
+    fn visit_stmt_return(&mut self, return_: &Return) -> Self::StmtResult {
+        self.parenthesize("return", &[&return_.value])
+    }
+
+    fn visit_stmt_switch(&mut self, switch: &Switch) -> Self::StmtResult {
+        let mut result = format!("(switch {}", self.visit_expr(&switch.subject));
+        for (value, body) in &switch.cases {
+            result.push_str(&format!(" (case {}", self.visit_expr(value)));
+            for stmt in body {
+                result.push(' ');
+                result.push_str(&self.visit_stmt(stmt));
+            }
+            result.push(')');
+        }
+        if let Some(default) = &switch.default {
+            result.push_str(" (default");
+            for stmt in default {
+                result.push(' ');
+                result.push_str(&self.visit_stmt(stmt));
+            }
+            result.push(')');
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_stmt_throw(&mut self, throw: &Throw) -> Self::StmtResult {
+        self.parenthesize("throw", &[&throw.value])
+    }
+
+    fn visit_stmt_try(&mut self, try_: &Try) -> Self::StmtResult {
+        let mut result = "(try".to_owned();
+        for stmt in &try_.body {
+            result.push(' ');
+            result.push_str(&self.visit_stmt(stmt));
+        }
+        result.push_str(&format!(" (catch {}", try_.catch_name));
+        for stmt in &try_.catch_body {
+            result.push(' ');
+            result.push_str(&self.visit_stmt(stmt));
+        }
+        result.push_str("))");
+        result
+    }
+
+    fn visit_stmt_var(&mut self, var: &Var) -> Self::StmtResult {
+        match &var.initializer {
+            Some(initializer) => self.parenthesize(&format!("var {}", var.name), &[initializer]),
+            None => format!("(var {})", var.name),
+        }
+    }
+
+    fn visit_stmt_while(&mut self, while_: &While) -> Self::StmtResult {
+        let condition = self.visit_expr(&while_.condition);
+        let body = self.visit_stmt(&while_.body);
+        format!("(while {} {})", condition, body)
+    }
+}
+
+/// Returns `=`, or the compound assignment operator such as `+=`.
+fn assignment_operator(operator: &Option<TokenKind>) -> String {
+    match operator {
+        Some(operator) => format!("{}=", operator),
+        None => "=".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn print(source: &str) -> String {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        AstPrinter.print_program(&stmts)
+    }
+
+    #[test]
+    fn print_expressions() {
+        assert_eq!(
+            print("print -1 + 2 * (3 - x);"),
+            "(print (+ (- 1) (* 2 (group (- 3 x)))))\n"
+        );
+        assert_eq!(
+            print(r#"a.b = [1, "two"][0] ? {"k": nil} : c;"#),
+            "(; (= (. a b) (?: (index (list 1 \"two\") 0) (map (\"k\" nil)) c)))\n"
+        );
+    }
+
+    #[test]
+    fn print_logical_and_call_expressions() {
+        assert_eq!(print("a or b and !c;"), "(; (or a (and b (! c))))\n");
+        assert_eq!(
+            print("f(1, g())(x).y;"),
+            "(; (. (call (call f 1 (call g)) x) y))\n"
+        );
+    }
+
+    #[test]
+    fn print_for_loop() {
+        assert_eq!(
+            print("for (var i = 0; i < 3; i += 1) print i;"),
+            "(for (var i 0) (< i 3) (+= i 1) (print i))\n"
+        );
+        assert_eq!(print("for (;;) {}"), "(for () () () (block))\n");
+    }
+
+    #[test]
+    fn print_do_while() {
+        assert_eq!(
+            print("do { x = x - 1; } while (x > 0);"),
+            "(do (block (; (= x (- x 1)))) while (> x 0))\n"
+        );
+    }
+
+    #[test]
+    fn print_for_in() {
+        assert_eq!(
+            print("for (x in [1, 2]) print x;"),
+            "(for x in (list 1 2) (print x))\n"
+        );
+    }
+
+    #[test]
+    fn print_switch() {
+        assert_eq!(
+            print("switch (x) { case 1: print 1; case 2: default: print 3; }"),
+            "(switch x (case 1 (print 1)) (case 2) (default (print 3)))\n"
+        );
+    }
+
+    #[test]
+    fn print_exceptions() {
+        assert_eq!(
+            print("try { throw 1; } catch (e) { print e; }"),
+            "(try (throw 1) (catch e (print e)))\n"
+        );
+    }
+
+    #[test]
+    fn print_declarations() {
+        assert_eq!(
+            print("class B < A { init(x) { this.x = x; } }\nfun f([a, b]) { return super.g; }"),
+            "(class B < A (fun init (x) (; (= (. this x) x))))\n\
+             (fun f ([a, b]) (return (. super g)))\n"
+        );
+    }
+}
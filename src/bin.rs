@@ -1,13 +1,54 @@
-use anyhow::Result;
-use lox_lib::{run_file, run_prompt};
+use std::fs::{read_to_string, write};
+use std::io::{stdin, IsTerminal, Read, Write};
+
+use anyhow::anyhow;
+use anyhow::{Context, Result};
+use lox_lib::{format_source, lint, print_ast, render_error, run_prompt, run_timed, Interpreter};
 use structopt::StructOpt;
 
 /// Run a lox script.
 #[derive(StructOpt)]
 struct Cli {
-    /// Path to a lox file.
+    /// Path to a lox file. If omitted, the program is read from stdin when it
+    /// is piped in, and an interactive prompt is started otherwise.
     #[structopt(parse(from_os_str))]
     script: Option<std::path::PathBuf>,
+
+    /// Run the given source code instead of a file.
+    #[structopt(short, long, conflicts_with = "script")]
+    eval: Option<String>,
+
+    /// Print the parsed syntax tree instead of running the program.
+    #[structopt(long)]
+    ast: bool,
+
+    /// Print the program formatted in a consistent style instead of running
+    /// it.
+    #[structopt(long)]
+    fmt: bool,
+
+    /// With --fmt, overwrite the script with the formatted program instead
+    /// of printing it.
+    #[structopt(long, requires_all = &["fmt", "script"])]
+    write: bool,
+
+    /// Print how long scanning, parsing and interpreting took to stderr.
+    #[structopt(long)]
+    time: bool,
+
+    /// Print warnings about local variables that are never read to stderr
+    /// before running the program.
+    #[structopt(long)]
+    warn_unused: bool,
+
+    /// Compute operators whose operands are all literals before running the
+    /// program.
+    #[structopt(long)]
+    fold: bool,
+
+    /// Let the program read and write files with file_read and file_write.
+    #[structopt(long)]
+    allow_fs: bool,
 }
 
 fn main() -> Result<()> {
@@ -15,8 +56,60 @@ fn main() -> Result<()> {
 
     let args = Cli::from_args();
 
-    match args.script {
-        Some(path) => run_file(path).map(|_| ()),
-        None => run_prompt(),
+    let source = match (&args.script, args.eval) {
+        (Some(path), _) => {
+            read_to_string(path).with_context(|| format!("could not read file {:?}", path))?
+        }
+        (None, Some(source)) => source,
+        // only prompt for input when there is someone to type it
+        (None, None) if stdin().is_terminal() && !args.ast && !args.fmt => {
+            if let Some(code) = run_prompt()? {
+                std::process::exit(code);
+            }
+            return Ok(());
+        }
+        (None, None) => {
+            let mut source = String::new();
+            stdin()
+                .read_to_string(&mut source)
+                .with_context(|| "could not read program from stdin")?;
+            source
+        }
+    };
+    if args.ast {
+        let ast = print_ast(&source).map_err(|err| anyhow!(render_error(&err, &source)))?;
+        print!("{}", ast);
+        return Ok(());
+    }
+    if args.fmt {
+        let formatted =
+            format_source(&source).map_err(|err| anyhow!(render_error(&err, &source)))?;
+        match &args.script {
+            Some(path) if args.write => write(path, formatted)
+                .with_context(|| format!("could not write file {:?}", path))?,
+            _ => print!("{}", formatted),
+        }
+        return Ok(());
+    }
+    if args.warn_unused {
+        let warnings = lint(&source).map_err(|err| anyhow!(render_error(&err, &source)))?;
+        for warning in warnings {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+    // stream output as the program runs rather than collecting it
+    let mut interpreter = Interpreter::with_writer(Box::new(std::io::stdout()));
+    interpreter.fold_constants = args.fold;
+    interpreter.allow_fs = args.allow_fs;
+    let timings =
+        run_timed(&mut interpreter, &source).map_err(|err| anyhow!(render_error(&err, &source)))?;
+    if args.time {
+        eprintln!("{}", timings);
+    }
+    if let Some(code) = interpreter.exit_code {
+        // exiting skips destructors, so flush any buffered output first
+        std::io::stdout().flush()?;
+        std::process::exit(code);
     }
+    Ok(())
 }
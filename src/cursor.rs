@@ -41,7 +41,9 @@ impl<T: Clone> Cursor<T> {
     //     self.index = index;
     // }
 
-    // pub fn look_ahead(&self, n: usize) -> Option<&Token> {
-    //     self.stream[self.index..].get(n).map(|token| token)
-    // }
+    /// Returns the item `n` places after the next one, without consuming
+    /// anything.
+    pub fn look_ahead(&self, n: usize) -> Option<&T> {
+        self.stream[self.index..].get(n)
+    }
 }
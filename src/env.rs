@@ -1,36 +1,106 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
 use generational_arena::Index;
 
-#[derive(Debug, Clone, PartialEq, Default)]
+use crate::symbol::Symbol;
+
+/// A single scope of variables. Scopes are shared (rather than copied) by
+/// everything that refers to them, such as the closures defined inside them,
+/// so a variable defined in a scope is visible to all of its users.
+#[derive(Debug, PartialEq, Default)]
 pub struct Environment {
-    enclosing: Option<Box<Environment>>,
-    values: HashMap<String, Index>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+    values: HashMap<Symbol, Index>,
 }
 
 impl Environment {
-    pub fn insert(&self, name: String, value: Index) -> Environment {
-        let mut new_env = self.clone();
-        new_env.values.insert(name, value);
-        new_env
+    /// Creates a new, empty scope nested inside `enclosing`.
+    pub(crate) fn enclose(enclosing: &Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        Rc::new(RefCell::new(Environment {
+            enclosing: Some(enclosing.clone()),
+            ..Default::default()
+        }))
     }
 
-    pub fn enclose(&self) -> Environment {
-        Environment {
-            enclosing: Some(Box::new(self.clone())),
-            ..Default::default()
-        }
+    /// Binds `name` to `value`, returning the variable it previously
+    /// referred to in this scope, if any.
+    pub(crate) fn define(&mut self, name: Symbol, value: Index) -> Option<Index> {
+        self.values.insert(name, value)
+    }
+
+    /// Consumes the scope, returning its enclosing scope and the variables
+    /// defined in it.
+    pub(crate) fn into_parts(self) -> (Option<Rc<RefCell<Environment>>>, Vec<Index>) {
+        (self.enclosing, self.values.into_values().collect())
     }
 
-    pub fn get(&self, name: &String) -> Option<Index> {
-        if let Some(idx) = self.values.get(name) {
+    /// Returns the variables defined in this scope alone.
+    pub(crate) fn indices(&self) -> Vec<Index> {
+        self.values.values().copied().collect()
+    }
+
+    pub(crate) fn enclosing(&self) -> Option<&Rc<RefCell<Environment>>> {
+        self.enclosing.as_ref()
+    }
+
+    pub(crate) fn get(&self, name: Symbol) -> Option<Index> {
+        if let Some(idx) = self.values.get(&name) {
             return Some(*idx);
         }
 
         if let Some(enclosing) = &self.enclosing {
-            Some(enclosing.get(name)?)
+            enclosing.borrow().get(name)
         } else {
             None
         }
     }
+
+    /// Looks up `name` in the scope `depth` levels out from this one,
+    /// without searching any other scopes.
+    pub(crate) fn get_at(&self, depth: usize, name: Symbol) -> Option<Index> {
+        if depth == 0 {
+            return self.values.get(&name).copied();
+        }
+        self.enclosing.as_ref()?.borrow().get_at(depth - 1, name)
+    }
+
+    /// Returns the names of all variables visible from this scope, innermost
+    /// scope first and sorted within each scope. A name that is shadowed by
+    /// an inner scope is only listed for the inner one.
+    pub fn names(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut names = vec![];
+        let mut scope_names = self.scope_names();
+        let mut enclosing = self.enclosing.clone();
+        loop {
+            for name in scope_names {
+                if seen.insert(name) {
+                    names.push(name.to_string());
+                }
+            }
+            let env = match enclosing {
+                Some(env) => env,
+                None => return names,
+            };
+            scope_names = env.borrow().scope_names();
+            enclosing = env.borrow().enclosing.clone();
+        }
+    }
+
+    /// Returns the names defined in this scope alone, sorted.
+    fn scope_names(&self) -> Vec<Symbol> {
+        let mut names: Vec<Symbol> = self.values.keys().copied().collect();
+        names.sort_by_key(|name| name.as_str());
+        names
+    }
+
+    /// Looks up `name` in the outermost (global) scope.
+    pub(crate) fn get_global(&self, name: Symbol) -> Option<Index> {
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get_global(name),
+            None => self.values.get(&name).copied(),
+        }
+    }
 }
@@ -0,0 +1,247 @@
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::parser::ParseError;
+use crate::scanner::ScanError;
+use crate::span::Span;
+
+/// An error in a Lox program, tagged with the phase that found it and where
+/// in the source it happened. Errors returned by `run` and friends can be
+/// downcast to this type to inspect them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoxError {
+    /// The source contains text that isn't a valid token.
+    Scan { message: String, span: Span },
+    /// The tokens don't form a valid program.
+    Parse { message: String, span: Span },
+    /// The program failed while running. The span is that of the innermost
+    /// expression or statement being executed, and the trace lists the
+    /// functions being called at the time, innermost first, like
+    /// "at f (line 3)", with recursive calls and any past the first 20
+    /// summarized rather than listed.
+    Runtime {
+        message: String,
+        span: Span,
+        trace: Vec<String>,
+    },
+}
+
+impl LoxError {
+    pub fn message(&self) -> &str {
+        match self {
+            LoxError::Scan { message, .. }
+            | LoxError::Parse { message, .. }
+            | LoxError::Runtime { message, .. } => message,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            LoxError::Scan { span, .. }
+            | LoxError::Parse { span, .. }
+            | LoxError::Runtime { span, .. } => *span,
+        }
+    }
+
+    /// The functions being called when the error happened, innermost first.
+    /// Only runtime errors have one.
+    pub fn trace(&self) -> &[String] {
+        match self {
+            LoxError::Runtime { trace, .. } => trace,
+            _ => &[],
+        }
+    }
+
+    /// The line the error happened on, counting from 1.
+    pub fn line(&self) -> u32 {
+        self.span().start_line
+    }
+
+    /// Renders the error followed by the line of `source` it happened on,
+    /// with carets under the offending text, like so:
+    ///
+    /// ```text
+    /// Expected ';' after value on line 1:7
+    ///   |
+    /// 1 | print 1 print 2;
+    ///   |         ^^^^^
+    /// ```
+    ///
+    /// followed by the trace, one function per line. Only the message is
+    /// rendered if the position isn't in `source`.
+    pub fn render(&self, source: &str) -> String {
+        let span = self.span();
+        // "\r\n", "\n" and a lone "\r" each end a line, as in the scanner
+        let source = source.replace("\r\n", "\n");
+        let line = match (span.start_line as usize)
+            .checked_sub(1)
+            .and_then(|index| source.split(['\n', '\r']).nth(index))
+        {
+            Some(line) => line,
+            None => return self.to_string(),
+        };
+        let start = span.start_col.saturating_sub(1) as usize;
+        let width = if span.end_line == span.start_line && span.end_col > span.start_col {
+            (span.end_col - span.start_col) as usize
+        } else {
+            1
+        };
+        // keep tabs, so that the carets line up with the text above them
+        let indent: String = line
+            .chars()
+            .take(start)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let number = span.start_line.to_string();
+        let gutter = " ".repeat(number.len());
+        let trace: String = self
+            .trace()
+            .iter()
+            .map(|frame| format!("\n  {}", frame))
+            .collect();
+        format!(
+            "{}\n{} |\n{} | {}\n{} | {}{}{}",
+            self,
+            gutter,
+            number,
+            line,
+            gutter,
+            indent,
+            "^".repeat(width),
+            trace
+        )
+    }
+
+    /// Merges several errors from the same phase into one, whose message
+    /// lists all of them (one per line) and whose span is the first one's.
+    /// Returns `None` if there are no errors.
+    pub(crate) fn combine(errors: Vec<LoxError>) -> Option<LoxError> {
+        let message = errors.iter().join("\n");
+        let mut first = errors.into_iter().next()?;
+        match &mut first {
+            LoxError::Scan { message: m, .. }
+            | LoxError::Parse { message: m, .. }
+            | LoxError::Runtime { message: m, .. } => *m = message,
+        }
+        Some(first)
+    }
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+impl From<ScanError> for LoxError {
+    fn from(err: ScanError) -> Self {
+        LoxError::Scan {
+            message: err.message,
+            span: err.span,
+        }
+    }
+}
+
+impl From<ParseError> for LoxError {
+    fn from(err: ParseError) -> Self {
+        LoxError::Parse {
+            message: err.message,
+            span: err.span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run;
+
+    fn run_err(source: &str) -> LoxError {
+        let err = run(source).unwrap_err();
+        err.downcast::<LoxError>().unwrap()
+    }
+
+    #[test]
+    fn render_snippet() {
+        let source = "var a = 1;\nprint a print 2;";
+        assert_eq!(
+            run_err(source).render(source),
+            "Expected ';' after value on line 2:7\n  |\n2 | print a print 2;\n  |         ^^^^^"
+        );
+        let source = "\tvar s = #;";
+        assert_eq!(
+            run_err(source).render(source),
+            "unexpected character '#' on line 1:10\n  |\n1 | \tvar s = #;\n  | \t        ^"
+        );
+        let err = LoxError::Runtime {
+            message: "oops".into(),
+            span: Span::default(),
+            trace: vec![],
+        };
+        assert_eq!(err.render("print 1;"), "oops");
+    }
+
+    #[test]
+    fn scan_errors() {
+        let err = run_err("var a = 1;\nvar b = @;");
+        assert!(matches!(err, LoxError::Scan { .. }));
+        assert_eq!(err.span(), Span::new(2, 9, 2, 10));
+        assert_eq!(err.message(), "unexpected character '@' on line 2:9");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = run_err("print 1;\n\nprint 2\nprint 3;");
+        assert!(matches!(err, LoxError::Parse { .. }));
+        assert_eq!(err.line(), 4);
+
+        // every error is reported, but the position is the first one's
+        let err = run_err("var = 1;\nvar = 2;");
+        assert_eq!(err.line(), 1);
+        assert_eq!(err.message().lines().count(), 2);
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let err = run_err("var x = 1;\nfun f() {\n  return -\"a\";\n}\nprint f();");
+        assert!(matches!(err, LoxError::Runtime { .. }));
+        assert_eq!(err.line(), 3);
+
+        let err = run_err("print 1;\nprint y;");
+        assert!(matches!(err, LoxError::Runtime { .. }));
+        assert_eq!(err.line(), 2);
+        assert!(err.trace().is_empty());
+    }
+
+    #[test]
+    fn runtime_error_traces() {
+        let source = "fun inner() { return missing; }\nfun outer() {\n  return inner() + 1;\n}\nprint outer();";
+        let err = run_err(source);
+        assert_eq!(err.trace(), ["at inner (line 3)", "at outer (line 5)"]);
+        assert_eq!(
+            err.render(source),
+            "Undefined variable missing on line 1:22.\n  |\n1 | fun inner() { return missing; }\n  |                      ^^^^^^^\n  at inner (line 3)\n  at outer (line 5)"
+        );
+
+        // a tail call takes the place of its caller
+        let err = run_err("fun f() { return g(); }\nfun g() { return nil(); }\nf();");
+        assert_eq!(err.trace(), ["at g (line 1)"]);
+
+        // a value thrown and never caught has the trace from where it was
+        // thrown
+        let err = run_err("fun f() { throw \"oops\"; }\nfun g() {\n  f();\n}\ng();");
+        assert_eq!(err.message(), "Uncaught exception oops on line 1:11.");
+        assert_eq!(err.trace(), ["at f (line 3)", "at g (line 5)"]);
+
+        // long traces are cut short
+        let err = run_err(
+            "fun a(n) { if (n == 0) return nil(); b(n - 1); }\nfun b(n) {\n  a(n);\n}\na(30);",
+        );
+        assert_eq!(err.trace().len(), 21);
+        assert_eq!(err.trace()[..2], ["at a (line 3)", "at b (line 1)"]);
+        assert_eq!(err.trace()[20], "... 41 more");
+    }
+}
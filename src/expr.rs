@@ -1,42 +1,149 @@
+use std::cell::Cell;
+
+use crate::span::Span;
+use crate::symbol::Symbol;
 use crate::token::TokenKind;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
     Assign(Assign),
     Binary(Binary),
     Call(Call),
+    Get(Get),
     Grouping(Grouping),
-    Literal(Literal),
+    Index(Index),
+    Interpolation(Interpolation),
+    ListLiteral(ListLiteral),
+    Literal(LiteralExpr),
     Logical(Logical),
+    MapLiteral(MapLiteral),
+    Set(Set),
+    SetIndex(SetIndex),
+    Super(Super),
+    Ternary(Ternary),
+    This(This),
     Variable(Variable),
     Unary(Unary),
 }
 
+impl Expr {
+    /// Returns the region of source code the expression was parsed from.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Assign(assign) => assign.span,
+            Expr::Binary(binary) => binary.span,
+            Expr::Call(call) => call.span,
+            Expr::Get(get) => get.span,
+            Expr::Grouping(grouping) => grouping.span,
+            Expr::Index(index) => index.span,
+            Expr::Interpolation(interpolation) => interpolation.span,
+            Expr::ListLiteral(list_literal) => list_literal.span,
+            Expr::Literal(literal) => literal.span,
+            Expr::Logical(logical) => logical.span,
+            Expr::MapLiteral(map_literal) => map_literal.span,
+            Expr::Set(set) => set.span,
+            Expr::SetIndex(set_index) => set_index.span,
+            Expr::Super(super_) => super_.span,
+            Expr::Ternary(ternary) => ternary.span,
+            Expr::This(this) => this.span,
+            Expr::Variable(variable) => variable.span,
+            Expr::Unary(unary) => unary.span,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Assign {
-    pub name: String,
+    pub name: Symbol,
+    /// The operator of a compound assignment such as `name += value`, which
+    /// combines the variable with the value.
+    pub operator: Option<TokenKind>,
     pub value: Box<Expr>,
+    /// The number of scopes between this reference and the variable's
+    /// declaration, filled in by the resolver. `None` means the variable is
+    /// global.
+    pub depth: Cell<Option<usize>>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Binary {
     pub left: Box<Expr>,
     pub operator: TokenKind,
     pub right: Box<Expr>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Call {
     pub callee: Box<Expr>,
     pub arguments: Vec<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Get {
+    pub object: Box<Expr>,
+    pub name: Symbol,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Grouping {
     pub expression: Box<Expr>,
+    pub span: Span,
+}
+
+/// An element lookup, `object[index]`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Index {
+    pub object: Box<Expr>,
+    pub index: Box<Expr>,
+    pub span: Span,
+}
+
+/// A string literal with expressions embedded in it, such as
+/// `"hello ${name}"`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Interpolation {
+    pub parts: Vec<StringPart>,
+    pub span: Span,
+}
+
+/// A piece of an interpolated string.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StringPart {
+    Text(String),
+    Expr(Expr),
+}
+
+/// A list literal such as `[1, 2, 3]`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ListLiteral {
+    pub elements: Vec<Expr>,
+    pub span: Span,
+}
+
+/// A number, string, boolean or `nil` written directly in the source.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LiteralExpr {
+    pub value: Literal,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Literal {
     Number(f64),
     String(String),
@@ -45,19 +152,86 @@ pub enum Literal {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Logical {
     pub left: Box<Expr>,
     pub operator: TokenKind,
     pub right: Box<Expr>,
+    pub span: Span,
+}
+
+/// A map literal such as `{"a": 1, "b": 2}`, as a list of key and value
+/// expressions.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MapLiteral {
+    pub entries: Vec<(Expr, Expr)>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Set {
+    pub object: Box<Expr>,
+    pub name: Symbol,
+    /// The operator of a compound assignment such as `object.name += value`,
+    /// which combines the property with the value.
+    pub operator: Option<TokenKind>,
+    pub value: Box<Expr>,
+    pub span: Span,
+}
+
+/// An assignment to an element, `object[index] = value`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SetIndex {
+    pub object: Box<Expr>,
+    pub index: Box<Expr>,
+    /// The operator of a compound assignment such as `object[index] += value`,
+    /// which combines the element with the value.
+    pub operator: Option<TokenKind>,
+    pub value: Box<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Super {
+    pub method: Symbol,
+    pub span: Span,
+}
+
+/// A conditional expression, `condition ? then_expr : else_expr`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ternary {
+    pub condition: Box<Expr>,
+    pub then_expr: Box<Expr>,
+    pub else_expr: Box<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct This {
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Variable {
-    pub name: String,
+    pub name: Symbol,
+    /// The number of scopes between this reference and the variable's
+    /// declaration, filled in by the resolver. `None` means the variable is
+    /// global.
+    pub depth: Cell<Option<usize>>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Unary {
     pub operator: TokenKind,
     pub right: Box<Expr>,
+    pub span: Span,
 }
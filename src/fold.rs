@@ -0,0 +1,179 @@
+use crate::{
+    expr::{Binary, Expr, Literal, LiteralExpr, Logical, Unary},
+    interpreter::{bitwise, to_integer},
+    stmt::Stmt,
+    token::TokenKind,
+    visit_mut::{self, VisitMut},
+};
+
+/// Replaces operators whose operands are all literals, such as `2 + 3 * 4`,
+/// with the value they compute. Operations that would fail at runtime (e.g.
+/// dividing by zero when that is an error) are left alone, so the program
+/// still reports the error when it reaches them.
+pub fn fold(stmts: &mut [Stmt], ieee_division: bool) {
+    let mut folder = Folder { ieee_division };
+    for stmt in stmts {
+        folder.visit_stmt_mut(stmt);
+    }
+}
+
+struct Folder {
+    ieee_division: bool,
+}
+
+impl Folder {
+    fn fold_binary(&self, binary: &Binary) -> Option<Literal> {
+        let (left, right) = match (&*binary.left, &*binary.right) {
+            (Expr::Literal(left), Expr::Literal(right)) => (&left.value, &right.value),
+            _ => return None,
+        };
+        match binary.operator {
+            TokenKind::BangEqual => return Some(Literal::Bool(left != right)),
+            TokenKind::EqualEqual => return Some(Literal::Bool(left == right)),
+            // joining a string with anything else depends on how the
+            // interpreter formats numbers, so only strings are joined here
+            TokenKind::Plus => {
+                if let (Literal::String(x), Literal::String(y)) = (left, right) {
+                    return Some(Literal::String(format!("{}{}", x, y)));
+                }
+            }
+            _ => {}
+        }
+
+        let (x, y) = match (left, right) {
+            (Literal::Number(x), Literal::Number(y)) => (*x, *y),
+            _ => return None,
+        };
+        let result = match binary.operator {
+            TokenKind::Greater => Literal::Bool(x > y),
+            TokenKind::GreaterEqual => Literal::Bool(x >= y),
+            TokenKind::Less => Literal::Bool(x < y),
+            TokenKind::LessEqual => Literal::Bool(x <= y),
+            TokenKind::Minus => Literal::Number(x - y),
+            TokenKind::Plus => Literal::Number(x + y),
+            TokenKind::Slash if y != 0.0 || self.ieee_division => Literal::Number(x / y),
+            TokenKind::Star => Literal::Number(x * y),
+            TokenKind::StarStar => Literal::Number(x.powf(y)),
+            TokenKind::Ampersand
+            | TokenKind::Pipe
+            | TokenKind::Caret
+            | TokenKind::LessLess
+            | TokenKind::GreaterGreater => Literal::Number(bitwise(&binary.operator, x, y).ok()?),
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    fn fold_logical(&self, logical: &Logical) -> Option<Literal> {
+        let (left, right) = match (&*logical.left, &*logical.right) {
+            (Expr::Literal(left), Expr::Literal(right)) => (&left.value, &right.value),
+            _ => return None,
+        };
+        let result = match (&logical.operator, is_truthy(left)) {
+            (TokenKind::Or, true) | (TokenKind::And, false) => left,
+            (TokenKind::Or, false) | (TokenKind::And, true) => right,
+            (TokenKind::QuestionQuestion, _) if *left != Literal::Nil => left,
+            (TokenKind::QuestionQuestion, _) => right,
+            _ => return None,
+        };
+        Some(result.clone())
+    }
+
+    fn fold_unary(&self, unary: &Unary) -> Option<Literal> {
+        let right = match &*unary.right {
+            Expr::Literal(right) => &right.value,
+            _ => return None,
+        };
+        match (&unary.operator, right) {
+            (TokenKind::Bang, right) => Some(Literal::Bool(!is_truthy(right))),
+            (TokenKind::Minus, Literal::Number(x)) => Some(Literal::Number(-x)),
+            (TokenKind::Tilde, Literal::Number(x)) => {
+                Some(Literal::Number(!to_integer(*x).ok()? as f64))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl VisitMut for Folder {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        // fold the operands first, so nested operators fold from the inside
+        // out
+        visit_mut::visit_expr_mut(self, e);
+        let folded = match e {
+            Expr::Binary(binary) => self.fold_binary(binary),
+            Expr::Grouping(grouping) => match &*grouping.expression {
+                Expr::Literal(literal) => Some(literal.value.clone()),
+                _ => None,
+            },
+            Expr::Logical(logical) => self.fold_logical(logical),
+            Expr::Unary(unary) => self.fold_unary(unary),
+            _ => None,
+        };
+        if let Some(value) = folded {
+            *e = Expr::Literal(LiteralExpr {
+                value,
+                span: e.span(),
+            });
+        }
+    }
+}
+
+/// Matches the interpreter's notion of truthiness, in which `nil`, `false`
+/// and `0` are false.
+fn is_truthy(literal: &Literal) -> bool {
+    match literal {
+        Literal::Bool(x) => *x,
+        Literal::Nil => false,
+        Literal::Number(x) => *x != 0.0,
+        Literal::String(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast_printer::AstPrinter, parser::Parser, scanner::Scanner};
+
+    fn fold_source(source: &str, ieee_division: bool) -> String {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let mut stmts = Parser::new(tokens).parse().unwrap();
+        fold(&mut stmts, ieee_division);
+        AstPrinter.print_program(&stmts)
+    }
+
+    #[test]
+    fn fold_literal_operators() {
+        assert_eq!(fold_source("print 2 + 3 * 4;", false), "(print 14)\n");
+        assert_eq!(fold_source("print (1 + 1) ** 3;", false), "(print 8)\n");
+        assert_eq!(fold_source("print !(1 < 2);", false), "(print false)\n");
+        assert_eq!(
+            fold_source("print true and false;", false),
+            "(print false)\n"
+        );
+        assert_eq!(
+            fold_source("print nil or \"a\" + \"b\";", false),
+            "(print \"ab\")\n"
+        );
+        assert_eq!(fold_source("print 1 == \"1\";", false), "(print false)\n");
+        assert_eq!(fold_source("print ~(6 & 3);", false), "(print -3)\n");
+        assert_eq!(fold_source("print nil ?? 0 ?? 1;", false), "(print 0)\n");
+    }
+
+    #[test]
+    fn keep_operators_with_other_operands() {
+        assert_eq!(fold_source("print x + 2 * 3;", false), "(print (+ x 6))\n");
+        assert_eq!(
+            fold_source("print \"a\" + 1;", false),
+            "(print (+ \"a\" 1))\n"
+        );
+        assert_eq!(fold_source("print -\"a\";", false), "(print (- \"a\"))\n");
+        assert_eq!(fold_source("print 1 << 64;", false), "(print (<< 1 64))\n");
+    }
+
+    #[test]
+    fn keep_division_by_zero() {
+        assert_eq!(fold_source("print 1 / 0;", false), "(print (/ 1 0))\n");
+        assert_eq!(fold_source("print 1 / 0;", true), "(print inf)\n");
+    }
+}
@@ -0,0 +1,792 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::{
+    expr::{
+        Assign, Binary, Call, Expr, Get, Grouping, Index, Interpolation, ListLiteral, Literal,
+        LiteralExpr, Logical, MapLiteral, Set, SetIndex, StringPart, Super, Ternary, This, Unary,
+        Variable,
+    },
+    span::Span,
+    stmt::{
+        Block, Class, DoWhile, Expression, For, ForIn, Function, If, Print, Return, Stmt, Switch,
+        Throw, Try, Var, While,
+    },
+    token::{Token, TokenKind},
+    visitor::Visit,
+};
+
+const INDENT: &str = "  ";
+
+/// Renders a program as Lox source in a consistent style: one statement per
+/// line, two-space indentation, and single spaces around operators.
+///
+/// The output parses back into the same syntax tree, so formatting it again
+/// changes nothing. Comments are not part of the syntax tree, so they are
+/// passed in as `comments`, the `TokenKind::Comment` tokens scanned from the
+/// program's source. A comment that starts on the last line of a statement
+/// follows it on that line, and any other comment goes on a line of its own
+/// ahead of the statement after it, or at the end of the block it is in.
+pub fn format(stmts: &[Stmt], comments: Vec<Token>) -> String {
+    let mut formatter = Formatter {
+        comments: comments
+            .into_iter()
+            .filter_map(|token| match token.kind {
+                TokenKind::Comment(text) => Some((token.span, text)),
+                _ => None,
+            })
+            .collect(),
+        ..Formatter::default()
+    };
+    let end = (u32::MAX, u32::MAX);
+    for stmt in stmts {
+        formatter.line(stmt.span(), end, |formatter| formatter.visit_stmt(stmt));
+        formatter.out.push('\n');
+    }
+    while let Some(text) = formatter.take_comment(|_| true) {
+        formatter.out.push_str(&text);
+        formatter.out.push('\n');
+    }
+    formatter.out
+}
+
+// syntax trees display as the source `format` would give them, so that they
+// read naturally in messages; a statement containing others spans several
+// lines, but has no newline at the end
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut formatter = Formatter::default();
+        formatter.visit_expr(self);
+        f.write_str(&formatter.out)
+    }
+}
+
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut formatter = Formatter::default();
+        formatter.visit_stmt(self);
+        f.write_str(&formatter.out)
+    }
+}
+
+/// How tightly each kind of expression binds, loosest first, following the
+/// parser's grammar.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    Assignment,
+    Ternary,
+    Coalesce,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
+    Term,
+    Factor,
+    Power,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    fn of(expr: &Expr) -> Precedence {
+        match expr {
+            Expr::Assign(_) | Expr::Set(_) | Expr::SetIndex(_) => Precedence::Assignment,
+            Expr::Ternary(_) => Precedence::Ternary,
+            Expr::Logical(logical) => Precedence::of_operator(&logical.operator),
+            Expr::Binary(binary) => Precedence::of_operator(&binary.operator),
+            Expr::Unary(_) => Precedence::Unary,
+            // a negative number is written with a minus sign, so it only
+            // comes out of optimizations such as constant folding
+            Expr::Literal(LiteralExpr {
+                value: Literal::Number(x),
+                ..
+            }) if x.is_sign_negative() => Precedence::Unary,
+            Expr::Call(_) | Expr::Get(_) | Expr::Index(_) => Precedence::Call,
+            _ => Precedence::Primary,
+        }
+    }
+
+    fn of_operator(operator: &TokenKind) -> Precedence {
+        match operator {
+            TokenKind::QuestionQuestion => Precedence::Coalesce,
+            TokenKind::Or => Precedence::Or,
+            TokenKind::And => Precedence::And,
+            TokenKind::BangEqual | TokenKind::EqualEqual => Precedence::Equality,
+            TokenKind::Greater
+            | TokenKind::GreaterEqual
+            | TokenKind::Less
+            | TokenKind::LessEqual
+            | TokenKind::Is => Precedence::Comparison,
+            TokenKind::Pipe => Precedence::BitOr,
+            TokenKind::Caret => Precedence::BitXor,
+            TokenKind::Ampersand => Precedence::BitAnd,
+            TokenKind::LessLess | TokenKind::GreaterGreater => Precedence::Shift,
+            TokenKind::Minus | TokenKind::Plus => Precedence::Term,
+            TokenKind::Slash | TokenKind::Star => Precedence::Factor,
+            _ => Precedence::Power,
+        }
+    }
+
+    /// The next tighter level, which the right operand of a left-associative
+    /// operator must have.
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::Assignment => Precedence::Ternary,
+            Precedence::Ternary => Precedence::Coalesce,
+            Precedence::Coalesce => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::BitOr,
+            Precedence::BitOr => Precedence::BitXor,
+            Precedence::BitXor => Precedence::BitAnd,
+            Precedence::BitAnd => Precedence::Shift,
+            Precedence::Shift => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Power,
+            Precedence::Power => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+/// A line and column in the source, which compare in the order they
+/// appear.
+type Position = (u32, u32);
+
+fn start(span: Span) -> Position {
+    (span.start_line, span.start_col)
+}
+
+fn end(span: Span) -> Position {
+    (span.end_line, span.end_col)
+}
+
+#[derive(Default)]
+struct Formatter {
+    out: String,
+    depth: usize,
+    // the comments not written yet, in the order they appear in the source
+    comments: VecDeque<(Span, String)>,
+}
+
+impl Formatter {
+    /// Writes `expr`, in parentheses if it binds more loosely than `min`.
+    fn expr(&mut self, expr: &Expr, min: Precedence) {
+        if Precedence::of(expr) < min {
+            self.out.push('(');
+            self.visit_expr(expr);
+            self.out.push(')');
+        } else {
+            self.visit_expr(expr);
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr, Precedence::Assignment);
+        }
+    }
+
+    /// Writes the text of a string literal, escaping characters that can't
+    /// appear in it as they are.
+    fn string_text(&mut self, text: &str) {
+        let mut chars = text.chars().peekable();
+        while let Some(char) = chars.next() {
+            match char {
+                '\n' => self.out.push_str("\\n"),
+                '\t' => self.out.push_str("\\t"),
+                '\r' => self.out.push_str("\\r"),
+                '\0' => self.out.push_str("\\0"),
+                '\\' => self.out.push_str("\\\\"),
+                '"' => self.out.push_str("\\\""),
+                // a "${" would start an embedded expression
+                '$' if chars.peek() == Some(&'{') => self.out.push_str("\\$"),
+                char => self.out.push(char),
+            }
+        }
+    }
+
+    fn binary(&mut self, left: &Expr, operator: &TokenKind, right: &Expr) {
+        let precedence = Precedence::of_operator(operator);
+        // exponentiation is right-associative, and its left operand can't be
+        // another power
+        let (left_min, right_min) = match precedence {
+            Precedence::Power => (Precedence::Unary, Precedence::Power),
+            _ => (precedence, precedence.next()),
+        };
+        self.expr(left, left_min);
+        self.out.push_str(&format!(" {} ", operator));
+        self.expr(right, right_min);
+    }
+
+    /// Writes `=`, or the compound assignment operator such as `+=`.
+    fn assignment_operator(&mut self, operator: &Option<TokenKind>) {
+        match operator {
+            Some(operator) => self.out.push_str(&format!("{}= ", operator)),
+            None => self.out.push_str("= "),
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.out.push('\n');
+        self.out.push_str(&INDENT.repeat(self.depth));
+    }
+
+    /// Removes and returns the text of the next comment if its span
+    /// satisfies `pred`.
+    fn take_comment(&mut self, pred: impl FnOnce(Span) -> bool) -> Option<String> {
+        match self.comments.front() {
+            Some((span, _)) if pred(*span) => self.comments.pop_front().map(|(_, text)| text),
+            _ => None,
+        }
+    }
+
+    /// Writes the code at `span` using `write`, preceded by the comments
+    /// before it and followed by the comments on its last line. Comments at
+    /// or past `limit`, the end of the enclosing block, are left for later.
+    fn line(&mut self, span: Span, limit: Position, write: impl FnOnce(&mut Self)) {
+        while let Some(text) = self.take_comment(|comment| start(comment) < start(span)) {
+            self.out.push_str(&text);
+            self.new_line();
+        }
+        write(self);
+        while let Some(text) = self
+            .take_comment(|comment| comment.start_line <= span.end_line && start(comment) < limit)
+        {
+            self.out.push(' ');
+            self.out.push_str(&text);
+        }
+    }
+
+    /// Writes the comments before `limit` on lines of their own, such as
+    /// those after the last statement of a block.
+    fn closing_comments(&mut self, limit: Position) {
+        while let Some(text) = self.take_comment(|comment| start(comment) < limit) {
+            self.new_line();
+            self.out.push_str(&text);
+        }
+    }
+
+    /// Writes each statement on its own line, along with the comments
+    /// before `limit`, without the braces around them.
+    fn statements(&mut self, stmts: &[Stmt], limit: Position) {
+        for stmt in stmts {
+            self.new_line();
+            self.line(stmt.span(), limit, |formatter| formatter.visit_stmt(stmt));
+        }
+        self.closing_comments(limit);
+    }
+
+    /// Writes statements between braces, each on its own line. `limit` is
+    /// where the block ends in the source.
+    fn block(&mut self, stmts: &[Stmt], limit: Position) {
+        self.out.push('{');
+        self.depth += 1;
+        self.statements(stmts, limit);
+        self.depth -= 1;
+        self.new_line();
+        self.out.push('}');
+    }
+
+    /// Writes a function's name, parameters and body, as in a declaration
+    /// but without the `fun` keyword, which methods leave out.
+    fn function(&mut self, function: &Function) {
+        let params: Vec<String> = function
+            .params
+            .iter()
+            .map(|param| param.to_string())
+            .collect();
+        self.out
+            .push_str(&format!("{}({}) ", function.name, params.join(", ")));
+        self.block(&function.body, end(function.span));
+    }
+}
+
+impl<'ast> Visit<'ast> for Formatter {
+    fn visit_expr_assign(&mut self, e: &'ast Assign) {
+        self.out.push_str(&format!("{} ", e.name));
+        self.assignment_operator(&e.operator);
+        self.expr(&e.value, Precedence::Assignment);
+    }
+
+    fn visit_expr_binary(&mut self, e: &'ast Binary) {
+        self.binary(&e.left, &e.operator, &e.right);
+    }
+
+    fn visit_expr_call(&mut self, e: &'ast Call) {
+        self.expr(&e.callee, Precedence::Call);
+        self.out.push('(');
+        self.exprs(&e.arguments);
+        self.out.push(')');
+    }
+
+    fn visit_expr_get(&mut self, e: &'ast Get) {
+        self.expr(&e.object, Precedence::Call);
+        self.out.push_str(&format!(".{}", e.name));
+    }
+
+    fn visit_expr_grouping(&mut self, e: &'ast Grouping) {
+        self.out.push('(');
+        self.expr(&e.expression, Precedence::Assignment);
+        self.out.push(')');
+    }
+
+    fn visit_expr_index(&mut self, e: &'ast Index) {
+        self.expr(&e.object, Precedence::Call);
+        self.out.push('[');
+        self.expr(&e.index, Precedence::Assignment);
+        self.out.push(']');
+    }
+
+    fn visit_expr_interpolation(&mut self, e: &'ast Interpolation) {
+        self.out.push('"');
+        for part in &e.parts {
+            match part {
+                StringPart::Text(text) => self.string_text(text),
+                StringPart::Expr(expr) => {
+                    self.out.push_str("${");
+                    self.expr(expr, Precedence::Assignment);
+                    self.out.push('}');
+                }
+            }
+        }
+        self.out.push('"');
+    }
+
+    fn visit_expr_list_literal(&mut self, e: &'ast ListLiteral) {
+        self.out.push('[');
+        self.exprs(&e.elements);
+        self.out.push(']');
+    }
+
+    fn visit_expr_literal(&mut self, e: &'ast LiteralExpr) {
+        match &e.value {
+            Literal::Number(x) => self.out.push_str(&x.to_string()),
+            Literal::String(x) => {
+                self.out.push('"');
+                self.string_text(x);
+                self.out.push('"');
+            }
+            Literal::Bool(x) => self.out.push_str(&x.to_string()),
+            Literal::Nil => self.out.push_str("nil"),
+        }
+    }
+
+    fn visit_expr_logical(&mut self, e: &'ast Logical) {
+        self.binary(&e.left, &e.operator, &e.right);
+    }
+
+    fn visit_expr_map_literal(&mut self, e: &'ast MapLiteral) {
+        self.out.push('{');
+        for (i, (key, value)) in e.entries.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(key, Precedence::Assignment);
+            self.out.push_str(": ");
+            self.expr(value, Precedence::Assignment);
+        }
+        self.out.push('}');
+    }
+
+    fn visit_expr_set(&mut self, e: &'ast Set) {
+        self.expr(&e.object, Precedence::Call);
+        self.out.push_str(&format!(".{} ", e.name));
+        self.assignment_operator(&e.operator);
+        self.expr(&e.value, Precedence::Assignment);
+    }
+
+    fn visit_expr_set_index(&mut self, e: &'ast SetIndex) {
+        self.expr(&e.object, Precedence::Call);
+        self.out.push('[');
+        self.expr(&e.index, Precedence::Assignment);
+        self.out.push_str("] ");
+        self.assignment_operator(&e.operator);
+        self.expr(&e.value, Precedence::Assignment);
+    }
+
+    fn visit_expr_super(&mut self, e: &'ast Super) {
+        self.out.push_str(&format!("super.{}", e.method));
+    }
+
+    fn visit_expr_ternary(&mut self, e: &'ast Ternary) {
+        self.expr(&e.condition, Precedence::Or);
+        self.out.push_str(" ? ");
+        self.expr(&e.then_expr, Precedence::Assignment);
+        self.out.push_str(" : ");
+        self.expr(&e.else_expr, Precedence::Ternary);
+    }
+
+    fn visit_expr_this(&mut self, _: &'ast This) {
+        self.out.push_str("this");
+    }
+
+    fn visit_expr_variable(&mut self, e: &'ast Variable) {
+        self.out.push_str(e.name.as_str());
+    }
+
+    fn visit_expr_unary(&mut self, e: &'ast Unary) {
+        self.out.push_str(&e.operator.to_string());
+        self.expr(&e.right, Precedence::Unary);
+    }
+
+    fn visit_stmt_block(&mut self, s: &'ast Block) {
+        self.block(&s.statements, end(s.span));
+    }
+
+    fn visit_stmt_class(&mut self, s: &'ast Class) {
+        self.out.push_str(&format!("class {} ", s.name));
+        if let Some(superclass) = &s.superclass {
+            self.out.push_str(&format!("< {} ", superclass.name));
+        }
+        self.out.push('{');
+        self.depth += 1;
+        for method in &s.methods {
+            self.new_line();
+            self.line(method.span, end(s.span), |formatter| {
+                formatter.function(method)
+            });
+        }
+        self.closing_comments(end(s.span));
+        self.depth -= 1;
+        self.new_line();
+        self.out.push('}');
+    }
+
+    fn visit_stmt_do_while(&mut self, s: &'ast DoWhile) {
+        self.out.push_str("do ");
+        self.visit_stmt(&s.body);
+        self.out.push_str(" while (");
+        self.expr(&s.condition, Precedence::Assignment);
+        self.out.push_str(");");
+    }
+
+    fn visit_stmt_expression(&mut self, s: &'ast Expression) {
+        // a map literal at the start of a statement would be read as a block
+        if matches!(s.expression, Expr::MapLiteral(_)) {
+            self.out.push('(');
+            self.visit_expr(&s.expression);
+            self.out.push(')');
+        } else {
+            self.visit_expr(&s.expression);
+        }
+        self.out.push(';');
+    }
+
+    fn visit_stmt_for(&mut self, s: &'ast For) {
+        self.out.push_str("for (");
+        // the initializer is a statement, which writes its own semicolon
+        match &s.initializer {
+            Some(initializer) => self.visit_stmt(initializer),
+            None => self.out.push(';'),
+        }
+        if let Some(condition) = &s.condition {
+            self.out.push(' ');
+            self.expr(condition, Precedence::Assignment);
+        }
+        self.out.push(';');
+        if let Some(increment) = &s.increment {
+            self.out.push(' ');
+            self.expr(increment, Precedence::Assignment);
+        }
+        self.out.push_str(") ");
+        self.visit_stmt(&s.body);
+    }
+
+    fn visit_stmt_for_in(&mut self, s: &'ast ForIn) {
+        self.out.push_str(&format!("for ({} in ", s.name));
+        self.expr(&s.iterable, Precedence::Assignment);
+        self.out.push_str(") ");
+        self.visit_stmt(&s.body);
+    }
+
+    fn visit_stmt_function(&mut self, s: &'ast Function) {
+        self.out.push_str("fun ");
+        self.function(s);
+    }
+
+    fn visit_stmt_if(&mut self, s: &'ast If) {
+        self.out.push_str("if (");
+        self.expr(&s.condition, Precedence::Assignment);
+        self.out.push_str(") ");
+        self.visit_stmt(&s.then_branch);
+        if let Some(else_branch) = &s.else_branch {
+            self.out.push_str(" else ");
+            self.visit_stmt(else_branch);
+        }
+    }
+
+    fn visit_stmt_print(&mut self, s: &'ast Print) {
+        self.out.push_str("print ");
+        for (i, expression) in s.expressions.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expression, Precedence::Assignment);
+        }
+        self.out.push(';');
+    }
+
+    fn visit_stmt_return(&mut self, s: &'ast Return) {
+        self.out.push_str("return ");
+        self.expr(&s.value, Precedence::Assignment);
+        self.out.push(';');
+    }
+
+    fn visit_stmt_switch(&mut self, s: &'ast Switch) {
+        self.out.push_str("switch (");
+        self.expr(&s.subject, Precedence::Assignment);
+        self.out.push_str(") {");
+        self.depth += 1;
+        let cases = s.cases.iter().map(|(value, body)| (Some(value), body));
+        let mut cases = cases
+            .chain(s.default.iter().map(|body| (None, body)))
+            .peekable();
+        while let Some((value, body)) = cases.next() {
+            self.new_line();
+            match value {
+                Some(value) => {
+                    self.out.push_str("case ");
+                    self.expr(value, Precedence::Assignment);
+                    self.out.push(':');
+                }
+                None => self.out.push_str("default:"),
+            }
+            // where a case ends isn't recorded, so comments after the line
+            // of its last statement go in the next case
+            let limit = match body.last() {
+                _ if cases.peek().is_none() => end(s.span),
+                Some(stmt) => (stmt.span().end_line, u32::MAX),
+                None => value.map_or(end(s.span), |value| (value.span().end_line, u32::MAX)),
+            };
+            self.depth += 1;
+            self.statements(body, limit);
+            self.depth -= 1;
+        }
+        self.depth -= 1;
+        self.new_line();
+        self.out.push('}');
+    }
+
+    fn visit_stmt_throw(&mut self, s: &'ast Throw) {
+        self.out.push_str("throw ");
+        self.expr(&s.value, Precedence::Assignment);
+        self.out.push(';');
+    }
+
+    fn visit_stmt_try(&mut self, s: &'ast Try) {
+        // where the try block ends isn't recorded, so comments after the
+        // line of its last statement go in the catch block
+        let limit = match s.body.last() {
+            Some(stmt) => (stmt.span().end_line, u32::MAX),
+            None => start(s.span),
+        };
+        self.out.push_str("try ");
+        self.block(&s.body, limit);
+        self.out.push_str(&format!(" catch ({}) ", s.catch_name));
+        self.block(&s.catch_body, end(s.span));
+    }
+
+    fn visit_stmt_var(&mut self, s: &'ast Var) {
+        self.out.push_str(&format!("var {}", s.name));
+        if let Some(initializer) = &s.initializer {
+            self.out.push_str(" = ");
+            self.expr(initializer, Precedence::Assignment);
+        }
+        self.out.push(';');
+    }
+
+    fn visit_stmt_while(&mut self, s: &'ast While) {
+        self.out.push_str("while (");
+        self.expr(&s.condition, Precedence::Assignment);
+        self.out.push_str(") ");
+        self.visit_stmt(&s.body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read_dir, read_to_string};
+
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    #[test]
+    fn format_statements() {
+        let source = "class B<A{init(x){this.x=x;}}
+            fun f([a,b],c){if(a)return b;else{print c;}}
+            var m={\"k\":[1,2][0]};
+            switch(m){case 1:print 1;default:print \"a\\n\";}
+            try{throw nil;}catch(e){print e;}";
+        assert_eq!(
+            format(&parse(source), vec![]),
+            "class B < A {
+  init(x) {
+    this.x = x;
+  }
+}
+fun f([a, b], c) {
+  if (a) return b; else {
+    print c;
+  }
+}
+var m = {\"k\": [1, 2][0]};
+switch (m) {
+  case 1:
+    print 1;
+  default:
+    print \"a\\n\";
+}
+try {
+  throw nil;
+} catch (e) {
+  print e;
+}
+"
+        );
+    }
+
+    #[test]
+    fn format_keeps_sugar() {
+        // parentheses are added where they are needed to keep the meaning
+        let format = |source| format(&parse(source), vec![]);
+        assert_eq!(format("x *= 1 + 2;"), "x *= 1 + 2;\n");
+        assert_eq!(format("a - (b - c) ** 2;"), "a - (b - c) ** 2;\n");
+        assert_eq!(
+            format("for (var i = 0; i < 2; i += 1) print i;"),
+            "for (var i = 0; i < 2; i += 1) print i;\n"
+        );
+        assert_eq!(
+            format("for(;;){} for(x=1;x;)print x;"),
+            "for (;;) {\n}\nfor (x = 1; x;) print x;\n"
+        );
+    }
+
+    #[test]
+    fn format_keeps_comments() {
+        let source = "// counts to three
+var total = 0;  // running sum
+for (var i = 0; i < 3; i += 1) {
+  /* add it */ total += i;
+  // at the end of the loop
+}
+fun f(a) { return \"${a /* inside */}\"; }
+switch (total) { case 1: print 1;
+  // before a case
+  case 3: print 3; }
+try { throw 1; } catch (e) {
+  // caught
+  print e;
+}
+/* the end */";
+        let formatted = crate::format_source(source).unwrap();
+        assert_eq!(
+            formatted,
+            "// counts to three
+var total = 0; // running sum
+for (var i = 0; i < 3; i += 1) {
+  /* add it */
+  total += i;
+  // at the end of the loop
+}
+fun f(a) {
+  return \"${a}\"; /* inside */
+}
+switch (total) {
+  case 1:
+    print 1;
+  case 3:
+    // before a case
+    print 3;
+}
+try {
+  throw 1;
+} catch (e) {
+  // caught
+  print e;
+}
+/* the end */
+"
+        );
+        assert_eq!(crate::format_source(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn format_parenthesizes_operands() {
+        let binary = |left, operator, right| {
+            Expr::Binary(Binary {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+                span: Default::default(),
+            })
+        };
+        let number = |x| {
+            Expr::Literal(LiteralExpr {
+                value: Literal::Number(x),
+                span: Default::default(),
+            })
+        };
+        let sum = binary(number(1.0), TokenKind::Plus, number(2.0));
+        let difference = binary(number(3.0), TokenKind::Minus, sum.clone());
+        let cube = binary(number(2.0), TokenKind::StarStar, number(3.0));
+        let power = binary(cube, TokenKind::StarStar, number(-2.0));
+        let mut formatter = Formatter::default();
+        formatter.visit_expr(&binary(sum.clone(), TokenKind::Star, difference));
+        formatter.out.push(' ');
+        formatter.visit_expr(&power);
+        assert_eq!(formatter.out, "(1 + 2) * (3 - (1 + 2)) (2 ** 3) ** -2");
+
+        let or = binary(number(1.0), TokenKind::Pipe, number(2.0));
+        let shift = binary(number(3.0), TokenKind::LessLess, sum);
+        let mut formatter = Formatter::default();
+        formatter.visit_expr(&binary(or, TokenKind::Ampersand, shift));
+        assert_eq!(formatter.out, "(1 | 2) & 3 << 1 + 2");
+    }
+
+    #[test]
+    fn display_syntax_trees() {
+        let stmts = parse("print f(a and !b, g()) or nil;\nif (x) { y = [1, \"2\"]; }");
+        match &stmts[0] {
+            Stmt::Print(print) => {
+                assert_eq!(print.expressions[0].to_string(), "f(a and !b, g()) or nil")
+            }
+            stmt => panic!("expected a print statement but got {}", stmt),
+        }
+        assert_eq!(stmts[0].to_string(), "print f(a and !b, g()) or nil;");
+        assert_eq!(
+            format!("unexpected statement: {}", stmts[1]),
+            "unexpected statement: if (x) {\n  y = [1, \"2\"];\n}"
+        );
+    }
+
+    #[test]
+    fn formatting_examples_is_stable() {
+        for entry in read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            let formatted = crate::format_source(&read_to_string(&path).unwrap()).unwrap();
+            let comments = Scanner::new(&formatted).comments();
+            assert_eq!(
+                format(&parse(&formatted), comments),
+                formatted,
+                "{:?}",
+                path
+            );
+        }
+    }
+}
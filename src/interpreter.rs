@@ -1,75 +1,376 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt;
-use std::fmt::Write;
+use std::io;
+use std::io::BufRead;
 use std::iter::zip;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use anyhow::anyhow;
 use anyhow::Result;
 use generational_arena::Arena;
 use generational_arena::Index;
+use itertools::Itertools;
 
 use crate::env::Environment;
+use crate::error::LoxError;
 use crate::expr::Assign;
 use crate::expr::Binary;
 use crate::expr::Call;
+use crate::expr::Get;
 use crate::expr::Grouping;
+use crate::expr::Index as IndexExpr;
+use crate::expr::Interpolation;
+use crate::expr::ListLiteral;
+use crate::expr::LiteralExpr;
 use crate::expr::Logical;
+use crate::expr::MapLiteral;
+use crate::expr::Set;
+use crate::expr::SetIndex;
+use crate::expr::StringPart;
+use crate::expr::Super;
+use crate::expr::Ternary;
+use crate::expr::This;
 use crate::expr::Unary;
 use crate::expr::Variable;
+use crate::natives;
+use crate::natives::NativeFunction;
+use crate::resolver;
+use crate::rng::Rng;
+use crate::span::Span;
 use crate::stmt::Block;
+use crate::stmt::Class;
+use crate::stmt::DoWhile;
 use crate::stmt::Expression;
+use crate::stmt::For;
+use crate::stmt::ForIn;
 use crate::stmt::Function;
 use crate::stmt::If;
+use crate::stmt::Param;
 use crate::stmt::Print;
 use crate::stmt::Return;
+use crate::stmt::Switch;
+use crate::stmt::Throw;
+use crate::stmt::Try;
 use crate::stmt::Var;
 use crate::stmt::While;
+use crate::symbol::Symbol;
+use crate::visitor;
 use crate::visitor::ExprVisitor;
 use crate::visitor::StmtVisitor;
-use crate::{expr::Literal, stmt::Stmt, token::TokenKind};
+use crate::{
+    expr::{Expr, Literal},
+    stmt::Stmt,
+    token::TokenKind,
+};
+
+/// The most entries the trace of an error lists before counting the rest.
+const MAX_TRACE_LEN: usize = 20;
 
 // A custom error type used to signal that a value is being returned, so
-// the error should be "caught" by the nearest function call.
+// the error should be "caught" by the nearest function call. The value
+// itself is stashed on the interpreter, since runtime values are not
+// necessarily thread-safe (which `anyhow` errors must be).
 #[derive(Debug, Clone)]
-struct ReturnValueError(RuntimeValue);
+struct ReturnValueError;
 
 impl fmt::Display for ReturnValueError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "<returning {}>", self.0)
+        write!(f, "<returning>")
     }
 }
 
 impl std::error::Error for ReturnValueError {}
 
-#[derive(Debug, Clone, PartialEq)]
+/// A call in tail position (`return f(...);`), which is made by the caller
+/// of the function returning rather than inside it.
+struct TailCall {
+    function: Rc<Function>,
+    closure: Rc<RefCell<Environment>>,
+    arguments: Vec<RuntimeValue>,
+    span: Span,
+}
+
+/// Signals that the program threw a value, unwinding it like an error until
+/// the nearest enclosing `try` catches it. Like returned values, the thrown
+/// value itself is stashed on the interpreter.
+#[derive(Debug, Clone)]
+struct ThrownValue {
+    span: Span,
+    // the functions being called when the value was thrown, in case it is
+    // never caught
+    trace: Vec<String>,
+}
+
+impl fmt::Display for ThrownValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<throwing>")
+    }
+}
+
+impl std::error::Error for ThrownValue {}
+
+/// Signals that the program called `exit`, unwinding it like an error
+/// until `interpret` (or `evaluate`) catches it and records the code.
+#[derive(Debug, Clone)]
+pub(crate) struct ExitError(pub i32);
+
+impl fmt::Display for ExitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<exiting with status {}>", self.0)
+    }
+}
+
+impl std::error::Error for ExitError {}
+
+#[derive(Debug, Clone)]
 pub enum RuntimeValue {
     Bool(bool),
-    Callable(Stmt, Environment),
+    Callable(Rc<Function>, Rc<RefCell<Environment>>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    List(Rc<RefCell<Vec<RuntimeValue>>>),
+    Map(Rc<RefCell<HashMap<MapKey, RuntimeValue>>>),
+    NativeFunction(NativeFunction),
     Nil,
     Number(f64),
     String(String),
 }
 
+// functions, classes, instances, lists and maps are compared by identity,
+// everything else is compared structurally; a function is the same if it
+// comes from the same declaration and closes over the same scope
+impl PartialEq for RuntimeValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (RuntimeValue::Bool(x), RuntimeValue::Bool(y)) => x == y,
+            (RuntimeValue::Callable(x_ast, x_env), RuntimeValue::Callable(y_ast, y_env)) => {
+                Rc::ptr_eq(x_ast, y_ast) && Rc::ptr_eq(x_env, y_env)
+            }
+            (RuntimeValue::Class(x), RuntimeValue::Class(y)) => Rc::ptr_eq(x, y),
+            (RuntimeValue::Instance(x), RuntimeValue::Instance(y)) => Rc::ptr_eq(x, y),
+            (RuntimeValue::List(x), RuntimeValue::List(y)) => Rc::ptr_eq(x, y),
+            (RuntimeValue::Map(x), RuntimeValue::Map(y)) => Rc::ptr_eq(x, y),
+            (RuntimeValue::NativeFunction(x), RuntimeValue::NativeFunction(y)) => x == y,
+            (RuntimeValue::Nil, RuntimeValue::Nil) => true,
+            (RuntimeValue::Number(x), RuntimeValue::Number(y)) => x == y,
+            (RuntimeValue::String(x), RuntimeValue::String(y)) => x == y,
+            _ => false,
+        }
+    }
+}
+
 impl Eq for RuntimeValue {}
 
+/// A value that can be used as a key in a map: either a string or a number.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    // stored as bits so that keys can be hashed; -0 is normalized to 0 so
+    // that keys which compare equal as numbers are the same key
+    Number(u64),
+    String(String),
+}
+
+impl MapKey {
+    /// Converts a runtime value into a key, or returns `None` if values of
+    /// its type cannot be used as keys.
+    pub fn from_value(value: &RuntimeValue) -> Option<MapKey> {
+        match value {
+            RuntimeValue::Number(x) if *x == 0.0 => Some(MapKey::Number(0.0f64.to_bits())),
+            RuntimeValue::Number(x) => Some(MapKey::Number(x.to_bits())),
+            RuntimeValue::String(s) => Some(MapKey::String(s.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn to_value(&self) -> RuntimeValue {
+        match self {
+            MapKey::Number(bits) => RuntimeValue::Number(f64::from_bits(*bits)),
+            MapKey::String(s) => RuntimeValue::String(s.clone()),
+        }
+    }
+}
+
+// numbers come before strings, so that maps are displayed in a predictable
+// order
+impl PartialOrd for MapKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MapKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match (self, other) {
+            (MapKey::Number(x), MapKey::Number(y)) => {
+                f64::from_bits(*x).total_cmp(&f64::from_bits(*y))
+            }
+            (MapKey::Number(_), MapKey::String(_)) => std::cmp::Ordering::Less,
+            (MapKey::String(_), MapKey::Number(_)) => std::cmp::Ordering::Greater,
+            (MapKey::String(x), MapKey::String(y)) => x.cmp(y),
+        }
+    }
+}
+
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_value())
+    }
+}
+
+#[derive(Debug)]
+pub struct LoxClass {
+    pub name: String,
+    pub superclass: Option<Rc<LoxClass>>,
+    // each method is stored as a `RuntimeValue::Callable` whose closure
+    // does not yet have `this` bound
+    pub methods: HashMap<Symbol, RuntimeValue>,
+}
+
+impl LoxClass {
+    pub fn find_method(&self, name: Symbol) -> Option<&RuntimeValue> {
+        self.methods.get(&name).or_else(|| {
+            self.superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name))
+        })
+    }
+
+    /// Counts the references to `env` from the methods of this class, which
+    /// close over either `env` itself or a scope inside it where `super` is
+    /// bound.
+    fn references_to(&self, env: &Rc<RefCell<Environment>>) -> usize {
+        let closures: Vec<&Rc<RefCell<Environment>>> = self
+            .methods
+            .values()
+            .filter_map(|method| match method {
+                RuntimeValue::Callable(_, closure) => Some(closure),
+                _ => None,
+            })
+            .collect();
+        let mut count = 0;
+        for (i, closure) in closures.iter().enumerate() {
+            if Rc::ptr_eq(closure, env) {
+                count += 1;
+                continue;
+            }
+            // count each scope holding `super` once, by its first method, and
+            // only if the methods are all that refer to it
+            if closures[..i].iter().any(|other| Rc::ptr_eq(other, closure)) {
+                continue;
+            }
+            let uses = closures
+                .iter()
+                .filter(|other| Rc::ptr_eq(other, closure))
+                .count();
+            let encloses = closure
+                .borrow()
+                .enclosing()
+                .is_some_and(|enclosing| Rc::ptr_eq(enclosing, env));
+            if encloses && Rc::strong_count(closure) == uses {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Returns whether this class is `class` or inherits from it.
+    pub fn is_subclass_of(&self, class: &LoxClass) -> bool {
+        std::ptr::eq(self, class)
+            || self
+                .superclass
+                .as_ref()
+                .is_some_and(|superclass| superclass.is_subclass_of(class))
+    }
+}
+
+#[derive(Debug)]
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    pub fields: HashMap<Symbol, RuntimeValue>,
+}
+
 impl fmt::Display for RuntimeValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, None)
+    }
+}
+
+/// A value displayed with a fixed number of decimal places for numbers.
+struct WithPrecision<'a>(&'a RuntimeValue, Option<usize>);
+
+impl fmt::Display for WithPrecision<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.write(f, self.1)
+    }
+}
+
+impl RuntimeValue {
+    /// Formats a number the way Lox displays it: whole numbers without a
+    /// fractional part, very large and very small magnitudes in scientific
+    /// notation, and exactly `precision` decimal places if it is given.
+    pub fn format_number(x: f64, precision: Option<usize>) -> String {
+        if x.is_nan() {
+            "NaN".into()
+        } else if x.is_infinite() {
+            if x > 0.0 { "Infinity" } else { "-Infinity" }.into()
+        } else if let Some(precision) = precision {
+            format!("{:.*}", precision, x)
+        } else if x != 0.0 && (x.abs() >= 1e21 || x.abs() < 1e-7) {
+            format!("{:e}", x)
+        } else {
+            x.to_string()
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, precision: Option<usize>) -> fmt::Result {
         match self {
             RuntimeValue::Bool(x) => write!(f, "{}", x),
-            RuntimeValue::Callable(ast, _) => {
-                if let &Stmt::Function(Function { name, .. }) = &ast {
-                    write!(f, "<fn {}>", name)
-                } else {
-                    Err(std::fmt::Error)
-                }
+            RuntimeValue::Callable(function, _) => write!(f, "<fn {}>", function.name),
+            RuntimeValue::Class(class) => write!(f, "{}", class.name),
+            RuntimeValue::Instance(instance) => {
+                write!(f, "{} instance", instance.borrow().class.name)
+            }
+            RuntimeValue::List(elements) => {
+                let elements = elements.borrow();
+                let elements = elements
+                    .iter()
+                    .map(|element| WithPrecision(element, precision));
+                write!(f, "[{}]", elements.format(", "))
             }
+            RuntimeValue::Map(map) => {
+                let map = map.borrow();
+                let entries = map
+                    .iter()
+                    .sorted_by(|(x, _), (y, _)| x.cmp(y))
+                    .map(|(key, value)| format!("{}: {}", key, WithPrecision(value, precision)));
+                write!(f, "{{{}}}", entries.format(", "))
+            }
+            RuntimeValue::NativeFunction(native) => write!(f, "<native fn {}>", native.name),
             RuntimeValue::Nil => write!(f, "nil"),
-            RuntimeValue::Number(x) => write!(f, "{}", x),
+            RuntimeValue::Number(x) => write!(f, "{}", Self::format_number(*x, precision)),
             RuntimeValue::String(x) => write!(f, "{}", x),
         }
     }
-}
 
-impl RuntimeValue {
+    /// Returns the name of this value's type, as reported to Lox programs.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            RuntimeValue::Bool(_) => "bool",
+            RuntimeValue::Callable(_, _) => "function",
+            RuntimeValue::Class(_) => "class",
+            RuntimeValue::Instance(_) => "instance",
+            RuntimeValue::List(_) => "list",
+            RuntimeValue::Map(_) => "map",
+            RuntimeValue::NativeFunction(_) => "function",
+            RuntimeValue::Nil => "nil",
+            RuntimeValue::Number(_) => "number",
+            RuntimeValue::String(_) => "string",
+        }
+    }
+
     pub fn unwrap_number(&self, e: anyhow::Error) -> Result<f64> {
         if let RuntimeValue::Number(val) = self {
             Ok(*val)
@@ -79,261 +380,454 @@ impl RuntimeValue {
     }
 }
 
-pub struct Interpreter {
-    env: Environment,
-    variables: Arena<RuntimeValue>,
-    pub stdout: String,
+impl From<f64> for RuntimeValue {
+    fn from(x: f64) -> Self {
+        RuntimeValue::Number(x)
+    }
 }
 
-impl Default for Interpreter {
-    fn default() -> Self {
-        Interpreter {
-            env: Environment::default(),
-            variables: Arena::new(),
-            stdout: String::new(),
-        }
+impl From<bool> for RuntimeValue {
+    fn from(x: bool) -> Self {
+        RuntimeValue::Bool(x)
     }
 }
 
-impl Interpreter {
-    pub fn interpret(&mut self, statements: &Vec<Stmt>) -> Result<()> {
-        for stmt in statements {
-            self.visit_stmt(stmt)?;
-        }
-        Ok(())
+impl From<String> for RuntimeValue {
+    fn from(x: String) -> Self {
+        RuntimeValue::String(x)
     }
+}
 
-    fn define_in_env(
-        &mut self,
-        env: &Environment,
-        name: String,
-        value: RuntimeValue,
-    ) -> (Environment, Index) {
-        let index = self.variables.insert(value);
-        let new_env = env.insert(name, index);
-        (new_env, index)
+impl From<&str> for RuntimeValue {
+    fn from(x: &str) -> Self {
+        RuntimeValue::String(x.to_owned())
     }
+}
 
-    // this method only exists to avoid a borrow checker issue
-    fn define_in_self_env(&mut self, name: String, value: RuntimeValue) -> (Environment, Index) {
-        let index = self.variables.insert(value);
-        let new_env = self.env.insert(name, index);
-        (new_env, index)
-    }
+impl TryFrom<RuntimeValue> for f64 {
+    type Error = anyhow::Error;
 
-    fn update_var(&mut self, index: Index, value: RuntimeValue) -> Result<()> {
-        if let Some(old_value) = self.variables.get_mut(index) {
-            *old_value = value;
-            Ok(())
-        } else {
-            Err(anyhow!(
-                "Variable #{:?} is unexpectedly not allocated a value.",
-                index
-            ))
+    fn try_from(value: RuntimeValue) -> Result<Self> {
+        match value {
+            RuntimeValue::Number(x) => Ok(x),
+            _ => Err(anyhow!("Expected a number but got {}.", value.type_name())),
         }
     }
+}
 
-    fn lookup_in_env(&self, env: &Environment, name: &String) -> Result<RuntimeValue> {
-        let index = env
-            .get(name)
-            .ok_or_else(|| anyhow!("Undefined variable {}.", name))?;
-        if let Some(value) = self.variables.get(index) {
-            Ok(value.clone())
-        } else {
-            Err(anyhow!("Variable {} was unexpectedly deallocated.", name))
+impl TryFrom<RuntimeValue> for bool {
+    type Error = anyhow::Error;
+
+    fn try_from(value: RuntimeValue) -> Result<Self> {
+        match value {
+            RuntimeValue::Bool(x) => Ok(x),
+            _ => Err(anyhow!("Expected a bool but got {}.", value.type_name())),
         }
     }
+}
 
-    fn invoke_function(
-        &mut self,
-        callee: RuntimeValue,
-        arguments: Vec<RuntimeValue>,
-    ) -> Result<RuntimeValue> {
-        if let RuntimeValue::Callable(ast, closure) = callee {
-            if let Stmt::Function(Function {
-                name: _,
-                params,
-                body,
-            }) = &ast
-            {
-                if params.len() != arguments.len() {
-                    return Err(anyhow!(
-                        "Expected {} arguments but got {}.",
-                        params.len(),
-                        arguments.len()
-                    ));
-                }
+impl TryFrom<RuntimeValue> for String {
+    type Error = anyhow::Error;
 
-                // construct a new environment for the lifetime of the callable
-                // where the parameter variables have been assigned the values
-                // of the callable arguments
-                let mut invoke_env = closure.enclose();
-                for (param, arg) in zip(params, arguments) {
-                    (invoke_env, _) = self.define_in_env(&invoke_env, param.clone(), arg);
-                }
+    fn try_from(value: RuntimeValue) -> Result<Self> {
+        match value {
+            RuntimeValue::String(x) => Ok(x),
+            _ => Err(anyhow!("Expected a string but got {}.", value.type_name())),
+        }
+    }
+}
 
-                // update the environment being used to interpret statements
-                // let old_env = self.env;
-                // self.env = invoke_env;
-                let old_env = std::mem::replace(&mut self.env, invoke_env);
-
-                // evaluate each statement within our new environment
-                for sub_stmt in body {
-                    if let Err(err) = self.visit_stmt(sub_stmt) {
-                        match err.downcast::<ReturnValueError>() {
-                            Ok(ReturnValueError(value)) => {
-                                // if we are returning early, be sure to restore
-                                // the old environment
-                                self.env = old_env;
-                                return Ok(value);
-                            }
-                            Err(err) => return Err(err),
-                        }
-                    }
-                }
+/// An element of a list or map that is being looked up or assigned to.
+enum Element {
+    List(Rc<RefCell<Vec<RuntimeValue>>>, usize),
+    Map(Rc<RefCell<HashMap<MapKey, RuntimeValue>>>, MapKey),
+}
 
-                // restore the old environment
-                self.env = old_env;
+impl Element {
+    fn get(&self, span: &Span) -> Result<RuntimeValue> {
+        match self {
+            Element::List(list, index) => Ok(list.borrow()[*index].clone()),
+            Element::Map(map, key) => map
+                .borrow()
+                .get(key)
+                .cloned()
+                .ok_or_else(|| anyhow!("Undefined key {} on line {}.", key, span)),
+        }
+    }
 
-                Ok(RuntimeValue::Nil)
-            } else {
-                Err(anyhow!(
-                    "Compiler error: invalid function found in callable."
-                ))
+    fn set(&self, value: RuntimeValue) {
+        match self {
+            Element::List(list, index) => list.borrow_mut()[*index] = value,
+            Element::Map(map, key) => {
+                map.borrow_mut().insert(key.clone(), value);
             }
-        } else {
-            Err(anyhow!("Can only call functions and classes."))
         }
     }
 }
 
-impl StmtVisitor for Interpreter {
-    type StmtResult = Result<()>;
+pub type PrintCallback = Box<dyn FnMut(&str)>;
+pub type StmtCallback = Box<dyn FnMut(&Stmt, &Environment)>;
 
-    fn visit_stmt_block(&mut self, block: &Block) -> Self::StmtResult {
-        let Block { statements } = block;
-        // create an environment that will encapsulate the old one
-        let new_env = self.env.enclose();
+pub struct Interpreter {
+    env: Rc<RefCell<Environment>>,
+    variables: Arena<RuntimeValue>,
+    return_value: Option<RuntimeValue>,
+    thrown_value: Option<RuntimeValue>,
+    /// Output written by the program, collected only when no writer is set.
+    pub stdout: String,
+    writer: Option<Box<dyn io::Write>>,
+    reader: Option<Box<dyn io::BufRead>>,
+    /// Called with each line printed by the program (without the trailing
+    /// newline) as soon as it is printed, in addition to the usual output.
+    pub on_print: Option<PrintCallback>,
+    /// Called before each statement runs, with the scope it runs in, e.g.
+    /// so that a debugger can follow the program.
+    pub on_stmt: Option<StmtCallback>,
+    cancel_flag: Option<Arc<AtomicBool>>,
+    step_limit: Option<u64>,
+    steps: u64,
+    /// If set, the value of a program's final top-level expression
+    /// statement is printed, as if it were a print statement.
+    pub echo_final_expr: bool,
+    /// The deepest that calls to Lox functions may be nested before the
+    /// program is stopped with a stack overflow error. Calls in tail
+    /// position (`return f();`) replace the call making them, so they don't
+    /// count towards this.
+    pub max_call_depth: usize,
+    call_depth: usize,
+    /// The Lox functions being called, innermost last, each described by
+    /// its name and the line it was called from.
+    call_stack: Vec<String>,
+    tail_call: Option<TailCall>,
+    // how many try statements enclose the code being run, within the
+    // current function call
+    try_depth: usize,
+    /// If set, numbers are converted to strings (e.g. when printed) with
+    /// exactly this many decimal places, such as 2 for amounts of money.
+    pub number_precision: Option<usize>,
+    /// If set, dividing by zero follows IEEE 754 and produces an infinity or
+    /// NaN, instead of stopping the program with an error.
+    pub ieee_division: bool,
+    /// If set, operators whose operands are all literals are computed once,
+    /// before the program runs, rather than each time they are reached.
+    pub fold_constants: bool,
+    /// The status code the program passed to `exit`, if it called it. The
+    /// program stops at that point, but output it printed beforehand is
+    /// kept (in `stdout`, if no writer is set).
+    pub exit_code: Option<i32>,
+    /// If set, programs may read and write files with `file_read` and
+    /// `file_write`. Off by default, so that embedders must opt in to giving
+    /// programs access to the filesystem.
+    pub allow_fs: bool,
+    /// Source of the numbers returned by `random` and `random_int`. Unless
+    /// `seed_rng` is called, it is seeded from entropy at construction.
+    pub(crate) rng: Rng,
+}
 
-        // replace the Interpreter's current environment with the empty
-        // environment, returning the old one
-        let old_env = std::mem::replace(&mut self.env, new_env);
+impl Default for Interpreter {
+    fn default() -> Self {
+        let mut interpreter = Interpreter {
+            env: Rc::new(RefCell::new(Environment::default())),
+            variables: Arena::new(),
+            return_value: None,
+            thrown_value: None,
+            stdout: String::new(),
+            writer: None,
+            reader: None,
+            on_print: None,
+            on_stmt: None,
+            cancel_flag: None,
+            step_limit: None,
+            steps: 0,
+            echo_final_expr: false,
+            max_call_depth: 1000,
+            call_depth: 0,
+            call_stack: vec![],
+            tail_call: None,
+            try_depth: 0,
+            number_precision: None,
+            ieee_division: false,
+            allow_fs: false,
+            fold_constants: false,
+            exit_code: None,
+            rng: Rng::from_entropy(),
+        };
+        for native in natives::globals() {
+            interpreter.define_native(native);
+        }
+        interpreter
+    }
+}
 
-        // evaluate each statement (within our new environment)
-        for sub_stmt in statements {
-            self.visit_stmt(sub_stmt)?;
+impl Interpreter {
+    /// Creates an interpreter with `globals` already defined, so that a host
+    /// can give scripts values (e.g. configuration) without the script
+    /// declaring them.
+    pub fn new(globals: HashMap<String, RuntimeValue>) -> Self {
+        let mut interpreter = Interpreter::default();
+        for (name, value) in globals {
+            interpreter.define_in_self_env(Symbol::intern(&name), value);
         }
+        interpreter
+    }
 
-        // restore the environment, discarding all of the variables
-        // that were defined within the block
-        self.env = old_env;
+    /// Creates an interpreter that writes program output to `writer` instead
+    /// of collecting it in `stdout`.
+    pub fn with_writer(writer: Box<dyn io::Write>) -> Self {
+        Interpreter {
+            writer: Some(writer),
+            ..Default::default()
+        }
+    }
 
-        Ok(())
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<()> {
+        self.interpret_value(statements).map(|_| ())
     }
 
-    fn visit_stmt_expression(&mut self, expression: &Expression) -> Self::StmtResult {
-        let Expression { expression } = expression;
-        self.visit_expr(expression)?;
-        Ok(())
+    /// Runs a program like `interpret`, returning the value of its final
+    /// statement if that is an expression statement, or nil otherwise
+    /// (including when the program calls `exit`).
+    pub fn interpret_value(&mut self, statements: &[Stmt]) -> Result<RuntimeValue> {
+        resolver::resolve(statements)?;
+        let result = self.interpret_resolved(statements);
+        let result = self.report_uncaught(result);
+        Ok(self.catch_exit(result)?.unwrap_or(RuntimeValue::Nil))
     }
 
-    fn visit_stmt_print(&mut self, print: &Print) -> Self::StmtResult {
-        let Print { expression } = print;
-        let value = self.visit_expr(expression)?;
-        println!("{}", value);
-        writeln!(&mut self.stdout, "{}", value)?;
-        Ok(())
+    fn interpret_resolved(&mut self, statements: &[Stmt]) -> Result<RuntimeValue> {
+        let (last, rest) = match statements.split_last() {
+            Some(split) => split,
+            None => return Ok(RuntimeValue::Nil),
+        };
+        for stmt in rest {
+            self.visit_stmt(stmt)?;
+        }
+        match last {
+            Stmt::Expression(Expression { expression, .. }) => {
+                let value = self.visit_expr(expression)?;
+                if self.echo_final_expr {
+                    self.print_line(&self.stringify(&value))?;
+                }
+                Ok(value)
+            }
+            _ => {
+                self.visit_stmt(last)?;
+                Ok(RuntimeValue::Nil)
+            }
+        }
     }
 
-    fn visit_stmt_function(&mut self, function: &Function) -> Self::StmtResult {
-        let Function { name, params, body } = function;
-        let function = Stmt::Function(Function {
-            name: name.clone(),
-            params: params.clone(),
-            body: body.clone(),
-        });
+    /// Defines a native function in the current environment.
+    pub fn define_native(&mut self, native: NativeFunction) {
+        self.define_in_self_env(
+            Symbol::intern(native.name),
+            RuntimeValue::NativeFunction(native),
+        );
+    }
 
-        // initially bind function name to "nil" value so that it exists
-        // in the function's closure so that recursion works
-        let (new_env, index) = self.define_in_self_env(name.clone(), RuntimeValue::Nil);
+    /// Returns the variables visible in the current scope along with their
+    /// values, in the order given by `Environment::names`.
+    pub fn dump_scope(&self) -> Vec<(String, RuntimeValue)> {
+        let env = self.env.borrow();
+        env.names()
+            .into_iter()
+            .filter_map(|name| {
+                let index = env.get(Symbol::intern(&name))?;
+                let value = self.variables.get(index)?.clone();
+                Some((name, value))
+            })
+            .collect()
+    }
 
-        let callable = RuntimeValue::Callable(function, new_env.clone());
+    /// Makes the program read its input from `reader` instead of stdin.
+    pub fn set_reader(&mut self, reader: Box<dyn io::BufRead>) {
+        self.reader = Some(reader);
+    }
 
-        // update the function name's binding to actual Callable value
-        self.update_var(index, callable)?;
+    /// Reads a line of input for the program, without its line ending.
+    /// Returns `None` at the end of the input.
+    pub(crate) fn read_line(&mut self) -> Result<Option<String>> {
+        // make sure a prompt printed by the program is shown first
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+        }
+        let mut line = String::new();
+        let read = match &mut self.reader {
+            Some(reader) => reader.read_line(&mut line)?,
+            None => io::stdin().lock().read_line(&mut line)?,
+        };
+        if read == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
 
-        // use this new environment going forward in the current scope
-        self.env = new_env;
+    /// Reseeds the random number generator, so that the numbers the program
+    /// draws are the same on every run, e.g. in tests.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Rng::with_seed(seed);
+    }
 
-        Ok(())
+    /// Sets a flag that can be used (e.g. from another thread) to cancel
+    /// execution. It is checked before every loop iteration and function
+    /// call, so a running program stops with an error soon after it is set.
+    pub fn set_cancel_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.cancel_flag = Some(flag);
     }
 
-    fn visit_stmt_if(&mut self, if_: &If) -> Self::StmtResult {
-        let If {
-            condition,
-            then_branch,
-            else_branch,
-        } = if_;
-        if is_truthy(&self.visit_expr(condition)?) {
-            self.visit_stmt(then_branch)?;
-        } else if let Some(unwrapped) = else_branch {
-            self.visit_stmt(unwrapped)?;
+    fn check_cancelled(&self) -> Result<()> {
+        match &self.cancel_flag {
+            Some(flag) if flag.load(Ordering::Relaxed) => Err(anyhow!("Execution cancelled.")),
+            _ => Ok(()),
         }
-        Ok(())
     }
 
-    fn visit_stmt_return(&mut self, return_: &Return) -> Self::StmtResult {
-        let Return { value } = return_;
-        let value = self.visit_expr(value)?;
-        Err(ReturnValueError(value).into())
+    /// Limits how many statements and expressions may be evaluated from now
+    /// on, after which the program is stopped with an error. This bounds the
+    /// running time of untrusted programs. Since tail calls don't count
+    /// towards `max_call_depth`, it (or the cancel flag) is also the only
+    /// thing that stops unbounded tail recursion like
+    /// `fun f(n) { return f(n + 1); }`.
+    pub fn set_step_limit(&mut self, limit: u64) {
+        self.step_limit = Some(limit);
+        self.steps = 0;
     }
 
-    fn visit_stmt_var(&mut self, var: &Var) -> Self::StmtResult {
-        let Var { name, initializer } = var;
-        let value = match initializer {
-            Some(expr) => self.visit_expr(expr)?,
-            None => RuntimeValue::Nil,
-        };
-        let (new_env, _) = self.define_in_self_env(name.clone(), value);
-        self.env = new_env;
+    fn take_step(&mut self) -> Result<()> {
+        self.steps += 1;
+        match self.step_limit {
+            Some(limit) if self.steps > limit => {
+                Err(anyhow!("Step limit exceeded: used all {} steps.", limit))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes program output to the configured writer, or collects it in
+    /// `stdout` if there is none.
+    fn write_output(&mut self, text: &str) -> Result<()> {
+        match &mut self.writer {
+            Some(writer) => writer.write_all(text.as_bytes())?,
+            None => self.stdout.push_str(text),
+        }
         Ok(())
     }
 
-    fn visit_stmt_while(&mut self, while_: &While) -> Self::StmtResult {
-        let While { condition, body } = while_;
-        while is_truthy(&self.visit_expr(condition)?) {
-            self.visit_stmt(body)?;
+    /// Writes text that doesn't end a line, flushing the writer so that it
+    /// shows up straight away.
+    pub(crate) fn write_text(&mut self, text: &str) -> Result<()> {
+        self.write_output(text)?;
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
         }
         Ok(())
     }
-}
 
-impl ExprVisitor for Interpreter {
-    type ExprResult = Result<RuntimeValue>;
+    pub(crate) fn print_line(&mut self, line: &str) -> Result<()> {
+        if let Some(on_print) = &mut self.on_print {
+            on_print(line);
+        }
+        self.write_output(&format!("{}\n", line))
+    }
 
-    fn visit_expr_assign(&mut self, assign: &Assign) -> Self::ExprResult {
-        let Assign { name, value } = assign;
-        let evaluated = self.visit_expr(value)?;
-        let index = self
-            .env
-            .get(name)
-            .ok_or_else(|| anyhow!("Undefined variable {}.", name))?;
-        self.update_var(index, evaluated.clone())?;
-        Ok(evaluated)
+    /// Converts a value into the text that `print` displays for it, using
+    /// the configured number precision.
+    pub fn stringify(&self, value: &RuntimeValue) -> String {
+        WithPrecision(value, self.number_precision).to_string()
     }
 
-    fn visit_expr_binary(&mut self, binary: &Binary) -> Self::ExprResult {
-        let Binary {
-            left,
-            operator,
-            right,
-        } = binary;
-        let left_val = self.visit_expr(left)?;
-        let right_val = self.visit_expr(right)?;
-        match operator {
-            TokenKind::Greater => {
-                let left_num =
-                    left_val.unwrap_number(anyhow!("Unexpected operand before >: {}", left_val))?;
+    /// Evaluates an expression. If it calls `exit`, the exit code is
+    /// recorded and nil is returned.
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<RuntimeValue> {
+        let result = self
+            .visit_expr(expr)
+            .map_err(|err| self.locate_error(err, expr.span()));
+        let result = self.report_uncaught(result);
+        Ok(self.catch_exit(result)?.unwrap_or(RuntimeValue::Nil))
+    }
+
+    /// Parses and evaluates `source` as a single expression, such as
+    /// `x * 2`, in the global scope, so it can use anything defined by
+    /// programs this interpreter has run.
+    pub fn eval_expr(&mut self, source: &str) -> Result<RuntimeValue> {
+        let expr = crate::parse_expression(source)?;
+        self.evaluate(&expr)
+    }
+
+    /// Runs `statements` in `env`, which should enclose the current
+    /// environment, and restores the current environment afterwards.
+    fn execute_block(&mut self, statements: &[Stmt], env: Rc<RefCell<Environment>>) -> Result<()> {
+        // evaluate each statement (within our new environment)
+        self.execute_in(env, |interpreter| {
+            statements
+                .iter()
+                .try_for_each(|sub_stmt| interpreter.visit_stmt(sub_stmt))
+        })
+    }
+
+    /// Calls `run` with `env`, which should enclose the current environment,
+    /// as the current environment, and restores the current environment
+    /// afterwards.
+    fn execute_in(
+        &mut self,
+        env: Rc<RefCell<Environment>>,
+        run: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<()> {
+        // replace the Interpreter's current environment with the new one,
+        // returning the old one
+        let old_env = std::mem::replace(&mut self.env, env);
+
+        let result = run(self);
+
+        // restore the environment, discarding all of the variables
+        // that were defined within the block, even if a statement failed
+        let block_env = std::mem::replace(&mut self.env, old_env);
+        self.release_env(block_env);
+
+        result
+    }
+
+    /// Looks up the property `name` of an instance: a field if it has one,
+    /// and otherwise a method bound to the instance.
+    fn get_property(
+        &mut self,
+        object_val: RuntimeValue,
+        name: Symbol,
+        span: &Span,
+    ) -> Result<RuntimeValue> {
+        if let RuntimeValue::Instance(instance) = &object_val {
+            if let Some(value) = instance.borrow().fields.get(&name) {
+                return Ok(value.clone());
+            }
+            let class = instance.borrow().class.clone();
+            if let Some(method) = class.find_method(name) {
+                return Ok(self.bind_method(method, object_val.clone()));
+            }
+            Err(anyhow!("Undefined property {} on line {}.", name, span))
+        } else {
+            Err(anyhow!("Only instances have properties."))
+        }
+    }
+
+    /// Applies a binary operator to the values of its operands, which is
+    /// also how compound assignments combine a target with a value.
+    fn apply_binary(
+        &mut self,
+        operator: &TokenKind,
+        left_val: RuntimeValue,
+        right_val: RuntimeValue,
+        span: &Span,
+    ) -> Result<RuntimeValue> {
+        match operator {
+            TokenKind::Greater => {
+                let left_num =
+                    left_val.unwrap_number(anyhow!("Unexpected operand before >: {}", left_val))?;
                 let right_num = right_val
                     .unwrap_number(anyhow!("Unexpected operand after >: {}", right_val))?;
                 Ok(RuntimeValue::Bool(left_num > right_num))
@@ -361,6 +855,22 @@ impl ExprVisitor for Interpreter {
             }
             TokenKind::BangEqual => Ok(RuntimeValue::Bool(left_val != right_val)),
             TokenKind::EqualEqual => Ok(RuntimeValue::Bool(left_val == right_val)),
+            TokenKind::Is => {
+                let class = match &right_val {
+                    RuntimeValue::Class(class) => class,
+                    _ => {
+                        return Err(anyhow!(
+                            "Right operand of is must be a class but got {}.",
+                            right_val
+                        ))
+                    }
+                };
+                // values other than instances aren't of any class
+                Ok(RuntimeValue::Bool(match &left_val {
+                    RuntimeValue::Instance(instance) => instance.borrow().class.is_subclass_of(class),
+                    _ => false,
+                }))
+            }
             TokenKind::Minus => {
                 let left_num =
                     left_val.unwrap_number(anyhow!("Unexpected operand before -: {}", left_val))?;
@@ -368,32 +878,33 @@ impl ExprVisitor for Interpreter {
                     .unwrap_number(anyhow!("Unexpected operand after -: {}", right_val))?;
                 Ok(RuntimeValue::Number(left_num - right_num))
             }
-            TokenKind::Plus => {
-                if let RuntimeValue::Number(left_num) = left_val {
-                    if let RuntimeValue::Number(right_num) = right_val {
-                        return Ok(RuntimeValue::Number(left_num + right_num));
-                    }
+            TokenKind::Plus => match (left_val, right_val) {
+                (RuntimeValue::Number(left_num), RuntimeValue::Number(right_num)) => {
+                    Ok(RuntimeValue::Number(left_num + right_num))
                 }
-
-                if let RuntimeValue::String(ref left_str) = left_val {
-                    if let RuntimeValue::String(right_str) = right_val {
-                        let mut new_str = left_str.clone();
-                        new_str.push_str(&right_str);
-                        return Ok(RuntimeValue::String(new_str));
-                    }
+                // if either side is a string, the other side is converted
+                // into one
+                (RuntimeValue::String(mut left_str), right_val) => {
+                    left_str.push_str(&self.stringify(&right_val));
+                    Ok(RuntimeValue::String(left_str))
                 }
-
-                Err(anyhow!(
-                            "Unexpected operands for + (must be a pair of numbers or pair of strings): {}, {}",
-                            left_val,
-                            right_val
-                        ))
-            }
+                (left_val, RuntimeValue::String(right_str)) => {
+                    Ok(RuntimeValue::String(self.stringify(&left_val) + &right_str))
+                }
+                (left_val, right_val) => Err(anyhow!(
+                    "Unexpected operands for + (must be a pair of numbers or include a string): {}, {}",
+                    left_val,
+                    right_val
+                )),
+            },
             TokenKind::Slash => {
                 let left_num =
                     left_val.unwrap_number(anyhow!("Unexpected operand before /: {}", left_val))?;
                 let right_num = right_val
                     .unwrap_number(anyhow!("Unexpected operand after /: {}", right_val))?;
+                if right_num == 0.0 && !self.ieee_division {
+                    return Err(anyhow!("Division by zero on line {}.", span));
+                }
                 Ok(RuntimeValue::Number(left_num / right_num))
             }
             TokenKind::Star => {
@@ -403,101 +914,2030 @@ impl ExprVisitor for Interpreter {
                     .unwrap_number(anyhow!("Unexpected operand after *: {}", right_val))?;
                 Ok(RuntimeValue::Number(left_num * right_num))
             }
+            TokenKind::StarStar => {
+                let left_num = left_val
+                    .unwrap_number(anyhow!("Unexpected operand before **: {}", left_val))?;
+                let right_num = right_val
+                    .unwrap_number(anyhow!("Unexpected operand after **: {}", right_val))?;
+                Ok(RuntimeValue::Number(left_num.powf(right_num)))
+            }
+            TokenKind::Ampersand
+            | TokenKind::Pipe
+            | TokenKind::Caret
+            | TokenKind::LessLess
+            | TokenKind::GreaterGreater => {
+                let left_num = left_val.unwrap_number(anyhow!(
+                    "Unexpected operand before {}: {}",
+                    operator,
+                    left_val
+                ))?;
+                let right_num = right_val.unwrap_number(anyhow!(
+                    "Unexpected operand after {}: {}",
+                    operator,
+                    right_val
+                ))?;
+                Ok(RuntimeValue::Number(bitwise(operator, left_num, right_num)?))
+            }
             _ => Err(anyhow!("Unexpected binary operator: {}", operator)),
         }
     }
 
-    fn visit_expr_call(&mut self, call: &Call) -> Self::ExprResult {
-        let Call { callee, arguments } = call;
-        let callee_val = self.visit_expr(callee)?;
-
-        let mut argument_vals = vec![];
-        for arg in arguments {
-            argument_vals.push(self.visit_expr(arg)?);
+    /// Turns an error raised while running the code at `span` into a
+    /// `LoxError::Runtime`, unless it already is one (from code nested
+    /// inside) or is only being used to unwind the stack. The line is added
+    /// to the message if it doesn't already say where the error happened,
+    /// and the functions being called are recorded as its trace.
+    fn locate_error(&self, err: anyhow::Error, span: Span) -> anyhow::Error {
+        if err.is::<LoxError>()
+            || err.is::<ReturnValueError>()
+            || err.is::<ThrownValue>()
+            || err.is::<ExitError>()
+        {
+            return err;
+        }
+        let message = err.to_string();
+        let message = if message.contains(" on line ") {
+            message
+        } else {
+            match message.strip_suffix('.') {
+                Some(message) => format!("{} on line {}.", message, span),
+                None => format!("{} on line {}", message, span),
+            }
+        };
+        LoxError::Runtime {
+            message,
+            span,
+            trace: self.trace(),
         }
+        .into()
+    }
 
-        self.invoke_function(callee_val, argument_vals)
+    /// Lists the functions being called, innermost first, for the trace of
+    /// an error. A run of calls from the same line, as in recursion, is
+    /// listed once, and past `MAX_TRACE_LEN` entries the rest are counted
+    /// rather than listed.
+    fn trace(&self) -> Vec<String> {
+        let mut trace = vec![];
+        let mut frames = self.call_stack.iter().rev().dedup_with_count();
+        for (count, frame) in frames.by_ref().take(MAX_TRACE_LEN) {
+            trace.push(format!("at {}", frame));
+            if count > 1 {
+                trace.push(format!("... repeated {} more times", count - 1));
+            }
+        }
+        let hidden: usize = frames.map(|(count, _)| count).sum();
+        if hidden > 0 {
+            trace.push(format!("... {} more", hidden));
+        }
+        trace
     }
 
-    fn visit_expr_grouping(&mut self, grouping: &Grouping) -> Self::ExprResult {
-        let Grouping { expression } = grouping;
-        self.visit_expr(expression)
+    /// Turns a value thrown but never caught into an error describing it.
+    fn report_uncaught<T>(&mut self, result: Result<T>) -> Result<T> {
+        result.map_err(|err| match err.downcast_ref::<ThrownValue>() {
+            Some(ThrownValue { span, trace }) => {
+                let value = self.thrown_value.take().unwrap_or(RuntimeValue::Nil);
+                LoxError::Runtime {
+                    message: format!(
+                        "Uncaught exception {} on line {}.",
+                        self.stringify(&value),
+                        span
+                    ),
+                    span: *span,
+                    trace: trace.clone(),
+                }
+                .into()
+            }
+            None => err,
+        })
     }
 
-    fn visit_expr_literal(&mut self, literal: &Literal) -> Self::ExprResult {
-        match literal {
-            Literal::Number(x) => Ok(RuntimeValue::Number(*x)),
-            Literal::String(x) => Ok(RuntimeValue::String(x.to_owned())),
-            Literal::Bool(x) => Ok(RuntimeValue::Bool(*x)),
-            Literal::Nil => Ok(RuntimeValue::Nil),
+    /// Records the exit code if `result` is the program calling `exit`.
+    fn catch_exit<T>(&mut self, result: Result<T>) -> Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(err) => match err.downcast_ref::<ExitError>() {
+                Some(ExitError(code)) => {
+                    self.exit_code = Some(*code);
+                    Ok(None)
+                }
+                None => Err(err),
+            },
         }
     }
 
-    fn visit_expr_logical(&mut self, logical: &Logical) -> Self::ExprResult {
-        let Logical {
-            left,
-            operator,
-            right,
-        } = logical;
-        let left_val = self.visit_expr(left)?;
+    fn define_in_env(
+        &mut self,
+        env: &Rc<RefCell<Environment>>,
+        name: Symbol,
+        value: RuntimeValue,
+    ) -> Index {
+        let index = self.variables.insert(value);
+        // nothing else can refer to a variable that has been shadowed within
+        // the same scope, so it can be freed right away
+        if let Some(shadowed) = env.borrow_mut().define(name, index) {
+            self.variables.remove(shadowed);
+        }
+        index
+    }
 
-        match operator {
-            TokenKind::Or => {
-                if is_truthy(&left_val) {
-                    return Ok(left_val);
+    /// Frees the variables of a scope that is no longer in use, along with
+    /// any enclosing scopes that were only kept alive by it. Scopes that are
+    /// still referenced elsewhere, such as by a closure, are left alone.
+    fn release_env(&mut self, env: Rc<RefCell<Environment>>) {
+        let mut next = Some(env);
+        while let Some(env) = next.take() {
+            // a function or class declared in the scope refers back to it,
+            // so unless something else refers to the scope too, its
+            // variables are dropped to break the cycle
+            if env.borrow().enclosing().is_some()
+                && Rc::strong_count(&env) == 1 + self.self_references(&env)
+            {
+                self.free_variables(&env);
+            }
+            if let Ok(env) = Rc::try_unwrap(env) {
+                let (enclosing, indices) = env.into_inner().into_parts();
+                for index in indices {
+                    self.variables.remove(index);
                 }
+                next = enclosing;
             }
-            TokenKind::And => {
-                if !is_truthy(&left_val) {
-                    return Ok(left_val);
+        }
+    }
+
+    /// Counts the references to `env` from the functions and classes stored
+    /// in its own variables.
+    fn self_references(&self, env: &Rc<RefCell<Environment>>) -> usize {
+        env.borrow()
+            .indices()
+            .into_iter()
+            .filter_map(|index| self.variables.get(index))
+            .map(|value| match value {
+                RuntimeValue::Callable(_, closure) if Rc::ptr_eq(closure, env) => 1,
+                // the class is shared by copies of it and by its instances,
+                // which may be elsewhere, so its methods only count if
+                // nothing else has it
+                RuntimeValue::Class(class) if Rc::strong_count(class) == 1 => {
+                    class.references_to(env)
+                }
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Frees the variables of `env` while it is still referenced, along with
+    /// the scopes holding `super` for the methods of classes declared in it.
+    fn free_variables(&mut self, env: &Rc<RefCell<Environment>>) {
+        let values: Vec<RuntimeValue> = env
+            .borrow()
+            .indices()
+            .into_iter()
+            .filter_map(|index| self.variables.remove(index))
+            .collect();
+        for value in values {
+            let class = match value {
+                RuntimeValue::Class(class) => class,
+                _ => continue,
+            };
+            let class = match Rc::try_unwrap(class) {
+                Ok(class) => class,
+                Err(_) => continue,
+            };
+            let mut method_envs: Vec<Rc<RefCell<Environment>>> = vec![];
+            for method in class.methods.into_values() {
+                if let RuntimeValue::Callable(_, closure) = method {
+                    if !Rc::ptr_eq(&closure, env)
+                        && !method_envs.iter().any(|other| Rc::ptr_eq(other, &closure))
+                    {
+                        method_envs.push(closure);
+                    }
                 }
             }
-            _ => return Err(anyhow!("Unexpected logical operator: {}.", operator)),
+            for method_env in method_envs {
+                self.release_env(method_env);
+            }
         }
+    }
 
-        self.visit_expr(right)
+    // this method only exists to avoid a borrow checker issue
+    fn define_in_self_env(&mut self, name: Symbol, value: RuntimeValue) -> Index {
+        let env = self.env.clone();
+        self.define_in_env(&env, name, value)
     }
 
-    fn visit_expr_unary(&mut self, unary: &Unary) -> Self::ExprResult {
-        let Unary { operator, right } = unary;
-        let right_val = self.visit_expr(right)?;
+    fn update_var(&mut self, index: Index, value: RuntimeValue) -> Result<()> {
+        if let Some(old_value) = self.variables.get_mut(index) {
+            *old_value = value;
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Variable #{:?} is unexpectedly not allocated a value.",
+                index
+            ))
+        }
+    }
 
-        match operator {
-            TokenKind::Bang => Ok(RuntimeValue::Bool(is_truthy(&right_val))),
-            TokenKind::Minus => match right_val {
-                RuntimeValue::Number(x) => Ok(RuntimeValue::Number(-x)),
-                _ => Err(anyhow!("Unexpected operand after -: {}.", right_val)),
+    fn lookup_in_env(&self, env: &Rc<RefCell<Environment>>, name: Symbol) -> Result<RuntimeValue> {
+        let index = env
+            .borrow()
+            .get(name)
+            .ok_or_else(|| anyhow!("Undefined variable {}.", name))?;
+        self.lookup_index(index, name)
+    }
+
+    /// Finds the variable `name` using the scope depth computed by the
+    /// resolver, or in the global scope if it was not resolved to a local.
+    fn resolve_variable(&self, name: Symbol, depth: Option<usize>) -> Result<Index> {
+        match depth {
+            Some(depth) => self.env.borrow().get_at(depth, name),
+            None => self.env.borrow().get_global(name),
+        }
+        .ok_or_else(|| anyhow!("Undefined variable {}.", name))
+    }
+
+    fn lookup_index(&self, index: Index, name: Symbol) -> Result<RuntimeValue> {
+        if let Some(value) = self.variables.get(index) {
+            Ok(value.clone())
+        } else {
+            Err(anyhow!("Variable {} was unexpectedly deallocated.", name))
+        }
+    }
+
+    /// Binds the names in `param` to the argument `arg`, destructuring it
+    /// if the parameter is a list pattern.
+    fn bind_param(
+        &mut self,
+        env: &Rc<RefCell<Environment>>,
+        param: &Param,
+        arg: RuntimeValue,
+    ) -> Result<()> {
+        match param {
+            Param::Name(name) => {
+                self.define_in_env(env, *name, arg);
+                Ok(())
+            }
+            Param::List(params) => {
+                let elements = match &arg {
+                    RuntimeValue::List(elements) => elements.borrow().clone(),
+                    _ => {
+                        return Err(anyhow!(
+                            "Expected a list to destructure into {} but got {}.",
+                            param,
+                            arg
+                        ))
+                    }
+                };
+                if elements.len() != params.len() {
+                    return Err(anyhow!(
+                        "Expected a list of {} elements to destructure into {} but got {}.",
+                        params.len(),
+                        param,
+                        arg
+                    ));
+                }
+                for (param, element) in zip(params, elements) {
+                    self.bind_param(env, param, element)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Evaluates the collection and index of an element lookup or
+    /// assignment, checking that the index can be used with the collection.
+    fn eval_index(&mut self, object: &Expr, index: &Expr, span: Span) -> Result<Element> {
+        let object = self.visit_expr(object)?;
+        let index = self.visit_expr(index)?;
+        match object {
+            RuntimeValue::List(list) => {
+                let position = match index {
+                    RuntimeValue::Number(n) if n.fract() == 0.0 => n,
+                    value => {
+                        return Err(anyhow!(
+                            "List index must be an integer but got {} on line {}.",
+                            value,
+                            span
+                        ))
+                    }
+                };
+                let len = list.borrow().len();
+                if position < 0.0 || position >= len as f64 {
+                    return Err(anyhow!(
+                        "List index {} is out of range for a list of length {} on line {}.",
+                        position,
+                        len,
+                        span
+                    ));
+                }
+                Ok(Element::List(list, position as usize))
+            }
+            RuntimeValue::Map(map) => match MapKey::from_value(&index) {
+                Some(key) => Ok(Element::Map(map, key)),
+                None => Err(anyhow!(
+                    "Map keys must be strings or numbers but got {} on line {}.",
+                    index,
+                    span
+                )),
             },
-            _ => Err(anyhow!("Unexpected unary operator: {}.", operator)),
+            value => Err(anyhow!(
+                "Only lists and maps can be indexed, not {}.",
+                value
+            )),
         }
     }
 
-    fn visit_expr_variable(&mut self, variable: &Variable) -> Self::ExprResult {
-        let Variable { name } = variable;
-        self.lookup_in_env(&self.env, name)
+    /// Returns a copy of `method` whose closure has `this` bound to the
+    /// given instance.
+    fn bind_method(&mut self, method: &RuntimeValue, instance: RuntimeValue) -> RuntimeValue {
+        if let RuntimeValue::Callable(ast, closure) = method {
+            let method_env = Environment::enclose(closure);
+            self.define_in_env(&method_env, Symbol::THIS, instance);
+            RuntimeValue::Callable(ast.clone(), method_env)
+        } else {
+            method.clone()
+        }
     }
-}
 
-fn is_truthy(value: &RuntimeValue) -> bool {
-    match value {
-        RuntimeValue::Bool(x) => *x,
-        RuntimeValue::Callable(_, _) => true,
-        RuntimeValue::Nil => false,
-        RuntimeValue::Number(x) => *x != 0.0,
-        RuntimeValue::String(_) => true,
+    /// Evaluates the function and arguments of a call, without calling it.
+    fn eval_call(&mut self, call: &Call) -> Result<(RuntimeValue, Vec<RuntimeValue>)> {
+        let Call {
+            callee, arguments, ..
+        } = call;
+        let callee = self.visit_expr(callee)?;
+        let mut argument_vals = vec![];
+        for arg in arguments {
+            argument_vals.push(self.visit_expr(arg)?);
+        }
+        Ok((callee, argument_vals))
+    }
+
+    /// Calls `callee`, which was called from `line`.
+    fn invoke_function(
+        &mut self,
+        callee: RuntimeValue,
+        arguments: Vec<RuntimeValue>,
+        line: u32,
+    ) -> Result<RuntimeValue> {
+        self.check_cancelled()?;
+
+        if let RuntimeValue::Class(class) = callee {
+            let instance = RuntimeValue::Instance(Rc::new(RefCell::new(LoxInstance {
+                class: class.clone(),
+                fields: HashMap::new(),
+            })));
+            if let Some(initializer) = class.find_method(Symbol::INIT) {
+                let initializer = self.bind_method(initializer, instance.clone());
+                self.invoke_function(initializer, arguments, line)?;
+            } else if !arguments.is_empty() {
+                return Err(anyhow!("Expected 0 arguments but got {}.", arguments.len()));
+            }
+            return Ok(instance);
+        }
+
+        if let RuntimeValue::NativeFunction(native) = callee {
+            let max_arity = native.arity + native.optional;
+            if arguments.len() < native.arity || arguments.len() > max_arity {
+                let expected = if native.optional == 0 {
+                    native.arity.to_string()
+                } else {
+                    format!("{} to {}", native.arity, max_arity)
+                };
+                return Err(anyhow!(
+                    "{} expects {} argument{} but got {}.",
+                    native.name,
+                    expected,
+                    if max_arity == 1 { "" } else { "s" },
+                    arguments.len()
+                ));
+            }
+            return (native.func)(self, arguments);
+        }
+
+        if let RuntimeValue::Callable(mut function, mut closure) = callee {
+            let mut arguments = arguments;
+            self.call_stack
+                .push(format!("{} (line {})", function.name, line));
+            // a body that ends in a tail call leaves the call for us to make
+            // here, so each iteration is one function in a chain of tail calls,
+            // which takes the place of its caller on the call stack
+            let mut tail_span = None;
+            let result = loop {
+                let tail_call = match self.call_function(&function, closure, arguments) {
+                    Ok(tail_call) => tail_call,
+                    // errors making a tail call, e.g. binding its parameters,
+                    // belong to the call rather than to our caller's call
+                    Err(err) => match tail_span {
+                        Some(span) => break Err(self.locate_error(err, span)),
+                        None => break Err(err),
+                    },
+                };
+                match tail_call {
+                    Some(tail_call) => {
+                        if let Err(err) = self.check_cancelled() {
+                            break Err(self.locate_error(err, tail_call.span));
+                        }
+                        function = tail_call.function;
+                        closure = tail_call.closure;
+                        arguments = tail_call.arguments;
+                        tail_span = Some(tail_call.span);
+                        if let Some(frame) = self.call_stack.last_mut() {
+                            *frame =
+                                format!("{} (line {})", function.name, tail_call.span.start_line);
+                        }
+                    }
+                    None => break Ok(self.return_value.take().unwrap_or(RuntimeValue::Nil)),
+                }
+            };
+            self.call_stack.pop();
+            result
+        } else {
+            Err(anyhow!("Can only call functions and classes."))
+        }
+    }
+
+    /// Runs the body of a Lox function, leaving any value it returns in
+    /// `return_value`. If the body ended in a tail call, the call is
+    /// returned to be made by the caller instead.
+    fn call_function(
+        &mut self,
+        function: &Function,
+        closure: Rc<RefCell<Environment>>,
+        arguments: Vec<RuntimeValue>,
+    ) -> Result<Option<TailCall>> {
+        let Function {
+            name, params, body, ..
+        } = function;
+        if params.len() != arguments.len() {
+            return Err(anyhow!(
+                "Expected {} arguments but got {}.",
+                params.len(),
+                arguments.len()
+            ));
+        }
+
+        // construct a new environment for the lifetime of the callable
+        // where the parameter variables have been assigned the values
+        // of the callable arguments
+        let invoke_env = Environment::enclose(&closure);
+        for (param, arg) in zip(params, arguments) {
+            self.bind_param(&invoke_env, param, arg)?;
+        }
+
+        // update the environment being used to interpret statements
+        let old_env = std::mem::replace(&mut self.env, invoke_env);
+
+        if self.call_depth >= self.max_call_depth {
+            self.env = old_env;
+            return Err(anyhow!(
+                "Stack overflow: exceeded max call depth of {} when calling {}.",
+                self.max_call_depth,
+                name
+            ));
+        }
+
+        // evaluate each statement within our new environment, where
+        // none of the caller's try statements are enclosing
+        self.call_depth += 1;
+        let try_depth = std::mem::replace(&mut self.try_depth, 0);
+        let result = body
+            .iter()
+            .try_for_each(|sub_stmt| self.visit_stmt(sub_stmt));
+        self.try_depth = try_depth;
+        self.call_depth -= 1;
+
+        // restore the old environment, however the body finished,
+        // and free the call's variables unless a closure kept them
+        let invoke_env = std::mem::replace(&mut self.env, old_env);
+        self.release_env(invoke_env);
+        self.release_env(closure);
+
+        match result {
+            Ok(()) => Ok(None),
+            Err(err) if err.is::<ReturnValueError>() => Ok(self.tail_call.take()),
+            Err(err) => Err(err),
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl StmtVisitor for Interpreter {
+    type StmtResult = Result<()>;
 
-    #[test]
-    fn runtime_values_equality() {
-        assert_eq!(RuntimeValue::Number(3.0), RuntimeValue::Number(3.0));
-        assert_eq!(RuntimeValue::Number(-0.5), RuntimeValue::Number(-0.5));
-        assert_eq!(RuntimeValue::Number(0.0), RuntimeValue::Number(0.0));
-        assert_ne!(RuntimeValue::Number(0.1), RuntimeValue::Number(0.2));
-        assert_ne!(RuntimeValue::Number(-5.0), RuntimeValue::Number(-6.0));
+    fn visit_stmt(&mut self, stmt: &Stmt) -> Self::StmtResult {
+        if let Some(on_stmt) = &mut self.on_stmt {
+            on_stmt(stmt, &self.env.borrow());
+        }
+        let result = self
+            .take_step()
+            .and_then(|_| visitor::dispatch_stmt(self, stmt));
+        result.map_err(|err| self.locate_error(err, stmt.span()))
+    }
+
+    fn visit_stmt_block(&mut self, block: &Block) -> Self::StmtResult {
+        let Block { statements, .. } = block;
+        // create an environment that will encapsulate the old one
+        let new_env = Environment::enclose(&self.env);
+        self.execute_block(statements, new_env)
+    }
+
+    fn visit_stmt_class(&mut self, class: &Class) -> Self::StmtResult {
+        let Class {
+            name,
+            superclass,
+            methods,
+            ..
+        } = class;
+
+        let superclass = match superclass {
+            Some(variable) => match self.visit_expr_variable(variable)? {
+                RuntimeValue::Class(superclass) => Some(superclass),
+                _ => return Err(anyhow!("Superclass of {} must be a class.", name)),
+            },
+            None => None,
+        };
+
+        // bind the class name before creating the methods so that they can
+        // refer to the class itself
+        let index = self.define_in_self_env(*name, RuntimeValue::Nil);
+
+        // methods of a subclass get an extra scope where "super" is bound
+        let method_env = match &superclass {
+            Some(superclass) => {
+                let method_env = Environment::enclose(&self.env);
+                self.define_in_env(
+                    &method_env,
+                    Symbol::SUPER,
+                    RuntimeValue::Class(superclass.clone()),
+                );
+                method_env
+            }
+            None => self.env.clone(),
+        };
+
+        let methods = methods
+            .iter()
+            .map(|method| {
+                (
+                    method.name,
+                    RuntimeValue::Callable(Rc::new(method.clone()), method_env.clone()),
+                )
+            })
+            .collect();
+        let class = RuntimeValue::Class(Rc::new(LoxClass {
+            name: name.to_string(),
+            superclass,
+            methods,
+        }));
+        self.update_var(index, class)?;
+
+        Ok(())
+    }
+
+    fn visit_stmt_do_while(&mut self, do_while: &DoWhile) -> Self::StmtResult {
+        let DoWhile {
+            body, condition, ..
+        } = do_while;
+        loop {
+            self.check_cancelled()?;
+            self.visit_stmt(body)?;
+            if !is_truthy(&self.visit_expr(condition)?) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn visit_stmt_expression(&mut self, expression: &Expression) -> Self::StmtResult {
+        let Expression { expression, .. } = expression;
+        self.visit_expr(expression)?;
+        Ok(())
+    }
+
+    fn visit_stmt_print(&mut self, print: &Print) -> Self::StmtResult {
+        let Print { expressions, .. } = print;
+        let mut line = String::new();
+        for (i, expression) in expressions.iter().enumerate() {
+            let value = self.visit_expr(expression)?;
+            if i > 0 {
+                line.push(' ');
+            }
+            line.push_str(&self.stringify(&value));
+        }
+        self.print_line(&line)?;
+        Ok(())
+    }
+
+    fn visit_stmt_for(&mut self, for_: &For) -> Self::StmtResult {
+        let For {
+            initializer,
+            condition,
+            increment,
+            body,
+            ..
+        } = for_;
+        // a variable declared by the initializer is shared by every
+        // iteration, in a scope around the whole loop
+        let env = Environment::enclose(&self.env);
+        self.execute_in(env, |interpreter| {
+            if let Some(initializer) = initializer {
+                interpreter.visit_stmt(initializer)?;
+            }
+            loop {
+                if let Some(condition) = condition {
+                    if !is_truthy(&interpreter.visit_expr(condition)?) {
+                        return Ok(());
+                    }
+                }
+                interpreter.check_cancelled()?;
+                interpreter.visit_stmt(body)?;
+                if let Some(increment) = increment {
+                    interpreter.visit_expr(increment)?;
+                }
+            }
+        })
+    }
+
+    fn visit_stmt_for_in(&mut self, for_in: &ForIn) -> Self::StmtResult {
+        let ForIn {
+            name,
+            iterable,
+            body,
+            ..
+        } = for_in;
+        let elements = match self.visit_expr(iterable)? {
+            // elements added to a list while looping over it are visited too
+            RuntimeValue::List(list) => list,
+            RuntimeValue::String(s) => Rc::new(RefCell::new(
+                s.chars()
+                    .map(|c| RuntimeValue::String(c.to_string()))
+                    .collect(),
+            )),
+            value => {
+                return Err(anyhow!(
+                    "Only lists and strings can be looped over, not {}.",
+                    value
+                ))
+            }
+        };
+        for index in 0.. {
+            let element = match elements.borrow().get(index) {
+                Some(element) => element.clone(),
+                None => break,
+            };
+            self.check_cancelled()?;
+            let env = Environment::enclose(&self.env);
+            self.define_in_env(&env, *name, element);
+            self.execute_block(std::slice::from_ref(body), env)?;
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_function(&mut self, function: &Function) -> Self::StmtResult {
+        let name = &function.name;
+        let function = Rc::new(function.clone());
+
+        // initially bind function name to "nil" value so that it exists
+        // in the function's closure so that recursion works
+        let index = self.define_in_self_env(*name, RuntimeValue::Nil);
+
+        let callable = RuntimeValue::Callable(function, self.env.clone());
+
+        // update the function name's binding to actual Callable value
+        self.update_var(index, callable)?;
+
+        Ok(())
+    }
+
+    fn visit_stmt_if(&mut self, if_: &If) -> Self::StmtResult {
+        let If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } = if_;
+        if is_truthy(&self.visit_expr(condition)?) {
+            self.visit_stmt(then_branch)?;
+        } else if let Some(unwrapped) = else_branch {
+            self.visit_stmt(unwrapped)?;
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_return(&mut self, return_: &Return) -> Self::StmtResult {
+        let Return { value, .. } = return_;
+        let value = match value {
+            // unless a try statement needs to see what the call throws,
+            // calling a Lox function is left to the function returning, so
+            // that tail recursion doesn't grow the stack
+            Expr::Call(call) if self.try_depth == 0 => {
+                self.take_step()?;
+                let (callee, arguments) = self.eval_call(call)?;
+                match callee {
+                    RuntimeValue::Callable(function, closure)
+                        if function.params.len() == arguments.len() =>
+                    {
+                        self.tail_call = Some(TailCall {
+                            function,
+                            closure,
+                            arguments,
+                            span: call.span,
+                        });
+                        return Err(ReturnValueError.into());
+                    }
+                    callee => self
+                        .invoke_function(callee, arguments, call.span.start_line)
+                        .map_err(|err| self.locate_error(err, call.span))?,
+                }
+            }
+            value => self.visit_expr(value)?,
+        };
+        self.return_value = Some(value);
+        Err(ReturnValueError.into())
+    }
+
+    fn visit_stmt_switch(&mut self, switch: &Switch) -> Self::StmtResult {
+        let Switch {
+            subject,
+            cases,
+            default,
+            ..
+        } = switch;
+        let subject = self.visit_expr(subject)?;
+        // case values are only evaluated until one matches
+        for (value, body) in cases {
+            if self.visit_expr(value)? == subject {
+                return self.execute_block(body, Environment::enclose(&self.env));
+            }
+        }
+        match default {
+            Some(body) => self.execute_block(body, Environment::enclose(&self.env)),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_stmt_throw(&mut self, throw: &Throw) -> Self::StmtResult {
+        let Throw { value, span } = throw;
+        let value = self.visit_expr(value)?;
+        self.thrown_value = Some(value);
+        Err(ThrownValue {
+            span: *span,
+            trace: self.trace(),
+        }
+        .into())
+    }
+
+    fn visit_stmt_try(&mut self, try_: &Try) -> Self::StmtResult {
+        let Try {
+            body,
+            catch_name,
+            catch_body,
+            ..
+        } = try_;
+        // only values thrown by the program are caught, not other errors
+        self.try_depth += 1;
+        let result = self.execute_block(body, Environment::enclose(&self.env));
+        self.try_depth -= 1;
+        match result {
+            Err(err) if err.is::<ThrownValue>() => {
+                let value = self.thrown_value.take().unwrap_or(RuntimeValue::Nil);
+                let catch_env = Environment::enclose(&self.env);
+                self.define_in_env(&catch_env, *catch_name, value);
+                self.execute_block(catch_body, catch_env)
+            }
+            result => result,
+        }
+    }
+
+    fn visit_stmt_var(&mut self, var: &Var) -> Self::StmtResult {
+        let Var {
+            name, initializer, ..
+        } = var;
+        let value = match initializer {
+            Some(expr) => self.visit_expr(expr)?,
+            None => RuntimeValue::Nil,
+        };
+        self.define_in_self_env(*name, value);
+        Ok(())
+    }
+
+    fn visit_stmt_while(&mut self, while_: &While) -> Self::StmtResult {
+        let While {
+            condition, body, ..
+        } = while_;
+        while is_truthy(&self.visit_expr(condition)?) {
+            self.check_cancelled()?;
+            self.visit_stmt(body)?;
+        }
+        Ok(())
+    }
+}
+
+impl ExprVisitor for Interpreter {
+    type ExprResult = Result<RuntimeValue>;
+
+    fn visit_expr(&mut self, expr: &Expr) -> Self::ExprResult {
+        self.take_step()?;
+        // errors are located at the innermost expression they came from
+        visitor::dispatch_expr(self, expr).map_err(|err| self.locate_error(err, expr.span()))
+    }
+
+    fn visit_expr_assign(&mut self, assign: &Assign) -> Self::ExprResult {
+        let Assign {
+            name,
+            operator,
+            value,
+            depth,
+            span,
+        } = assign;
+        let evaluated = match operator {
+            Some(operator) => {
+                let current =
+                    self.lookup_index(self.resolve_variable(*name, depth.get())?, *name)?;
+                let value = self.visit_expr(value)?;
+                self.apply_binary(operator, current, value, span)?
+            }
+            None => self.visit_expr(value)?,
+        };
+        let index = self.resolve_variable(*name, depth.get())?;
+        self.update_var(index, evaluated.clone())?;
+        Ok(evaluated)
+    }
+
+    fn visit_expr_binary(&mut self, binary: &Binary) -> Self::ExprResult {
+        let Binary {
+            left,
+            operator,
+            right,
+            span,
+        } = binary;
+        let left_val = self.visit_expr(left)?;
+        let right_val = self.visit_expr(right)?;
+        self.apply_binary(operator, left_val, right_val, span)
+    }
+
+    fn visit_expr_call(&mut self, call: &Call) -> Self::ExprResult {
+        let (callee, arguments) = self.eval_call(call)?;
+        self.invoke_function(callee, arguments, call.span.start_line)
+    }
+
+    fn visit_expr_get(&mut self, get: &Get) -> Self::ExprResult {
+        let Get { object, name, span } = get;
+        let object_val = self.visit_expr(object)?;
+        self.get_property(object_val, *name, span)
+    }
+
+    fn visit_expr_grouping(&mut self, grouping: &Grouping) -> Self::ExprResult {
+        let Grouping { expression, .. } = grouping;
+        self.visit_expr(expression)
+    }
+
+    fn visit_expr_index(&mut self, index: &IndexExpr) -> Self::ExprResult {
+        let IndexExpr {
+            object,
+            index,
+            span,
+        } = index;
+        self.eval_index(object, index, *span)?.get(span)
+    }
+
+    fn visit_expr_interpolation(&mut self, interpolation: &Interpolation) -> Self::ExprResult {
+        let Interpolation { parts, .. } = interpolation;
+        let mut result = String::new();
+        for part in parts {
+            match part {
+                StringPart::Text(text) => result.push_str(text),
+                StringPart::Expr(expr) => {
+                    let value = self.visit_expr(expr)?;
+                    result.push_str(&self.stringify(&value));
+                }
+            }
+        }
+        Ok(RuntimeValue::String(result))
+    }
+
+    fn visit_expr_list_literal(&mut self, list_literal: &ListLiteral) -> Self::ExprResult {
+        let ListLiteral { elements, .. } = list_literal;
+        let mut values = vec![];
+        for element in elements {
+            values.push(self.visit_expr(element)?);
+        }
+        Ok(RuntimeValue::List(Rc::new(RefCell::new(values))))
+    }
+
+    fn visit_expr_literal(&mut self, literal: &LiteralExpr) -> Self::ExprResult {
+        match &literal.value {
+            Literal::Number(x) => Ok(RuntimeValue::Number(*x)),
+            Literal::String(x) => Ok(RuntimeValue::String(x.to_owned())),
+            Literal::Bool(x) => Ok(RuntimeValue::Bool(*x)),
+            Literal::Nil => Ok(RuntimeValue::Nil),
+        }
+    }
+
+    fn visit_expr_logical(&mut self, logical: &Logical) -> Self::ExprResult {
+        let Logical {
+            left,
+            operator,
+            right,
+            ..
+        } = logical;
+        let left_val = self.visit_expr(left)?;
+
+        match operator {
+            TokenKind::Or => {
+                if is_truthy(&left_val) {
+                    return Ok(left_val);
+                }
+            }
+            TokenKind::And => {
+                if !is_truthy(&left_val) {
+                    return Ok(left_val);
+                }
+            }
+            // unlike "or", only nil is replaced
+            TokenKind::QuestionQuestion => {
+                if left_val != RuntimeValue::Nil {
+                    return Ok(left_val);
+                }
+            }
+            _ => return Err(anyhow!("Unexpected logical operator: {}.", operator)),
+        }
+
+        self.visit_expr(right)
+    }
+
+    fn visit_expr_set(&mut self, set: &Set) -> Self::ExprResult {
+        let Set {
+            object,
+            name,
+            operator,
+            value,
+            span,
+        } = set;
+        let object_val = self.visit_expr(object)?;
+        if let RuntimeValue::Instance(instance) = &object_val {
+            let value = match operator {
+                Some(operator) => {
+                    let current = self.get_property(object_val.clone(), *name, span)?;
+                    let value = self.visit_expr(value)?;
+                    self.apply_binary(operator, current, value, span)?
+                }
+                None => self.visit_expr(value)?,
+            };
+            instance.borrow_mut().fields.insert(*name, value.clone());
+            Ok(value)
+        } else {
+            Err(anyhow!("Only instances have fields."))
+        }
+    }
+
+    fn visit_expr_map_literal(&mut self, map_literal: &MapLiteral) -> Self::ExprResult {
+        let MapLiteral { entries, span } = map_literal;
+        let mut map = HashMap::new();
+        for (key, value) in entries {
+            let key = self.visit_expr(key)?;
+            let key = MapKey::from_value(&key).ok_or_else(|| {
+                anyhow!(
+                    "Map keys must be strings or numbers but got {} on line {}.",
+                    key,
+                    span
+                )
+            })?;
+            map.insert(key, self.visit_expr(value)?);
+        }
+        Ok(RuntimeValue::Map(Rc::new(RefCell::new(map))))
+    }
+
+    fn visit_expr_set_index(&mut self, set_index: &SetIndex) -> Self::ExprResult {
+        let SetIndex {
+            object,
+            index,
+            operator,
+            value,
+            span,
+        } = set_index;
+        let element = self.eval_index(object, index, *span)?;
+        let value = match operator {
+            Some(operator) => {
+                let current = element.get(span)?;
+                let value = self.visit_expr(value)?;
+                self.apply_binary(operator, current, value, span)?
+            }
+            None => self.visit_expr(value)?,
+        };
+        element.set(value.clone());
+        Ok(value)
+    }
+
+    fn visit_expr_ternary(&mut self, ternary: &Ternary) -> Self::ExprResult {
+        let Ternary {
+            condition,
+            then_expr,
+            else_expr,
+            ..
+        } = ternary;
+        if is_truthy(&self.visit_expr(condition)?) {
+            self.visit_expr(then_expr)
+        } else {
+            self.visit_expr(else_expr)
+        }
+    }
+
+    fn visit_expr_super(&mut self, super_: &Super) -> Self::ExprResult {
+        let Super { method, span } = super_;
+        let superclass = self.lookup_in_env(&self.env, Symbol::SUPER)?;
+        let instance = self.lookup_in_env(&self.env, Symbol::THIS)?;
+        if let RuntimeValue::Class(superclass) = superclass {
+            match superclass.find_method(*method) {
+                Some(method) => Ok(self.bind_method(method, instance)),
+                None => Err(anyhow!("Undefined property {} on line {}.", method, span)),
+            }
+        } else {
+            Err(anyhow!("Compiler error: 'super' is not bound to a class."))
+        }
+    }
+
+    fn visit_expr_this(&mut self, _: &This) -> Self::ExprResult {
+        self.lookup_in_env(&self.env, Symbol::THIS)
+    }
+
+    fn visit_expr_unary(&mut self, unary: &Unary) -> Self::ExprResult {
+        let Unary {
+            operator, right, ..
+        } = unary;
+        let right_val = self.visit_expr(right)?;
+
+        match operator {
+            TokenKind::Bang => Ok(RuntimeValue::Bool(!is_truthy(&right_val))),
+            TokenKind::Minus => match right_val {
+                RuntimeValue::Number(x) => Ok(RuntimeValue::Number(-x)),
+                _ => Err(anyhow!("Unexpected operand after -: {}.", right_val)),
+            },
+            TokenKind::Tilde => {
+                let x = right_val
+                    .unwrap_number(anyhow!("Unexpected operand after ~: {}.", right_val))?;
+                Ok(RuntimeValue::Number(!to_integer(x)? as f64))
+            }
+            _ => Err(anyhow!("Unexpected unary operator: {}.", operator)),
+        }
+    }
+
+    fn visit_expr_variable(&mut self, variable: &Variable) -> Self::ExprResult {
+        let Variable { name, depth, .. } = variable;
+        let index = self.resolve_variable(*name, depth.get())?;
+        self.lookup_index(index, *name)
+    }
+}
+
+/// Converts the operand of a bitwise operator to an integer. Only whole
+/// numbers that fit in 64 bits are accepted.
+pub(crate) fn to_integer(x: f64) -> Result<i64> {
+    // i64::MAX isn't exactly representable, so the upper bound is 2^63
+    if x.fract() != 0.0 || x < i64::MIN as f64 || x >= -(i64::MIN as f64) {
+        return Err(anyhow!(
+            "Bitwise operands must be 64-bit integers but got {}.",
+            x
+        ));
+    }
+    Ok(x as i64)
+}
+
+/// Applies the bitwise operator `operator` to two numbers.
+pub(crate) fn bitwise(operator: &TokenKind, x: f64, y: f64) -> Result<f64> {
+    let (x, y) = (to_integer(x)?, to_integer(y)?);
+    let result = match operator {
+        TokenKind::Ampersand => x & y,
+        TokenKind::Pipe => x | y,
+        TokenKind::Caret => x ^ y,
+        TokenKind::LessLess | TokenKind::GreaterGreater => {
+            if !(0..64).contains(&y) {
+                return Err(anyhow!("Shift amounts must be from 0 to 63 but got {}.", y));
+            }
+            if *operator == TokenKind::LessLess {
+                x << y
+            } else {
+                x >> y
+            }
+        }
+        _ => return Err(anyhow!("Unexpected bitwise operator: {}", operator)),
+    };
+    Ok(result as f64)
+}
+
+pub(crate) fn is_truthy(value: &RuntimeValue) -> bool {
+    match value {
+        RuntimeValue::Bool(x) => *x,
+        RuntimeValue::Callable(_, _) => true,
+        RuntimeValue::Class(_) => true,
+        RuntimeValue::Instance(_) => true,
+        RuntimeValue::List(_) => true,
+        RuntimeValue::Map(_) => true,
+        RuntimeValue::NativeFunction(_) => true,
+        RuntimeValue::Nil => false,
+        RuntimeValue::Number(x) => *x != 0.0,
+        RuntimeValue::String(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+    use std::cell::Cell;
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    #[test]
+    fn runtime_values_equality() {
+        assert_eq!(RuntimeValue::Number(3.0), RuntimeValue::Number(3.0));
+        assert_eq!(RuntimeValue::Number(-0.5), RuntimeValue::Number(-0.5));
+        assert_eq!(RuntimeValue::Number(0.0), RuntimeValue::Number(0.0));
+        assert_ne!(RuntimeValue::Number(0.1), RuntimeValue::Number(0.2));
+        assert_ne!(RuntimeValue::Number(-5.0), RuntimeValue::Number(-6.0));
+    }
+
+    #[test]
+    fn closures_share_function_bodies() {
+        let source = "var fs = [];
+            for (var i = 0; i < 3; i = i + 1) { fun f() { return i; } push(fs, f); }
+            fs;";
+        let fs = match crate::run_value(source).unwrap() {
+            RuntimeValue::List(fs) => fs,
+            value => panic!("expected a list but got {}", value),
+        };
+        let fs = fs.borrow();
+        match (&fs[0], &fs[2]) {
+            (RuntimeValue::Callable(first, _), RuntimeValue::Callable(last, _)) => {
+                assert!(Rc::ptr_eq(&first.body, &last.body))
+            }
+            values => panic!("expected functions but got {:?}", values),
+        }
+    }
+
+    #[test]
+    fn eval_expr_uses_globals() {
+        let mut interpreter = Interpreter::default();
+        crate::run_with(
+            &mut interpreter,
+            "var x = 20; fun double(n) { return n * 2; }",
+        )
+        .unwrap();
+        let value = interpreter.eval_expr("double(x) + 2").unwrap();
+        assert_eq!(value, RuntimeValue::Number(42.0));
+        assert_eq!(interpreter.eval_expr("\"a\" + \"b\"").unwrap(), "ab".into());
+
+        let err = interpreter.eval_expr("x;").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected end of expression, found token ; on line 1:2"
+        );
+        let err = interpreter.eval_expr("y").unwrap_err();
+        assert_eq!(err.to_string(), "Undefined variable y on line 1:1.");
+    }
+
+    #[test]
+    fn runtime_value_conversions() {
+        assert_eq!(RuntimeValue::from(2.5), RuntimeValue::Number(2.5));
+        assert_eq!(f64::try_from(RuntimeValue::from(2.5)).unwrap(), 2.5);
+        assert_eq!(RuntimeValue::from(true), RuntimeValue::Bool(true));
+        assert!(bool::try_from(RuntimeValue::from(true)).unwrap());
+        let s = RuntimeValue::from("lox");
+        assert_eq!(s, RuntimeValue::from("lox".to_owned()));
+        assert_eq!(String::try_from(s).unwrap(), "lox");
+
+        let err = f64::try_from(RuntimeValue::from("1")).unwrap_err();
+        assert_eq!(err.to_string(), "Expected a number but got string.");
+        let err = bool::try_from(RuntimeValue::Nil).unwrap_err();
+        assert_eq!(err.to_string(), "Expected a bool but got nil.");
+        let err = String::try_from(RuntimeValue::from(1.0)).unwrap_err();
+        assert_eq!(err.to_string(), "Expected a string but got number.");
+    }
+
+    #[test]
+    fn uncaught_exceptions() {
+        let mut interpreter = Interpreter::default();
+        let err = interpreter
+            .interpret(&parse(
+                "fun f() {\n  throw [1, \"two\"];\n}\nprint 1;\nf();\nprint 2;",
+            ))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Uncaught exception [1, two] on line 2:3.");
+        assert_eq!(err.downcast::<LoxError>().unwrap().line(), 2);
+        assert_eq!(interpreter.stdout, "1\n");
+    }
+
+    #[test]
+    fn do_while_runs_body_first() {
+        let mut interpreter = Interpreter::default();
+        interpreter
+            .interpret(&parse(
+                "var i = 10; do { print i; i = i + 1; } while (i < 3);
+                 var j = 0; do j = j + 1; while (j < 3); print j;",
+            ))
+            .unwrap();
+        assert_eq!(interpreter.stdout, "10\n3\n");
+    }
+
+    #[test]
+    fn for_loops_with_missing_clauses() {
+        let mut interpreter = Interpreter::default();
+        interpreter
+            .interpret(&parse(
+                "fun f() { var i = 0; for (;;) { i += 1; if (i == 3) return i; } }
+                 print f();
+                 for (var j = 0; j < 2;) { print j; j += 1; }",
+            ))
+            .unwrap();
+        assert_eq!(interpreter.stdout, "3\n0\n1\n");
+    }
+
+    #[test]
+    fn for_in_loops() {
+        let mut interpreter = Interpreter::default();
+        interpreter
+            .interpret(&parse(
+                "var fns = [];
+                 for (x in [1, 2, 3]) { fun get() { return x; } push(fns, get); }
+                 for (f in fns) print f();
+                 for (c in \"hé\") print c;
+                 for (x in []) print x;",
+            ))
+            .unwrap();
+        // each iteration has its own binding, which closures capture
+        assert_eq!(interpreter.stdout, "1\n2\n3\nh\né\n");
+
+        let err = interpreter
+            .interpret(&parse("for (x in 3) print x;"))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Only lists and strings can be looped over, not 3 on line 1:1."
+        );
+    }
+
+    #[test]
+    fn switch_statements() {
+        let source = "fun describe(x) {
+                          switch (x) {
+                            case 1: print \"one\";
+                            case \"two\": var s = x; print s;
+                            case [3]: print \"never\";
+                            default: print \"other\";
+                          }
+                          switch (x) { case 1: print \"again\"; }
+                        }
+                        describe(1); describe(\"two\"); describe(nil);";
+        let mut interpreter = Interpreter::default();
+        interpreter.interpret(&parse(source)).unwrap();
+        assert_eq!(interpreter.stdout, "one\nagain\ntwo\nother\n");
+
+        // the subject is evaluated once, and later cases are not evaluated
+        let source = "var n = 0;
+                      fun next() { n = n + 1; return n; }
+                      switch (next()) { case 0: print 0; case next() - 1: print n; case next(): print n; }";
+        let mut interpreter = Interpreter::default();
+        interpreter.interpret(&parse(source)).unwrap();
+        assert_eq!(interpreter.stdout, "2\n");
+    }
+
+    #[test]
+    fn catch_scopes() {
+        let mut interpreter = Interpreter::default();
+        interpreter
+            .interpret(&parse(
+                "var e = \"outer\";
+                 try { var x = 1; throw x + 1; } catch (e) { print e; }
+                 print e;
+                 try { print \"fine\"; } catch (e) { print \"unreachable\"; }",
+            ))
+            .unwrap();
+        assert_eq!(interpreter.stdout, "2\nouter\nfine\n");
+
+        // other runtime errors are not caught
+        let err = interpreter
+            .interpret(&parse("try { print nil + 1; } catch (e) { print e; }"))
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Unexpected operands for +"));
+    }
+
+    #[test]
+    fn list_indexing() {
+        assert_eq!(
+            crate::run("var l = [1, \"two\", nil]; print l[1]; print l; print [];").unwrap(),
+            "two\n[1, two, nil]\n[]\n"
+        );
+        assert_eq!(
+            crate::run("var l = [1, 2]; l[0] = l[1] * 5; print l;").unwrap(),
+            "[10, 2]\n"
+        );
+        let err = crate::run("var l = [1, 2];\nprint l[2];").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "List index 2 is out of range for a list of length 2 on line 2:7."
+        );
+        let err = crate::run("var l = [1, 2]; l[-1] = 0;").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "List index -1 is out of range for a list of length 2 on line 1:17."
+        );
+        let err = crate::run("var l = [1, 2]; print l[0.5];").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "List index must be an integer but got 0.5 on line 1:23."
+        );
+        let err = crate::run("print \"abc\"[0];").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Only lists and maps can be indexed, not abc on line 1:7."
+        );
+    }
+
+    #[test]
+    fn map_lookup_and_assignment() {
+        assert_eq!(
+            crate::run(
+                r#"var m = {"a": 1, 2: "two"};
+                print m["a"]; print m[2];
+                m["a"] += 10; m[-0] = "zero"; m["new"] = nil;
+                print m;"#
+            )
+            .unwrap(),
+            "1\ntwo\n{0: zero, 2: two, a: 11, new: nil}\n"
+        );
+        let err = crate::run("var m = {};\nprint m[\"x\"];").unwrap_err();
+        assert_eq!(err.to_string(), "Undefined key x on line 2:7.");
+        let err = crate::run("var m = {}; m[nil] = 1;").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Map keys must be strings or numbers but got nil on line 1:13."
+        );
+        let err = crate::run("var m = {true: 1};").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Map keys must be strings or numbers but got true on line 1:9."
+        );
+    }
+
+    #[test]
+    fn braces_start_a_block_at_the_start_of_a_statement() {
+        assert_eq!(
+            crate::run("{ print {}; } var m = {1: {2: 3}}; print m[1][2];").unwrap(),
+            "{}\n3\n"
+        );
+    }
+
+    #[test]
+    fn functions_are_compared_by_identity() {
+        assert_eq!(
+            crate::run("fun f() {} var g = f; print f == g;").unwrap(),
+            "true\n"
+        );
+        // each call declares a new closure over a new scope
+        assert_eq!(
+            crate::run("fun make() { fun f() {} return f; } print make() == make();").unwrap(),
+            "false\n"
+        );
+    }
+
+    #[test]
+    fn lists_are_compared_by_identity() {
+        assert_eq!(
+            crate::run("var l = [1]; print l == l; print [1] == [1];").unwrap(),
+            "true\nfalse\n"
+        );
+    }
+
+    #[test]
+    fn list_pattern_params_destructure_lists() {
+        assert_eq!(
+            crate::run("fun f([a, [b, c]], d) { print a + b + c + d; } f([1, [2, 3]], 4);")
+                .unwrap(),
+            "10\n"
+        );
+        let err = crate::run("fun f([a, b]) {} f([1, 2, 3]);").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected a list of 2 elements to destructure into [a, b] but got [1, 2, 3] on line 1:18."
+        );
+    }
+
+    #[test]
+    fn list_pattern_param_requires_list() {
+        let err = crate::run("fun first([a, b]) { return a; } first(1);").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected a list to destructure into [a, b] but got 1 on line 1:33."
+        );
+    }
+
+    #[test]
+    fn runtime_errors_include_the_line() {
+        let err = crate::run("var a = 1;\n\nprint a + b;").unwrap_err();
+        assert_eq!(err.to_string(), "Undefined variable b on line 3:11.");
+        let err = crate::run("var f = 1;\nprint 1 +\n  f();").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Can only call functions and classes on line 3:3."
+        );
+    }
+
+    #[test]
+    fn undefined_property() {
+        let err = crate::run("class Foo {}\nvar foo = Foo();\nprint foo.bar;").unwrap_err();
+        assert_eq!(err.to_string(), "Undefined property bar on line 3:7.");
+    }
+
+    #[test]
+    fn superclass_must_be_a_class() {
+        let err = crate::run("var NotAClass = 1;\nclass Foo < NotAClass {}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Superclass of Foo must be a class on line 2:1."
+        );
+    }
+
+    #[test]
+    fn factor_is_left_associative() {
+        assert_eq!(crate::run("print 8 / 2 / 2;").unwrap(), "2\n");
+        assert_eq!(crate::run("print 6 / 2 * 3;").unwrap(), "9\n");
+    }
+
+    #[test]
+    fn echo_final_expr() {
+        let stmts = parse("print \"sum:\";\n1 + 2;");
+
+        let mut interpreter = Interpreter::default();
+        interpreter.interpret(&stmts).unwrap();
+        assert_eq!(interpreter.stdout, "sum:\n");
+
+        let mut interpreter = Interpreter {
+            echo_final_expr: true,
+            ..Default::default()
+        };
+        interpreter.interpret(&stmts).unwrap();
+        assert_eq!(interpreter.stdout, "sum:\n3\n");
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn print_to_writer() {
+        let buffer = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_writer(Box::new(buffer.clone()));
+        interpreter
+            .interpret(&parse("print 1; print \"two\";"))
+            .unwrap();
+        assert_eq!(interpreter.stdout, "");
+        assert_eq!(buffer.0.borrow().as_slice(), b"1\ntwo\n");
+    }
+
+    #[test]
+    fn on_print_callback() {
+        let lines = Rc::new(RefCell::new(vec![]));
+        let mut interpreter = Interpreter::default();
+        let callback_lines = lines.clone();
+        interpreter.on_print = Some(Box::new(move |line| {
+            callback_lines.borrow_mut().push(line.to_owned())
+        }));
+        interpreter
+            .interpret(&parse("for (var i = 0; i < 3; i = i + 1) print i;"))
+            .unwrap();
+        assert_eq!(*lines.borrow(), ["0", "1", "2"]);
+        assert_eq!(interpreter.stdout, "0\n1\n2\n");
+    }
+
+    #[test]
+    fn on_stmt_callback() {
+        let count = Rc::new(Cell::new(0));
+        let mut interpreter = Interpreter::default();
+        let callback_count = count.clone();
+        interpreter.on_stmt = Some(Box::new(move |_, _| {
+            callback_count.set(callback_count.get() + 1)
+        }));
+        // the loop and its declaration run once, and the print three times
+        interpreter
+            .interpret(&parse("for (var i = 0; i < 3; i = i + 1) print i;"))
+            .unwrap();
+        assert_eq!(count.get(), 2 + 3);
+        assert_eq!(interpreter.stdout, "0\n1\n2\n");
+    }
+
+    #[test]
+    fn inspect_scopes() {
+        // lists the variables in scope other than natives
+        fn locals(interpreter: &mut Interpreter, _: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+            let locals = interpreter
+                .dump_scope()
+                .into_iter()
+                .filter(|(_, value)| !matches!(value, RuntimeValue::NativeFunction(_)))
+                .map(|(name, value)| format!("{}={}", name, value).into())
+                .collect();
+            Ok(RuntimeValue::List(Rc::new(RefCell::new(locals))))
+        }
+
+        let mut interpreter = Interpreter::default();
+        interpreter.define_native(NativeFunction {
+            name: "locals",
+            arity: 0,
+            optional: 0,
+            func: locals,
+        });
+        let source = "var b = 2; var a = 1;
+            fun f(c) { var a = \"inner\"; { var d = c + 1; print locals(); } }
+            f(3);";
+        crate::run_with(&mut interpreter, source).unwrap();
+        assert_eq!(interpreter.stdout, "[d=4, a=inner, c=3, b=2, f=<fn f>]\n");
+
+        let names = Rc::new(RefCell::new(vec![]));
+        let callback_names = names.clone();
+        interpreter.on_stmt = Some(Box::new(move |stmt, env| {
+            if let Stmt::Print(_) = stmt {
+                *callback_names.borrow_mut() = env.names();
+            }
+        }));
+        crate::run_with(&mut interpreter, "{ var x = 1; { var a = 2; print a; } }").unwrap();
+        let names = names.borrow();
+        assert_eq!(names[..3], ["a", "x", "abs"]);
+        assert_eq!(names.iter().filter(|name| *name == "a").count(), 1);
+        assert!(names.contains(&"f".to_string()));
+    }
+
+    #[test]
+    fn cancel_execution() {
+        fn cancel(interpreter: &mut Interpreter, _: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+            if let Some(flag) = &interpreter.cancel_flag {
+                flag.store(true, Ordering::Relaxed);
+            }
+            Ok(RuntimeValue::Nil)
+        }
+
+        let mut interpreter = Interpreter::default();
+        interpreter.set_cancel_flag(Arc::new(AtomicBool::new(false)));
+        interpreter.define_native(NativeFunction {
+            name: "cancel",
+            arity: 0,
+            optional: 0,
+            func: cancel,
+        });
+        let err = interpreter
+            .interpret(&parse(
+                "var i = 0;
+                 while (true) {
+                   i = i + 1;
+                   if (i == 10) cancel();
+                   print i;
+                 }",
+            ))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Execution cancelled on line 2:18.");
+        assert_eq!(interpreter.stdout.lines().last(), Some("10"));
+    }
+
+    #[test]
+    fn block_error_restores_environment() {
+        let mut interpreter = Interpreter::default();
+        crate::run_with(&mut interpreter, "var a = \"global\";").unwrap();
+        let err = crate::run_with(&mut interpreter, "{ var a = \"local\"; missing; }");
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "Undefined variable missing on line 1:20."
+        );
+        crate::run_with(&mut interpreter, "print a;").unwrap();
+        assert_eq!(interpreter.stdout, "global\n");
+    }
+
+    #[test]
+    fn function_error_restores_environment() {
+        let mut interpreter = Interpreter::default();
+        let source = "var a = \"global\";\nfun fail(a) { var b = a; missing; }";
+        crate::run_with(&mut interpreter, source).unwrap();
+        let err = crate::run_with(&mut interpreter, "fail(\"param\");");
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "Undefined variable missing on line 2:26."
+        );
+        crate::run_with(&mut interpreter, "print a;").unwrap();
+        assert_eq!(interpreter.stdout, "global\n");
+    }
+
+    #[test]
+    fn functions_see_globals_defined_later() {
+        let source = "fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); }
+            fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); }
+            print isEven(4);";
+        assert_eq!(crate::run(source).unwrap(), "true\n");
+    }
+
+    #[test]
+    fn variables_are_freed_when_scopes_end() {
+        let mut interpreter = Interpreter::default();
+        let allocated = interpreter.variables.len();
+        let source = "class Counter { inc(n) { var next = n + 1; return next; } }
+            var counter = Counter();
+            fun twice(n) { var result = counter.inc(n); return counter.inc(result); }
+            var i = 0;
+            while (i < 10) { var doubled = twice(i); i = i + 1; }";
+        crate::run_with(&mut interpreter, source).unwrap();
+        // only the globals Counter, counter, twice and i remain
+        assert_eq!(interpreter.variables.len(), allocated + 4);
+    }
+
+    #[test]
+    fn scopes_declaring_functions_are_freed() {
+        let mut interpreter = Interpreter::default();
+        let allocated = interpreter.variables.len();
+        let source = "class Base { get() { return 1; } }
+            var total = 0;
+            for (var i = 0; i < 10; i = i + 1) {
+              fun helper(n) { return n + i; }
+              class Local < Base { twice() { return helper(super.get()) * 2; } }
+              class Empty {}
+              total = total + Local().twice();
+            }
+            fun call() { fun inner() { return 1; } return inner(); }
+            call();
+            print total;";
+        crate::run_with(&mut interpreter, source).unwrap();
+        assert_eq!(interpreter.stdout, "110\n");
+        // only the globals Base, total and call remain
+        assert_eq!(interpreter.variables.len(), allocated + 3);
+
+        // a scope is kept while a function declared in it is still in use
+        let source = "var f;
+            { var x = 1; fun g() { return x; } f = g; }
+            print f();";
+        crate::run_with(&mut interpreter, source).unwrap();
+        assert_eq!(interpreter.stdout, "110\n1\n");
+    }
+
+    #[test]
+    fn captured_variables_outlive_their_scope() {
+        let mut interpreter = Interpreter::default();
+        let source =
+            "fun makeCounter() { var i = 0; fun count() { i = i + 1; print i; } return count; }
+            var counter = makeCounter();
+            { var temp = 1; counter(); }
+            counter();";
+        crate::run_with(&mut interpreter, source).unwrap();
+        assert_eq!(interpreter.stdout, "1\n2\n");
+    }
+
+    #[test]
+    fn unbounded_recursion_overflows_gracefully() {
+        // unoptimized builds use a lot of native stack per Lox call, so give
+        // the test as much stack as a main thread gets
+        let test = || {
+            let mut interpreter = Interpreter::default();
+            let source = "fun forever(n) { forever(n + 1); }\nforever(0);";
+            let err = crate::run_with(&mut interpreter, source).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Stack overflow: exceeded max call depth of 1000 when calling forever on line 1:18."
+            );
+            // the recursive calls are listed once
+            assert_eq!(
+                err.downcast::<LoxError>().unwrap().trace(),
+                [
+                    "at forever (line 1)",
+                    "... repeated 998 more times",
+                    "at forever (line 2)"
+                ]
+            );
+
+            // the interpreter can still be used afterwards
+            interpreter.max_call_depth = 3;
+            let source = "fun depth(n) { if (n == 0) return 0; return depth(n - 1) + 1; }";
+            crate::run_with(&mut interpreter, source).unwrap();
+            crate::run_with(&mut interpreter, "print depth(2);").unwrap();
+            assert!(crate::run_with(&mut interpreter, "print depth(3);").is_err());
+            assert_eq!(interpreter.stdout, "2\n");
+        };
+        std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(test)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn tail_calls_do_not_grow_the_stack() {
+        // far deeper than max_call_depth allows for calls that are not in
+        // tail position, including mutual recursion
+        let mut interpreter = Interpreter::default();
+        let source =
+            "fun count(n, total) { if (n == 0) return total; return count(n - 1, total + 1); }
+            fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); }
+            fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); }
+            print count(100000, 0);
+            print isEven(10001);";
+        crate::run_with(&mut interpreter, source).unwrap();
+        assert_eq!(interpreter.stdout, "100000\nfalse\n");
+    }
+
+    #[test]
+    fn tail_calls_inside_try_can_be_caught() {
+        let mut interpreter = Interpreter::default();
+        let source = "fun fail() { throw \"oops\"; }
+            fun attempt() { try { return fail(); } catch (e) { return \"caught \" + e; } }
+            print attempt();";
+        crate::run_with(&mut interpreter, source).unwrap();
+        assert_eq!(interpreter.stdout, "caught oops\n");
+    }
+
+    #[test]
+    fn step_limit() {
+        let mut interpreter = Interpreter::default();
+        interpreter.set_step_limit(1000);
+        let err = crate::run_with(&mut interpreter, "while (true) {}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Step limit exceeded: used all 1000 steps on line 1:14."
+        );
+
+        // `print 1 + 2;` takes four steps: the statement and three expressions
+        interpreter.set_step_limit(4);
+        crate::run_with(&mut interpreter, "print 1 + 2;").unwrap();
+        assert!(crate::run_with(&mut interpreter, "print 3;").is_err());
+        assert_eq!(interpreter.stdout, "3\n");
+    }
+
+    #[test]
+    fn unbounded_tail_recursion_is_stopped() {
+        let source = "fun f(n) { return f(n + 1); } f(0);";
+        let mut interpreter = Interpreter::default();
+        interpreter.set_step_limit(100_000);
+        let err = crate::run_with(&mut interpreter, source).unwrap_err();
+        assert!(
+            err.to_string().starts_with("Step limit exceeded"),
+            "{}",
+            err
+        );
+
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut interpreter = Interpreter::default();
+        interpreter.set_cancel_flag(flag.clone());
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            flag.store(true, Ordering::Relaxed);
+        });
+        let err = crate::run_with(&mut interpreter, source).unwrap_err();
+        handle.join().unwrap();
+        assert_eq!(err.to_string(), "Execution cancelled on line 1:19.");
+    }
+
+    #[test]
+    fn tail_call_errors_are_located_at_the_call() {
+        let err = crate::run("fun f(a, b) {} fun g() { return f(1); } g();").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected 2 arguments but got 1 on line 1:33."
+        );
+        let err = crate::run("fun f([a]) {} fun g() { return f(1); } g();").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected a list to destructure into [a] but got 1 on line 1:32."
+        );
+    }
+
+    #[test]
+    fn power() {
+        assert_eq!(crate::run("print 2 ** 10;").unwrap(), "1024\n");
+        assert_eq!(crate::run("print 2 ** 3 ** 2;").unwrap(), "512\n");
+        assert_eq!(crate::run("print 2 * 3 ** 2;").unwrap(), "18\n");
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        assert_eq!(crate::run("print 6 & 3;").unwrap(), "2\n");
+        assert_eq!(crate::run("print 6 | 3, 6 ^ 3;").unwrap(), "7 5\n");
+        assert_eq!(crate::run("print 1 << 4, -16 >> 2;").unwrap(), "16 -4\n");
+        assert_eq!(crate::run("print ~0, ~~5;").unwrap(), "-1 5\n");
+        // bitwise operators bind tighter than comparisons, but looser than
+        // arithmetic
+        assert_eq!(
+            crate::run("print 5 & 1 == 1, 1 | 2 ^ 3 & 1 << 1 + 1;").unwrap(),
+            "true 3\n"
+        );
+        let err = crate::run("print 1.5 & 1;").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Bitwise operands must be 64-bit integers but got 1.5 on line 1:7."
+        );
+        let err = crate::run("print ~(2 ** 63);").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Bitwise operands must be 64-bit integers but got 9223372036854776000 on line 1:7."
+        );
+        let err = crate::run("print 1 << 64;").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Shift amounts must be from 0 to 63 but got 64 on line 1:7."
+        );
+    }
+
+    #[test]
+    fn nil_coalescing() {
+        assert_eq!(
+            crate::run("print nil ?? 1, false ?? 1, 0 ?? 1, \"\" ?? 1;").unwrap(),
+            "1 false 0 \n"
+        );
+        assert_eq!(
+            crate::run("print nil or 1, false or 1, 0 or 1, \"\" or 1;").unwrap(),
+            "1 1 1 \n"
+        );
+        // the right side is only evaluated when the left side is nil
+        assert_eq!(
+            crate::run("var n = 0; fun f() { n += 1; return n; } print 5 ?? f(), nil ?? f(), n;")
+                .unwrap(),
+            "5 1 1\n"
+        );
+        // binds more loosely than "or", and groups from the left
+        assert_eq!(
+            crate::run("print nil ?? false or 2, nil ?? nil ?? 3, true ? nil ?? 4 : 5;").unwrap(),
+            "2 3 4\n"
+        );
+    }
+
+    #[test]
+    fn instance_of_class() {
+        let source = r#"
+            class Shape {}
+            class Circle < Shape {}
+            class Unit < Circle {}
+            class Square < Shape {}
+            var c = Circle();
+            print c is Circle, c is Shape, c is Unit, c is Square;
+            print Unit() is Shape, Square() is Circle, Shape() is Shape;
+            print Circle is Circle, 1 is Shape, nil is Shape;
+            print c is Shape == true, !(c is Square);
+        "#;
+        assert_eq!(
+            crate::run(source).unwrap(),
+            "true true false false\ntrue false true\nfalse false false\ntrue true\n"
+        );
+        let err = crate::run("class A {}\nvar a = A();\nprint a is a;").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Right operand of is must be a class but got A instance on line 3:7."
+        );
+    }
+
+    #[test]
+    fn injected_globals() {
+        let globals = HashMap::from([
+            ("VERSION".to_string(), RuntimeValue::from("1.2")),
+            ("DEBUG".to_string(), RuntimeValue::from(true)),
+        ]);
+        let mut interpreter = Interpreter::new(globals);
+        crate::run_with(
+            &mut interpreter,
+            "fun version() { return VERSION; } if (DEBUG) print \"v\" + version();",
+        )
+        .unwrap();
+        assert_eq!(interpreter.stdout, "v1.2\n");
+        // natives are still defined, and scripts can replace the globals
+        crate::run_with(&mut interpreter, "var DEBUG = len(\"\") > 0; print DEBUG;").unwrap();
+        assert_eq!(interpreter.stdout, "v1.2\nfalse\n");
+    }
+
+    #[test]
+    fn if_else() {
+        assert_eq!(
+            crate::run("if (false) print 1; else print 2; if (nil) { print 3; } else if (true) { print 4; }")
+                .unwrap(),
+            "2\n4\n"
+        );
+    }
+
+    #[test]
+    fn unary_and_grouping() {
+        assert_eq!(
+            crate::run("print !true; print !nil; print -(1 + 2) * 2;").unwrap(),
+            "false\ntrue\n-6\n"
+        );
+    }
+
+    #[test]
+    fn number_formatting() {
+        assert_eq!(
+            crate::run("print 5; print 2.5; print -3; print -0; print 0.1 + 0.2;").unwrap(),
+            "5\n2.5\n-3\n-0\n0.30000000000000004\n"
+        );
+
+        assert_eq!(
+            crate::run("var big = 100000000000000000000; print big * 5; print big * 10; print 1 / 100000000;").unwrap(),
+            "500000000000000000000\n1e21\n1e-8\n"
+        );
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let err = crate::run("print 1;\nprint 2 / (1 - 1);").unwrap_err();
+        assert_eq!(err.to_string(), "Division by zero on line 2:7.");
+        assert!(crate::run("print 0 / 0;").is_err());
+
+        let mut interpreter = Interpreter {
+            ieee_division: true,
+            ..Default::default()
+        };
+        crate::run_with(&mut interpreter, "print 1 / 0; print -1 / 0; print 0 / 0;").unwrap();
+        assert_eq!(interpreter.stdout, "Infinity\n-Infinity\nNaN\n");
+    }
+
+    #[test]
+    fn number_precision() {
+        let mut interpreter = Interpreter {
+            number_precision: Some(2),
+            ..Default::default()
+        };
+        crate::run_with(
+            &mut interpreter,
+            "print 5; print 1 / 3; print \"$\" + 2.5; print [0.126];",
+        )
+        .unwrap();
+        assert_eq!(interpreter.stdout, "5.00\n0.33\n$2.50\n[0.13]\n");
+    }
+
+    #[test]
+    fn plus_coerces_to_string() {
+        assert_eq!(crate::run(r#"print "n=" + 3;"#).unwrap(), "n=3\n");
+        assert_eq!(crate::run(r#"print 3 + "!";"#).unwrap(), "3!\n");
+        assert_eq!(
+            crate::run(r#"print "a" + nil + true;"#).unwrap(),
+            "aniltrue\n"
+        );
+        assert_eq!(crate::run("print 1 + 2 + \"3\";").unwrap(), "33\n");
+        let err = crate::run("print 1 + nil;").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unexpected operands for + (must be a pair of numbers or include a string): 1, nil on line 1:7"
+        );
+    }
+
+    #[test]
+    fn compound_assignment() {
+        assert_eq!(
+            crate::run("var x = 1; x += 2; print x; x *= 4; print x;").unwrap(),
+            "3\n12\n"
+        );
+        assert_eq!(
+            crate::run("var x = 10; x -= 4; x /= 2; print x;").unwrap(),
+            "3\n"
+        );
+        assert_eq!(
+            crate::run("var s = \"a\"; s += \"b\"; print s;").unwrap(),
+            "ab\n"
+        );
+        assert_eq!(
+            crate::run("class P {} var p = P(); p.n = 1; p.n += 5; print p.n;").unwrap(),
+            "6\n"
+        );
+    }
+
+    #[test]
+    fn compound_assignment_evaluates_targets_once() {
+        let source = "var n = 0;
+            fun i() { n = n + 1; return n - 1; }
+            var a = [10, 20, 30];
+            a[i()] += 1;
+            print n, a;
+            class P {}
+            var p = P();
+            p.x = 1;
+            fun get() { n = n + 1; return p; }
+            get().x *= 5;
+            print n, p.x;";
+        assert_eq!(crate::run(source).unwrap(), "1 [11, 20, 30]\n2 5\n");
+        // the current value is read before the right side is evaluated
+        let source = "var m = {\"k\": 1};
+            m[\"k\"] += (m[\"k\"] = 10);
+            print m;";
+        assert_eq!(crate::run(source).unwrap(), "{k: 11}\n");
+        let err = crate::run("var m = {};\nm[\"k\"] += 1;").unwrap_err();
+        assert_eq!(err.to_string(), "Undefined key k on line 2:1.");
+    }
+
+    #[test]
+    fn ternary() {
+        assert_eq!(crate::run("print true ? 1 : 2;").unwrap(), "1\n");
+        assert_eq!(crate::run("print nil ? 1 : 2;").unwrap(), "2\n");
+        assert_eq!(
+            crate::run("var x = 5; print x < 3 ? \"low\" : x < 7 ? \"mid\" : \"high\";").unwrap(),
+            "mid\n"
+        );
+        // only the chosen branch is evaluated
+        assert_eq!(crate::run("print true ? 1 : undefined;").unwrap(), "1\n");
     }
 }
@@ -0,0 +1,180 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use serde_json::{Map, Number, Value};
+
+use crate::interpreter::{MapKey, RuntimeValue};
+
+/// Encodes a value as JSON. Lists become arrays and maps become objects,
+/// whose keys are always strings in JSON, so number keys are written the
+/// way Lox prints them. Functions, classes and instances have no JSON form,
+/// and neither do lists or maps that contain themselves, or maps with a
+/// number key and a string key that would be written the same way.
+pub(crate) fn to_json(value: &RuntimeValue) -> Result<String> {
+    Ok(encode(value, &mut vec![])?.to_string())
+}
+
+/// Decodes JSON into a value, turning arrays into lists and objects into
+/// maps.
+pub(crate) fn from_json(json: &str) -> Result<RuntimeValue> {
+    let value = serde_json::from_str(json)
+        .map_err(|err| anyhow!("from_json got invalid JSON: {}.", err))?;
+    Ok(decode(value))
+}
+
+// `parents` holds the lists and maps being encoded around `value`, so that
+// one which contains itself is reported instead of recursing forever
+fn encode(value: &RuntimeValue, parents: &mut Vec<*const ()>) -> Result<Value> {
+    let json = match value {
+        RuntimeValue::Nil => Value::Null,
+        RuntimeValue::Bool(x) => Value::Bool(*x),
+        // whole numbers are written without a fractional part, as Lox
+        // prints them
+        RuntimeValue::Number(x) if x.fract() == 0.0 && x.abs() < 2f64.powi(53) => {
+            Value::Number(Number::from(*x as i64))
+        }
+        RuntimeValue::Number(x) => match Number::from_f64(*x) {
+            Some(number) => Value::Number(number),
+            None => return Err(anyhow!("to_json can't encode the number {}.", value)),
+        },
+        RuntimeValue::String(s) => Value::String(s.clone()),
+        RuntimeValue::List(elements) => {
+            enter(Rc::as_ptr(elements) as *const (), parents, value)?;
+            let elements = elements
+                .borrow()
+                .iter()
+                .map(|element| encode(element, parents))
+                .collect::<Result<_>>()?;
+            parents.pop();
+            Value::Array(elements)
+        }
+        RuntimeValue::Map(map) => {
+            enter(Rc::as_ptr(map) as *const (), parents, value)?;
+            let mut entries = Map::new();
+            for (key, value) in map.borrow().iter() {
+                // the number 1 and the string "1" are both written as "1"
+                let key = key.to_string();
+                if entries.contains_key(&key) {
+                    return Err(anyhow!(
+                        "to_json can't encode a map with the duplicate key \"{}\", from a number and a string.",
+                        key
+                    ));
+                }
+                let value = encode(value, parents)?;
+                entries.insert(key, value);
+            }
+            parents.pop();
+            Value::Object(entries)
+        }
+        _ => {
+            return Err(anyhow!(
+                "to_json can't encode a {}: {}.",
+                value.type_name(),
+                value
+            ))
+        }
+    };
+    Ok(json)
+}
+
+fn enter(pointer: *const (), parents: &mut Vec<*const ()>, value: &RuntimeValue) -> Result<()> {
+    if parents.contains(&pointer) {
+        return Err(anyhow!(
+            "to_json can't encode a {} that contains itself.",
+            value.type_name()
+        ));
+    }
+    parents.push(pointer);
+    Ok(())
+}
+
+fn decode(json: Value) -> RuntimeValue {
+    match json {
+        Value::Null => RuntimeValue::Nil,
+        Value::Bool(x) => RuntimeValue::Bool(x),
+        // every JSON number fits in an f64, if only approximately
+        Value::Number(x) => RuntimeValue::Number(x.as_f64().unwrap_or(f64::NAN)),
+        Value::String(s) => RuntimeValue::String(s),
+        Value::Array(elements) => {
+            let elements = elements.into_iter().map(decode).collect();
+            RuntimeValue::List(Rc::new(RefCell::new(elements)))
+        }
+        Value::Object(entries) => {
+            let map: HashMap<_, _> = entries
+                .into_iter()
+                .map(|(key, value)| (MapKey::String(key), decode(value)))
+                .collect();
+            RuntimeValue::Map(Rc::new(RefCell::new(map)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run;
+
+    #[test]
+    fn json_round_trip() {
+        let source = r#"
+            var data = {"name": "lox", "tags": ["a", "b"], "meta": {"version": 1.5, "stable": false, "parent": nil}, "sizes": [[1, 2], [], [{}]]};
+            var json = to_json(data);
+            print json;
+            print to_json(from_json(json)) == json;
+            var decoded = from_json(json);
+            print decoded["meta"]["version"], decoded["sizes"][0][1], decoded["tags"];
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            concat!(
+                r#"{"meta":{"parent":null,"stable":false,"version":1.5},"name":"lox","sizes":[[1,2],[],[{}]],"tags":["a","b"]}"#,
+                "\ntrue\n1.5 2 [a, b]\n"
+            )
+        );
+        assert_eq!(
+            run(r#"print to_json({1: "one"}), to_json("say \"hi\""), to_json(-0.25);"#).unwrap(),
+            "{\"1\":\"one\"} \"say \\\"hi\\\"\" -0.25\n"
+        );
+        assert_eq!(
+            run(r#"print from_json(" [1e2, true, null] ");"#).unwrap(),
+            "[100, true, nil]\n"
+        );
+    }
+
+    #[test]
+    fn json_errors() {
+        let err = run("var xs = [1];\npush(xs, xs);\nto_json(xs);").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "to_json can't encode a list that contains itself on line 3:1."
+        );
+        // the same list may appear more than once, as long as it isn't
+        // inside itself
+        assert_eq!(
+            run("var xs = [1]; print to_json([xs, {\"a\": xs}]);").unwrap(),
+            "[[1],{\"a\":[1]}]\n"
+        );
+        let err = run("fun f() {}\nto_json({\"f\": f});").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "to_json can't encode a function: <fn f> on line 2:1."
+        );
+        let err = run("to_json({1: \"a\", \"1\": \"b\"});").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "to_json can't encode a map with the duplicate key \"1\", from a number and a string on line 1:1."
+        );
+        let err = run("to_json(pow(10, 400));").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "to_json can't encode the number Infinity on line 1:1."
+        );
+        let err = run("from_json(\"[1,\");").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "from_json got invalid JSON: EOF while parsing a value at line 1 column 3 on line 1:1."
+        );
+    }
+}
@@ -1,20 +1,81 @@
+//! An interpreter for the Lox language from
+//! [Crafting Interpreters](https://craftinginterpreters.com/).
+//!
+//! Besides running programs (see `run` and `Interpreter`), the front end can
+//! be used on its own. For example, this finds the variables a program
+//! reads, by walking its syntax tree with a custom `Visit` implementation:
+//!
+//! ```
+//! use lox_lib::expr::Variable;
+//! use lox_lib::visitor::Visit;
+//! use lox_lib::{Parser, Scanner};
+//!
+//! #[derive(Default)]
+//! struct Reads(Vec<String>);
+//!
+//! impl<'ast> Visit<'ast> for Reads {
+//!     fn visit_expr_variable(&mut self, e: &'ast Variable) {
+//!         self.0.push(e.name.to_string());
+//!     }
+//! }
+//!
+//! let tokens = Scanner::new("var x = 1; print x + y;").scan_tokens().unwrap();
+//! let stmts = Parser::new(tokens).parse().unwrap();
+//! let mut reads = Reads::default();
+//! for stmt in &stmts {
+//!     reads.visit_stmt(stmt);
+//! }
+//! assert_eq!(reads.0, ["x", "y"]);
+//! ```
+
 use std::{
+    fmt,
     fs::read_to_string,
     io::{stdin, stdout, BufRead, BufReader, Write},
+    ops::Range,
     path::PathBuf,
+    time::{Duration, Instant},
 };
 
+pub mod analysis;
+mod ast_printer;
 mod cursor;
 mod env;
-mod expr;
+mod error;
+pub mod expr;
+mod fold;
+mod formatter;
 mod interpreter;
+#[cfg(feature = "json")]
+mod json;
+mod natives;
 mod parser;
+mod resolver;
+mod rng;
 mod scanner;
-mod stmt;
+mod span;
+pub mod stmt;
+mod symbol;
 mod token;
-mod visitor;
+pub mod visit_mut;
+pub mod visitor;
+
+use anyhow::{anyhow, Context, Result};
 
-use anyhow::{Context, Result};
+use stmt::Expression;
+
+pub use env::Environment;
+pub use error::LoxError;
+pub use expr::Expr;
+pub use interpreter::{Interpreter, RuntimeValue};
+pub use natives::NativeFunction;
+pub use parser::{ParseError, Parser};
+pub use resolver::Warning;
+pub use scanner::{ScanError, Scanner};
+pub use span::Span;
+pub use stmt::Stmt;
+pub use symbol::Symbol;
+pub use token::{Token, TokenKind};
 
 pub fn run_file(path: PathBuf) -> Result<String> {
     let contents =
@@ -22,53 +83,455 @@ pub fn run_file(path: PathBuf) -> Result<String> {
     run(&contents)
 }
 
-pub fn run_prompt() -> Result<()> {
-    let mut reader = BufReader::new(stdin());
+/// Runs an interactive session on stdin. Returns the status code passed to
+/// `exit` if the session was ended by calling it.
+pub fn run_prompt() -> Result<Option<i32>> {
+    let mut interpreter = Interpreter::with_writer(Box::new(stdout()));
+    repl(BufReader::new(stdin()), &mut interpreter)?;
+    Ok(interpreter.exit_code)
+}
+
+/// Reads lines from `reader` and runs each of them with the same
+/// interpreter, so that variables and functions defined by one line are
+/// visible to the lines after it. If a line ends partway through a
+/// statement, more lines are read until it is complete or an empty line is
+/// entered.
+fn repl(mut reader: impl BufRead, interpreter: &mut Interpreter) -> Result<()> {
     loop {
         let mut buffer = String::new();
         print!("> ");
-        stdout().flush().with_context(|| "could not flush stdout")?;
-        reader.read_line(&mut buffer)?;
+        loop {
+            stdout().flush().with_context(|| "could not flush stdout")?;
+            let read = reader.read_line(&mut buffer)?;
+            if read == 0 || buffer.ends_with("\n\n") || !is_incomplete(&buffer) {
+                break;
+            }
+            print!("... ");
+        }
         if buffer.is_empty() {
             return Ok(());
         };
-        run(&buffer)?;
+        if buffer.starts_with(':') {
+            match run_command(interpreter, &buffer) {
+                Ok(output) => println!("{}", output),
+                Err(err) => println!("Error: {}", err),
+            }
+            continue;
+        }
+        // an error only ends the line it happened on, not the session
+        if let Err(err) = run_line(interpreter, &buffer) {
+            println!("Error: {}", render_error(&err, &buffer));
+        }
+        if interpreter.exit_code.is_some() {
+            return Ok(());
+        }
     }
 }
 
+/// Returns whether `source` ends partway through a statement, so that more
+/// input could make it valid.
+fn is_incomplete(source: &str) -> bool {
+    let (tokens, errors) = scanner::Scanner::new(source).scan_all();
+    if !errors.is_empty() {
+        return false;
+    }
+    // a lone expression doesn't need a semicolon in the REPL
+    if parser::Parser::new(tokens.clone())
+        .parse_standalone_expression()
+        .is_ok()
+    {
+        return false;
+    }
+    match parser::Parser::new(tokens).parse() {
+        Ok(_) => false,
+        Err(errors) => errors.iter().all(|err| err.incomplete),
+    }
+}
+
+/// Runs a line of REPL input. If the line is a lone expression statement,
+/// its value is displayed (unless it is nil), as other REPLs do. The
+/// semicolon after such an expression may be left out.
+fn run_line(interpreter: &mut Interpreter, line: &str) -> Result<()> {
+    let stmts = match parse(line) {
+        Ok(stmts) => stmts,
+        Err(err) => {
+            let tokens = scanner::Scanner::new(line).scan_tokens()?;
+            let expression = parser::Parser::new(tokens)
+                .parse_standalone_expression()
+                .map_err(|_| err)?;
+            let span = expression.span();
+            vec![Stmt::Expression(Expression { expression, span })]
+        }
+    };
+    match stmts.as_slice() {
+        [Stmt::Expression(Expression { expression, .. })] => {
+            resolver::resolve(&stmts)?;
+            let value = interpreter.evaluate(expression)?;
+            if value != RuntimeValue::Nil {
+                interpreter.print_line(&interpreter.stringify(&value))?;
+            }
+            Ok(())
+        }
+        _ => interpreter.interpret(&stmts),
+    }
+}
+
+/// Checks `source` for likely mistakes, such as local variables that are
+/// never read, without running it.
+pub fn lint(source: &str) -> Result<Vec<Warning>> {
+    resolver::resolve(&parse(source)?)
+}
+
+/// Formats an error from running `source`, showing the line it happened on
+/// if the error has a position.
+pub fn render_error(err: &anyhow::Error, source: &str) -> String {
+    match err.downcast_ref::<LoxError>() {
+        Some(err) => err.render(source),
+        None => err.to_string(),
+    }
+}
+
+/// Scans `source` into the kind of each token and the range of bytes it
+/// spans, for use by syntax highlighters.
+pub fn highlight(source: &str, keep_comments: bool) -> Result<Vec<(TokenKind, Range<usize>)>> {
+    scanner::Scanner::new(source).highlight(keep_comments)
+}
+
+/// Runs a REPL meta-command such as `:type <expr>`, returning the text that
+/// should be displayed.
+fn run_command(interpreter: &mut interpreter::Interpreter, command: &str) -> Result<String> {
+    if let Some(source) = command.strip_prefix(":type") {
+        let value = interpreter.eval_expr(source)?;
+        Ok(value.type_name().to_owned())
+    } else {
+        Err(anyhow!("unknown command {}", command.trim()))
+    }
+}
+
+/// Parses `source` and renders the resulting syntax tree as S-expressions,
+/// one line per top-level statement, without running it.
+pub fn print_ast(source: &str) -> Result<String> {
+    let stmts = parse(source)?;
+    Ok(ast_printer::AstPrinter.print_program(&stmts))
+}
+
+/// Parses `source` and renders it as Lox source code in a consistent style,
+/// without running it. Comments are kept, though they may move to the end
+/// of the line or ahead of the statement they were in.
+pub fn format_source(source: &str) -> Result<String> {
+    let stmts = parse(source)?;
+    let comments = Scanner::new(source).comments();
+    let texts = |comments: &[Token]| -> Vec<String> {
+        comments
+            .iter()
+            .map(|comment| comment.kind.to_string())
+            .collect()
+    };
+    let formatted = formatter::format(&stmts, comments.clone());
+    // the result may be written over the source, so make sure that no
+    // comment was lost rather than trusting the formatter
+    if texts(&Scanner::new(&formatted).comments()) != texts(&comments) {
+        return Err(anyhow!("Formatting would lose some of the comments."));
+    }
+    Ok(formatted)
+}
+
+/// Runs `source`, returning everything it printed. If the program calls
+/// `exit`, the output printed before then is returned, and the status code
+/// is discarded; use `run_with` to find out what it was.
 pub fn run(source: &str) -> Result<String> {
+    let mut interpreter = Interpreter::default();
+    run_with(&mut interpreter, source)?;
+    Ok(interpreter.stdout)
+}
+
+/// Runs `source` and returns the value of its final statement if that is an
+/// expression statement (which, unlike in the REPL, needs its semicolon), or
+/// nil otherwise. This lets Lox be used as a small expression language, e.g.
+/// `run_value("var x = 2; x * 3;")` returns 6. Anything printed is discarded.
+pub fn run_value(source: &str) -> Result<RuntimeValue> {
+    let mut interpreter = Interpreter::default();
+    run_value_with(&mut interpreter, source)
+}
+
+/// Like `run_value`, but runs `source` with the given interpreter.
+pub fn run_value_with(interpreter: &mut Interpreter, source: &str) -> Result<RuntimeValue> {
+    let mut stmts = parse(source)?;
+    if interpreter.fold_constants {
+        fold::fold(&mut stmts, interpreter.ieee_division);
+    }
+    interpreter.interpret_value(&stmts)
+}
+
+/// Runs `source` with the given interpreter, so that output is sent wherever
+/// the interpreter is configured to write it.
+pub fn run_with(interpreter: &mut Interpreter, source: &str) -> Result<()> {
+    run_timed(interpreter, source).map(|_| ())
+}
+
+/// The wall-clock time spent in each phase of running a program.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    pub scan: Duration,
+    pub parse: Duration,
+    /// Includes resolving variables, which happens just before the program
+    /// runs.
+    pub interpret: Duration,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.scan + self.parse + self.interpret
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "scan:      {:?}", self.scan)?;
+        writeln!(f, "parse:     {:?}", self.parse)?;
+        writeln!(f, "interpret: {:?}", self.interpret)?;
+        write!(f, "total:     {:?}", self.total())
+    }
+}
+
+/// Like `run_with`, but also measures how long each phase took.
+pub fn run_timed(interpreter: &mut Interpreter, source: &str) -> Result<Timings> {
+    let mut timings = Timings::default();
+
+    let start = Instant::now();
+    let tokens = scan(source)?;
+    timings.scan = start.elapsed();
+
+    let start = Instant::now();
+    let mut stmts = parse_tokens(tokens)?;
+    if interpreter.fold_constants {
+        fold::fold(&mut stmts, interpreter.ieee_division);
+    }
+    timings.parse = start.elapsed();
+
+    let start = Instant::now();
+    interpreter.interpret(&stmts)?;
+    timings.interpret = start.elapsed();
+
+    Ok(timings)
+}
+
+/// Scans and parses `source`, reporting every error found if it is not a
+/// valid program.
+fn parse(source: &str) -> Result<Vec<Stmt>> {
+    parse_tokens(scan(source)?)
+}
+
+fn scan(source: &str) -> Result<Vec<token::Token>> {
     let scanner = scanner::Scanner::new(source);
-    let tokens = scanner.scan_tokens()?;
+    let (tokens, errors) = scanner.scan_all();
+    let errors = errors.into_iter().map(LoxError::from).collect();
+    if let Some(err) = LoxError::combine(errors) {
+        return Err(err.into());
+    }
 
     // for debugging
     // for token in &tokens {
     //     println!("{:?}", token);
     // }
 
-    let mut parser = parser::Parser::new(tokens);
-    let stmts = parser.parse()?;
+    Ok(tokens)
+}
 
-    // let mut printer = AstPrinter;
-    // println!("{}", printer.visit_expr(&expr));
+fn parse_tokens(tokens: Vec<token::Token>) -> Result<Vec<Stmt>> {
+    let mut parser = parser::Parser::new(tokens);
+    let stmts = parser.parse().map_err(|errors| {
+        let errors = errors.into_iter().map(LoxError::from).collect();
+        LoxError::combine(errors).expect("parsing only fails with errors")
+    })?;
 
     // for debugging
     // println!("{:?}", stmts);
 
-    let mut interpreter = interpreter::Interpreter::default();
-    interpreter.interpret(&stmts)?;
+    Ok(stmts)
+}
 
-    Ok(interpreter.stdout)
+/// Scans and parses `source` as a single expression, such as `x + 1`, with
+/// no trailing semicolon.
+pub fn parse_expression(source: &str) -> Result<expr::Expr> {
+    parser::Parser::new(scan(source)?).parse_standalone_expression()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn run_returns_final_value() {
+        assert_eq!(
+            run_value("var x = 2; x * 3;").unwrap(),
+            RuntimeValue::Number(6.0)
+        );
+        assert_eq!(
+            run_value(r#""a" + "b";"#).unwrap(),
+            RuntimeValue::from("ab")
+        );
+        assert_eq!(run_value("var x = 2;").unwrap(), RuntimeValue::Nil);
+        assert_eq!(run_value("1; print 2;").unwrap(), RuntimeValue::Nil);
+        assert_eq!(run_value("").unwrap(), RuntimeValue::Nil);
+        assert_eq!(run_value("exit(0); 1;").unwrap(), RuntimeValue::Nil);
+
+        let mut interpreter = Interpreter::default();
+        run_with(&mut interpreter, "fun sq(x) { return x * x; }").unwrap();
+        let value = run_value_with(&mut interpreter, "sq(7);").unwrap();
+        assert_eq!(f64::try_from(value).unwrap(), 49.0);
+    }
+
+    #[test]
+    fn run_timed_measures_each_phase() {
+        let mut interpreter = Interpreter::default();
+        let timings = run_timed(&mut interpreter, "var x = 1; print x + 1;").unwrap();
+        assert_eq!(interpreter.stdout, "2\n");
+        assert_eq!(
+            timings.total(),
+            timings.scan + timings.parse + timings.interpret
+        );
+        assert!(timings.to_string().starts_with("scan: "));
+    }
+
+    #[test]
+    fn print_multiple_values() {
+        assert_eq!(run("print 1, \"x\", true;").unwrap(), "1 x true\n");
+        assert_eq!(run("print 1;").unwrap(), "1\n");
+        assert_eq!(
+            format_source("print 1,\"x\" , true;").unwrap(),
+            "print 1, \"x\", true;\n"
+        );
+    }
+
+    #[test]
+    fn fold_constants_before_running() {
+        let mut interpreter = Interpreter::default();
+        interpreter.fold_constants = true;
+        run_with(&mut interpreter, "print 2+3*4;").unwrap();
+        assert_eq!(interpreter.stdout, "14\n");
+        let err = run_with(&mut interpreter, "print 1/0;").unwrap_err();
+        assert_eq!(err.to_string(), "Division by zero on line 1:7.");
+    }
+
+    #[test]
+    fn host_functions_convert_values() {
+        fn repeat(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+            let mut args = args.into_iter();
+            let s = String::try_from(args.next().unwrap())?;
+            let times = f64::try_from(args.next().unwrap())?;
+            Ok(s.repeat(times as usize).into())
+        }
+
+        let mut interpreter = Interpreter::default();
+        interpreter.define_native(NativeFunction {
+            name: "repeat",
+            arity: 2,
+            optional: 0,
+            func: repeat,
+        });
+        run_with(&mut interpreter, r#"print repeat("ab", 3);"#).unwrap();
+        assert_eq!(interpreter.stdout, "ababab\n");
+        let err = run_with(&mut interpreter, "repeat(1, 2);").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected a string but got number on line 1:1."
+        );
+    }
+
+    #[test]
+    fn repl_type_command() {
+        let mut interpreter = interpreter::Interpreter::default();
+        assert_eq!(
+            run_command(&mut interpreter, ":type 1 + 1").unwrap(),
+            "number"
+        );
+        assert_eq!(
+            run_command(&mut interpreter, ":type replace").unwrap(),
+            "function"
+        );
+        assert!(run_command(&mut interpreter, ":type 1 +").is_err());
+    }
+
+    #[test]
+    fn repl_keeps_state_between_lines() {
+        let input = "var x = 1;\nfun inc() { x = x + 1; }\nmissing;\ninc();\nprint x;\n";
+        let mut interpreter = Interpreter::default();
+        repl(input.as_bytes(), &mut interpreter).unwrap();
+        assert_eq!(interpreter.stdout, "2\n");
+        assert_eq!(run_command(&mut interpreter, ":type x").unwrap(), "number");
+    }
+
+    #[test]
+    fn repl_reads_until_input_is_complete() {
+        let input = "fun f() {\n  return 1;\n}\nprint f();\nprint (1 +\n\nprint 2\n;\n:type\n";
+        let mut interpreter = Interpreter::default();
+        repl(input.as_bytes(), &mut interpreter).unwrap();
+        assert_eq!(interpreter.stdout, "1\n2\n");
+    }
+
+    #[test]
+    fn incomplete_input() {
+        assert!(is_incomplete("fun f() {"));
+        assert!(is_incomplete("print (1 +"));
+        assert!(is_incomplete("var x = 1"));
+        assert!(!is_incomplete("1 + 2"));
+        assert!(!is_incomplete("print 1;"));
+        assert!(!is_incomplete("print );"));
+        assert!(!is_incomplete("print ); {"));
+        assert!(!is_incomplete("print \"abc"));
+    }
+
+    #[test]
+    fn repl_stops_at_exit() {
+        let input = "print 1;\nexit(2)\nprint 3;\n";
+        let mut interpreter = Interpreter::default();
+        repl(input.as_bytes(), &mut interpreter).unwrap();
+        assert_eq!(interpreter.stdout, "1\n");
+        assert_eq!(interpreter.exit_code, Some(2));
+    }
+
+    #[test]
+    fn repl_prints_expression_values() {
+        let input = "1 + 2;\nvar x = \"hi\";\nx\nnil;\nx; x;\n";
+        let mut interpreter = Interpreter::default();
+        repl(input.as_bytes(), &mut interpreter).unwrap();
+        assert_eq!(interpreter.stdout, "3\nhi\n");
+    }
+
     #[test]
     fn unicode_support() {
         assert_eq!(run(r#"print "Hello, 世界";"#).unwrap(), "Hello, 世界\n");
     }
 
+    #[test]
+    fn escape_sequences() {
+        assert_eq!(run(r#"print "a\tb";"#).unwrap(), "a\tb\n");
+        assert_eq!(run(r#"print "\\";"#).unwrap(), "\\\n");
+    }
+
+    #[test]
+    fn string_interpolation() {
+        assert_eq!(
+            run(r#"var name = "Lox"; print "hello ${name}, ${1 + 2 * 3}!";"#).unwrap(),
+            "hello Lox, 7!\n"
+        );
+        assert_eq!(
+            run(r#"print "${ {"a": [1]}["a"] } ${"in${"ner"}"}";"#).unwrap(),
+            "[1] inner\n"
+        );
+        assert_eq!(run(r#"print "\${1}";"#).unwrap(), "${1}\n");
+        let err = run(r#"print "${1 2}";"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected '}' after interpolated expression, found token 2 on line 1:12"
+        );
+        assert_eq!(
+            format_source(r#"print "a${ x+1 }\${b}";"#).unwrap(),
+            "print \"a${x + 1}\\${b}\";\n"
+        );
+    }
+
     #[test]
     fn integ_fibonacci() {
         assert_eq!(
@@ -93,7 +556,17 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn ast_serde_round_trip() {
+        let stmts = parse(&read_to_string("examples/stmts.lox").unwrap()).unwrap();
+        let json = serde_json::to_string(&stmts).unwrap();
+        let decoded: Vec<Stmt> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, stmts);
+    }
+
     #[test]
+    #[allow(clippy::useless_vec)]
     fn integ_stmts() {
         assert_eq!(
             run_file("examples/stmts.lox".into()).unwrap(),
@@ -102,6 +575,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn integ_scopes() {
         assert_eq!(
             run_file("examples/scopes.lox".into()).unwrap(),
@@ -114,6 +588,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn integ_scopes2() {
         assert_eq!(
             run_file("examples/scopes2.lox".into()).unwrap(),
@@ -122,6 +597,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn integ_scopes3() {
         assert_eq!(
             run_file("examples/scopes3.lox".into()).unwrap(),
@@ -130,6 +606,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn integ_scopes4() {
         assert_eq!(
             run_file("examples/scopes4.lox".into()).unwrap(),
@@ -138,6 +615,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn integ_variables() {
         assert_eq!(
             run_file("examples/variables.lox".into()).unwrap(),
@@ -146,6 +624,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn integ_functions1() {
         assert_eq!(
             run_file("examples/functions1.lox".into()).unwrap(),
@@ -154,6 +633,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn integ_functions2() {
         assert_eq!(
             run_file("examples/functions2.lox".into()).unwrap(),
@@ -162,6 +642,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn integ_functions3() {
         assert_eq!(
             run_file("examples/functions3.lox".into()).unwrap(),
@@ -170,10 +651,161 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn integ_counter() {
         assert_eq!(
             run_file("examples/counter.lox".into()).unwrap(),
             vec!["1", "2", "1", "3", ""].join("\n")
         );
     }
+
+    #[test]
+    fn integ_classes() {
+        assert_eq!(
+            run_file("examples/classes.lox".into()).unwrap(),
+            [
+                "Greeter",
+                "Greeter instance",
+                "Hello, Dear Reader!",
+                "Hello, this!",
+                "Hello, bound method!",
+                "3",
+                "10",
+                "11",
+                ""
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn integ_inheritance() {
+        assert_eq!(
+            run_file("examples/inheritance.lox".into()).unwrap(),
+            [
+                "Fry until golden brown.",
+                "Pipe full of custard and coat with chocolate.",
+                "A doughnut",
+                ""
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn integ_maps() {
+        assert_eq!(
+            run_file("examples/maps.lox".into()).unwrap(),
+            [
+                "3",
+                "alice is 31",
+                "bob is 28",
+                "carol is 45",
+                "104",
+                "[1, two]",
+                "{1: one, two: 2}",
+                ""
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn integ_lists() {
+        assert_eq!(
+            run_file("examples/lists.lox".into()).unwrap(),
+            [
+                "2",
+                "11",
+                "28",
+                "[2, 13, 5, 7, 11]",
+                "[[1, 2], [13, 4]]",
+                "[second, first]",
+                ""
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn integ_math() {
+        assert_eq!(
+            run_file("examples/math.lox".into()).unwrap(),
+            ["5", "3", "4", "-3", "1024", "-7", "12", "3.142", ""].join("\n")
+        );
+    }
+
+    #[test]
+    fn integ_exceptions() {
+        assert_eq!(
+            run_file("examples/exceptions.lox".into()).unwrap(),
+            [
+                "5",
+                "caught: can't divide 1 by zero",
+                "true",
+                "invalid name",
+                "false",
+                "2",
+                ""
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn integ_foreach() {
+        assert_eq!(
+            run_file("examples/foreach.lox".into()).unwrap(),
+            ["14", "6", "10 20 ", "30 40 ", "ada is 36", "alan is 41", ""].join("\n")
+        );
+    }
+
+    #[test]
+    fn integ_guess() {
+        let mut interpreter = Interpreter::default();
+        interpreter.set_reader(Box::new("50\nabc\n20\n37\n".as_bytes()));
+        let source = read_to_string("examples/guess.lox").unwrap();
+        run_with(&mut interpreter, &source).unwrap();
+        assert_eq!(
+            interpreter.stdout,
+            [
+                "Guess the number between 1 and 100.",
+                "Too high!",
+                "abc is not a number.",
+                "Too low!",
+                "You got it in 3 guesses!",
+                ""
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn integ_strings() {
+        assert_eq!(
+            run_file("examples/strings.lox".into()).unwrap(),
+            [
+                "Hello",
+                "world",
+                "HELLO, WORLD!",
+                "hello, world!",
+                "7",
+                "-1",
+                "2",
+                "界",
+                "5",
+                "hello, lox!",
+                ""
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn integ_closures() {
+        assert_eq!(
+            run_file("examples/closures.lox".into()).unwrap(),
+            ["2", "1", "1", ""].join("\n")
+        );
+    }
 }
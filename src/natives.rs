@@ -0,0 +1,861 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use itertools::Itertools;
+
+use crate::interpreter::{is_truthy, ExitError, Interpreter, MapKey, RuntimeValue};
+
+pub type NativeFn = fn(&mut Interpreter, Vec<RuntimeValue>) -> Result<RuntimeValue>;
+
+/// A function implemented in Rust that can be called from Lox code.
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    /// How many more arguments may be passed after the first `arity`, which
+    /// are left out of the arguments the function receives when missing.
+    pub optional: usize,
+    pub func: NativeFn,
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NativeFunction({})", self.name)
+    }
+}
+
+// natives are uniquely identified by their name, so there is no need to
+// compare function pointers
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+/// Returns all of the native functions that are defined in the global
+/// environment by default.
+pub fn globals() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction {
+            name: "abs",
+            arity: 1,
+            optional: 0,
+            func: abs,
+        },
+        NativeFunction {
+            name: "assert",
+            arity: 1,
+            optional: 1,
+            func: assert,
+        },
+        NativeFunction {
+            name: "ceil",
+            arity: 1,
+            optional: 0,
+            func: ceil,
+        },
+        NativeFunction {
+            name: "exit",
+            arity: 1,
+            optional: 0,
+            func: exit,
+        },
+        NativeFunction {
+            name: "file_read",
+            arity: 1,
+            optional: 0,
+            func: file_read,
+        },
+        NativeFunction {
+            name: "file_write",
+            arity: 2,
+            optional: 0,
+            func: file_write,
+        },
+        NativeFunction {
+            name: "floor",
+            arity: 1,
+            optional: 0,
+            func: floor,
+        },
+        #[cfg(feature = "json")]
+        NativeFunction {
+            name: "from_json",
+            arity: 1,
+            optional: 0,
+            func: from_json,
+        },
+        NativeFunction {
+            name: "index_of",
+            arity: 2,
+            optional: 0,
+            func: index_of,
+        },
+        NativeFunction {
+            name: "keys",
+            arity: 1,
+            optional: 0,
+            func: keys,
+        },
+        NativeFunction {
+            name: "len",
+            arity: 1,
+            optional: 0,
+            func: len,
+        },
+        NativeFunction {
+            name: "lower",
+            arity: 1,
+            optional: 0,
+            func: lower,
+        },
+        NativeFunction {
+            name: "max",
+            arity: 2,
+            optional: 0,
+            func: max,
+        },
+        NativeFunction {
+            name: "min",
+            arity: 2,
+            optional: 0,
+            func: min,
+        },
+        NativeFunction {
+            name: "num",
+            arity: 1,
+            optional: 0,
+            func: num,
+        },
+        NativeFunction {
+            name: "pop",
+            arity: 1,
+            optional: 0,
+            func: pop,
+        },
+        NativeFunction {
+            name: "pow",
+            arity: 2,
+            optional: 0,
+            func: pow,
+        },
+        NativeFunction {
+            name: "push",
+            arity: 2,
+            optional: 0,
+            func: push,
+        },
+        NativeFunction {
+            name: "random",
+            arity: 0,
+            optional: 0,
+            func: random,
+        },
+        NativeFunction {
+            name: "random_int",
+            arity: 2,
+            optional: 0,
+            func: random_int,
+        },
+        NativeFunction {
+            name: "readline",
+            arity: 0,
+            optional: 0,
+            func: readline,
+        },
+        NativeFunction {
+            name: "replace",
+            arity: 3,
+            optional: 0,
+            func: replace,
+        },
+        NativeFunction {
+            name: "sqrt",
+            arity: 1,
+            optional: 0,
+            func: sqrt,
+        },
+        NativeFunction {
+            name: "str",
+            arity: 1,
+            optional: 0,
+            func: str,
+        },
+        NativeFunction {
+            name: "substring",
+            arity: 3,
+            optional: 0,
+            func: substring,
+        },
+        #[cfg(feature = "json")]
+        NativeFunction {
+            name: "to_json",
+            arity: 1,
+            optional: 0,
+            func: to_json,
+        },
+        NativeFunction {
+            name: "type",
+            arity: 1,
+            optional: 0,
+            func: type_of,
+        },
+        NativeFunction {
+            name: "upper",
+            arity: 1,
+            optional: 0,
+            func: upper,
+        },
+        NativeFunction {
+            name: "values",
+            arity: 1,
+            optional: 0,
+            func: values,
+        },
+        NativeFunction {
+            name: "write",
+            arity: 1,
+            optional: 0,
+            func: write,
+        },
+    ]
+}
+
+fn expect_number(name: &str, value: &RuntimeValue) -> Result<f64> {
+    if let RuntimeValue::Number(n) = value {
+        Ok(*n)
+    } else {
+        Err(anyhow!("{} expects a number but got {}.", name, value))
+    }
+}
+
+fn expect_integer(name: &str, value: &RuntimeValue) -> Result<f64> {
+    match value {
+        RuntimeValue::Number(n) if n.fract() == 0.0 => Ok(*n),
+        _ => Err(anyhow!("{} expects an integer but got {}.", name, value)),
+    }
+}
+
+fn expect_string<'a>(name: &str, value: &'a RuntimeValue) -> Result<&'a str> {
+    if let RuntimeValue::String(s) = value {
+        Ok(s)
+    } else {
+        Err(anyhow!("{} expects a string but got {}.", name, value))
+    }
+}
+
+fn expect_list<'a>(
+    name: &str,
+    value: &'a RuntimeValue,
+) -> Result<&'a Rc<RefCell<Vec<RuntimeValue>>>> {
+    if let RuntimeValue::List(list) = value {
+        Ok(list)
+    } else {
+        Err(anyhow!("{} expects a list but got {}.", name, value))
+    }
+}
+
+fn expect_map<'a>(
+    name: &str,
+    value: &'a RuntimeValue,
+) -> Result<&'a Rc<RefCell<HashMap<MapKey, RuntimeValue>>>> {
+    if let RuntimeValue::Map(map) = value {
+        Ok(map)
+    } else {
+        Err(anyhow!("{} expects a map but got {}.", name, value))
+    }
+}
+
+/// Interprets `value` as an index into a string, which must be a
+/// non-negative whole number.
+fn expect_index(name: &str, value: &RuntimeValue) -> Result<usize> {
+    match value {
+        RuntimeValue::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+        _ => Err(anyhow!(
+            "{} expects a non-negative integer index but got {}.",
+            name,
+            value
+        )),
+    }
+}
+
+fn abs(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::Number(expect_number("abs", &args[0])?.abs()))
+}
+
+/// Signals an error, including the message if one is given, unless the
+/// condition is truthy.
+fn assert(interpreter: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    if is_truthy(&args[0]) {
+        return Ok(RuntimeValue::Nil);
+    }
+    match args.get(1) {
+        Some(message) => Err(anyhow!(
+            "Assertion failed: {}.",
+            interpreter.stringify(message)
+        )),
+        None => Err(anyhow!("Assertion failed.")),
+    }
+}
+
+fn ceil(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::Number(
+        expect_number("ceil", &args[0])?.ceil(),
+    ))
+}
+
+/// Stops the program with the given status code.
+fn exit(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    match args[0] {
+        RuntimeValue::Number(n) if n.fract() == 0.0 && n.abs() <= i32::MAX as f64 => {
+            Err(ExitError(n as i32).into())
+        }
+        _ => Err(anyhow!(
+            "exit expects an integer status code but got {}.",
+            args[0]
+        )),
+    }
+}
+
+fn expect_fs_access(name: &str, interpreter: &Interpreter) -> Result<()> {
+    if interpreter.allow_fs {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} is not allowed, since this interpreter has no access to the filesystem.",
+            name
+        ))
+    }
+}
+
+fn file_read(interpreter: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    expect_fs_access("file_read", interpreter)?;
+    let path = expect_string("file_read", &args[0])?;
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(RuntimeValue::String(contents)),
+        Err(err) => Err(anyhow!("file_read could not read {:?}: {}.", path, err)),
+    }
+}
+
+fn file_write(interpreter: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    expect_fs_access("file_write", interpreter)?;
+    let path = expect_string("file_write", &args[0])?;
+    let contents = expect_string("file_write", &args[1])?;
+    match std::fs::write(path, contents) {
+        Ok(()) => Ok(RuntimeValue::Nil),
+        Err(err) => Err(anyhow!("file_write could not write {:?}: {}.", path, err)),
+    }
+}
+
+fn floor(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::Number(
+        expect_number("floor", &args[0])?.floor(),
+    ))
+}
+
+#[cfg(feature = "json")]
+fn from_json(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    crate::json::from_json(expect_string("from_json", &args[0])?)
+}
+
+fn index_of(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let s = expect_string("index_of", &args[0])?;
+    let needle = expect_string("index_of", &args[1])?;
+    // convert the byte offset found into a character index
+    let index = match s.find(needle) {
+        Some(offset) => s[..offset].chars().count() as f64,
+        None => -1.0,
+    };
+    Ok(RuntimeValue::Number(index))
+}
+
+/// Returns a new list of the keys in a map, in sorted order, with numbers
+/// before strings.
+fn keys(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let map = expect_map("keys", &args[0])?;
+    let keys = map.borrow().keys().sorted().map(MapKey::to_value).collect();
+    Ok(RuntimeValue::List(Rc::new(RefCell::new(keys))))
+}
+
+/// Returns the number of characters in a string, or elements in a list or
+/// map.
+fn len(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let len = match &args[0] {
+        RuntimeValue::String(s) => s.chars().count(),
+        RuntimeValue::List(list) => list.borrow().len(),
+        RuntimeValue::Map(map) => map.borrow().len(),
+        value => {
+            return Err(anyhow!(
+                "len expects a string, list or map but got {}.",
+                value
+            ))
+        }
+    };
+    Ok(RuntimeValue::Number(len as f64))
+}
+
+fn lower(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let s = expect_string("lower", &args[0])?;
+    Ok(RuntimeValue::String(s.to_lowercase()))
+}
+
+fn max(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let x = expect_number("max", &args[0])?;
+    let y = expect_number("max", &args[1])?;
+    Ok(RuntimeValue::Number(x.max(y)))
+}
+
+fn min(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let x = expect_number("min", &args[0])?;
+    let y = expect_number("min", &args[1])?;
+    Ok(RuntimeValue::Number(x.min(y)))
+}
+
+/// Parses a string into a number, ignoring surrounding whitespace. Returns
+/// nil if the string is not a finite number.
+fn num(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let s = expect_string("num", &args[0])?;
+    match s.trim().parse::<f64>() {
+        Ok(n) if n.is_finite() => Ok(RuntimeValue::Number(n)),
+        _ => Ok(RuntimeValue::Nil),
+    }
+}
+
+fn pop(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let list = expect_list("pop", &args[0])?;
+    let value = list.borrow_mut().pop();
+    value.ok_or_else(|| anyhow!("pop expects a non-empty list."))
+}
+
+/// Raises `base` to the power `exponent`. Results that can't be represented
+/// as a real number, like `pow(-8, 1/3)`, are NaN.
+fn pow(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let base = expect_number("pow", &args[0])?;
+    let exponent = expect_number("pow", &args[1])?;
+    Ok(RuntimeValue::Number(base.powf(exponent)))
+}
+
+fn push(_: &mut Interpreter, mut args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let value = args.pop().unwrap();
+    expect_list("push", &args[0])?.borrow_mut().push(value);
+    Ok(RuntimeValue::Nil)
+}
+
+/// Returns a random number that is at least 0 and less than 1.
+fn random(interpreter: &mut Interpreter, _: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::Number(interpreter.rng.next_f64()))
+}
+
+/// Returns a random integer between `lo` and `hi`, inclusive.
+fn random_int(interpreter: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let lo = expect_integer("random_int", &args[0])?;
+    let hi = expect_integer("random_int", &args[1])?;
+    if lo > hi {
+        return Err(anyhow!(
+            "random_int expects lo to be at most hi but got {} and {}.",
+            lo,
+            hi
+        ));
+    }
+    let count = hi - lo + 1.0;
+    Ok(RuntimeValue::Number(
+        lo + (interpreter.rng.next_f64() * count).floor(),
+    ))
+}
+
+/// Reads a line of input, returning nil once there is no more.
+fn readline(interpreter: &mut Interpreter, _: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    Ok(match interpreter.read_line()? {
+        Some(line) => RuntimeValue::String(line),
+        None => RuntimeValue::Nil,
+    })
+}
+
+fn replace(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let s = expect_string("replace", &args[0])?;
+    let from = expect_string("replace", &args[1])?;
+    let to = expect_string("replace", &args[2])?;
+    if from.is_empty() {
+        return Err(anyhow!("replace expects a non-empty pattern."));
+    }
+    Ok(RuntimeValue::String(s.replace(from, to)))
+}
+
+/// Returns the square root of a number, or NaN for negative numbers.
+fn sqrt(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::Number(
+        expect_number("sqrt", &args[0])?.sqrt(),
+    ))
+}
+
+/// Converts any value into the string that `print` would display for it.
+fn str(interpreter: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::String(interpreter.stringify(&args[0])))
+}
+
+/// Returns the characters of a string from `start` up to (but not including)
+/// `end`. Indices past the end of the string are clamped to its length.
+fn substring(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let s = expect_string("substring", &args[0])?;
+    let start = expect_index("substring", &args[1])?;
+    let end = expect_index("substring", &args[2])?;
+    if start > end {
+        return Err(anyhow!(
+            "substring expects start to be at most end but got {} and {}.",
+            start,
+            end
+        ));
+    }
+    Ok(RuntimeValue::String(
+        s.chars().skip(start).take(end - start).collect(),
+    ))
+}
+
+/// Returns a value encoded as a JSON string, with lists as arrays and maps
+/// as objects. Fails for values JSON can't represent, such as functions,
+/// infinite numbers, and lists or maps that contain themselves.
+#[cfg(feature = "json")]
+fn to_json(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::String(crate::json::to_json(&args[0])?))
+}
+
+/// Returns the name of a value's type, such as "number" or "list".
+fn type_of(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::String(args[0].type_name().to_string()))
+}
+
+fn upper(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let s = expect_string("upper", &args[0])?;
+    Ok(RuntimeValue::String(s.to_uppercase()))
+}
+
+/// Returns a new list of the values in a map, in the order of their keys,
+/// as `keys` gives them.
+fn values(_: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let map = expect_map("values", &args[0])?;
+    let values = map
+        .borrow()
+        .iter()
+        .sorted_by(|(x, _), (y, _)| x.cmp(y))
+        .map(|(_, value)| value.clone())
+        .collect();
+    Ok(RuntimeValue::List(Rc::new(RefCell::new(values))))
+}
+
+/// Outputs a value the way `print` displays it, but without a trailing
+/// newline.
+fn write(interpreter: &mut Interpreter, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let text = interpreter.stringify(&args[0]);
+    interpreter.write_text(&text)?;
+    Ok(RuntimeValue::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run;
+    use crate::Interpreter;
+
+    #[test]
+    fn write_without_newlines() {
+        assert_eq!(
+            run(r#"for (var i = 0; i < 3; i += 1) write("="); write(1); print "";"#).unwrap(),
+            "===1\n"
+        );
+        assert_eq!(run("print write(\"\");").unwrap(), "nil\n");
+    }
+
+    #[test]
+    fn replace_all_occurrences() {
+        assert_eq!(
+            run(r#"print replace("a.b.c", ".", "-");"#).unwrap(),
+            "a-b-c\n"
+        );
+        assert_eq!(run(r#"print replace("aaaa", "aa", "b");"#).unwrap(), "bb\n");
+    }
+
+    #[test]
+    fn len_counts_characters() {
+        assert_eq!(run(r#"print len("");"#).unwrap(), "0\n");
+        assert_eq!(run(r#"print len("hello");"#).unwrap(), "5\n");
+        assert_eq!(run(r#"print len("世界");"#).unwrap(), "2\n");
+        let err = run("len(3);").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "len expects a string, list or map but got 3 on line 1:1."
+        );
+    }
+
+    #[test]
+    fn push_pop_and_len_of_lists() {
+        assert_eq!(
+            run(
+                "var l = []; for (var i = 0; i < 5; i += 1) push(l, i * i); print len(l); print l;"
+            )
+            .unwrap(),
+            "5\n[0, 1, 4, 9, 16]\n"
+        );
+        assert_eq!(
+            run("var l = [1, 2]; var alias = l; print pop(alias); print len(l); print push(l, 3);")
+                .unwrap(),
+            "2\n1\nnil\n"
+        );
+        let err = run("pop([]);").unwrap_err();
+        assert_eq!(err.to_string(), "pop expects a non-empty list on line 1:1.");
+        let err = run(r#"push("abc", 1);"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "push expects a list but got abc on line 1:1."
+        );
+    }
+
+    #[test]
+    fn keys_and_len_of_maps() {
+        assert_eq!(
+            run(r#"var m = {"b": 1, 2: 2, "a": 3}; print keys(m); print len(m);"#).unwrap(),
+            "[2, a, b]\n3\n"
+        );
+        assert_eq!(run("print keys({});").unwrap(), "[]\n");
+        assert_eq!(
+            run(r#"var m = {"b": 1, 2: 2, "a": 3}; print values(m); print values({});"#).unwrap(),
+            "[2, 3, 1]\n[]\n"
+        );
+        let err = run("values(\"ab\");").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "values expects a map but got ab on line 1:1."
+        );
+        let err = run("keys([1]);").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "keys expects a map but got [1] on line 1:1."
+        );
+    }
+
+    #[test]
+    fn read_and_write_files() {
+        let dir = std::env::temp_dir().join(format!("lox-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt").to_str().unwrap().replace('\\', "/");
+        let mut interpreter = Interpreter::default();
+        interpreter.allow_fs = true;
+        let source = format!(
+            r#"
+            var path = "{}";
+            print file_write(path, "one\ntwo");
+            file_write(path, file_read(path) + "\nthree");
+            print file_read(path);
+            "#,
+            path
+        );
+        crate::run_with(&mut interpreter, &source).unwrap();
+        assert_eq!(interpreter.stdout, "nil\none\ntwo\nthree\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\nthree");
+
+        let missing = dir.join("missing.txt").to_str().unwrap().replace('\\', "/");
+        let err =
+            crate::run_with(&mut interpreter, &format!("file_read(\"{}\");", missing)).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with(&format!("file_read could not read {:?}: ", missing)));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // programs can't touch files unless the interpreter allows it
+        let err = run(&format!("print file_read(\"{}\");", path)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "file_read is not allowed, since this interpreter has no access to the filesystem on line 1:7."
+        );
+        let err = run("file_write(\"x.txt\", \"\");").unwrap_err();
+        assert!(err.to_string().starts_with("file_write is not allowed"));
+    }
+
+    #[test]
+    fn readline_reads_lines_until_the_end() {
+        let mut interpreter = Interpreter::default();
+        interpreter.set_reader(Box::new("one\r\ntwo\n\nthree".as_bytes()));
+        crate::run_with(
+            &mut interpreter,
+            "var line = readline(); while (line != nil) { print \"<\" + line + \">\"; line = readline(); }",
+        )
+        .unwrap();
+        assert_eq!(interpreter.stdout, "<one>\n<two>\n<>\n<three>\n");
+    }
+
+    #[test]
+    fn exit_stops_the_program() {
+        let mut interpreter = Interpreter::default();
+        let source = "fun stop() { exit(3); } print 1; stop(); print 2;";
+        crate::run_with(&mut interpreter, source).unwrap();
+        assert_eq!(interpreter.stdout, "1\n");
+        assert_eq!(interpreter.exit_code, Some(3));
+
+        let mut interpreter = Interpreter::default();
+        crate::run_with(&mut interpreter, "print 1;").unwrap();
+        assert_eq!(interpreter.exit_code, None);
+
+        let err = run("exit(1.5);").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "exit expects an integer status code but got 1.5 on line 1:1."
+        );
+    }
+
+    #[test]
+    fn substring_ranges() {
+        assert_eq!(run(r#"print substring("hello", 1, 3);"#).unwrap(), "el\n");
+        assert_eq!(run(r#"print substring("hello", 2, 99);"#).unwrap(), "llo\n");
+        assert_eq!(run(r#"print substring("hello", 9, 99);"#).unwrap(), "\n");
+        let err = run(r#"substring("hello", -1, 2);"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "substring expects a non-negative integer index but got -1 on line 1:1."
+        );
+        let err = run(r#"substring("hello", 3, 1);"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "substring expects start to be at most end but got 3 and 1 on line 1:1."
+        );
+    }
+
+    #[test]
+    fn str_and_num_conversions() {
+        assert_eq!(
+            run(r#"var n = 3; print "count: " + str(n);"#).unwrap(),
+            "count: 3\n"
+        );
+        assert_eq!(run("print str(nil) + str(true);").unwrap(), "niltrue\n");
+        assert_eq!(run(r#"print num(" 2.5 ") * 2;"#).unwrap(), "5\n");
+        assert_eq!(run(r#"print num("abc");"#).unwrap(), "nil\n");
+        assert_eq!(run(r#"print num("inf");"#).unwrap(), "nil\n");
+    }
+
+    #[test]
+    fn type_names() {
+        let source = r#"
+            class A {}
+            fun f() {}
+            var values = [true, f, A, A(), [], {}, len, nil, 1, "s"];
+            for (value in values) print type(value);
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            "bool\nfunction\nclass\ninstance\nlist\nmap\nfunction\nnil\nnumber\nstring\n"
+        );
+    }
+
+    #[test]
+    fn assertions() {
+        assert_eq!(
+            run("assert(1 < 2); assert(\"\", \"unused\"); print 1;").unwrap(),
+            "1\n"
+        );
+        let err = run("var x = 2;\nassert(x == 1, \"x is \" + str(x));").unwrap_err();
+        assert_eq!(err.to_string(), "Assertion failed: x is 2 on line 2:1.");
+        let err = run("assert(nil);").unwrap_err();
+        assert_eq!(err.to_string(), "Assertion failed on line 1:1.");
+        let err = run("assert();").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "assert expects 1 to 2 arguments but got 0 on line 1:1."
+        );
+    }
+
+    #[test]
+    fn math_functions() {
+        assert_eq!(
+            run("print abs(-2.5); print floor(-2.5); print ceil(-2.5);").unwrap(),
+            "2.5\n-3\n-2\n"
+        );
+        assert_eq!(
+            run("print sqrt(16); print sqrt(-1); print pow(2, 10); print pow(4, 0.5);").unwrap(),
+            "4\nNaN\n1024\n2\n"
+        );
+        assert_eq!(
+            run("print min(3, -1); print max(3, -1);").unwrap(),
+            "-1\n3\n"
+        );
+        let err = run(r#"sqrt("4");"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "sqrt expects a number but got 4 on line 1:1."
+        );
+        let err = run("max(1, nil);").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "max expects a number but got nil on line 1:1."
+        );
+        let err = run("pow(2);").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "pow expects 2 arguments but got 1 on line 1:1."
+        );
+    }
+
+    #[test]
+    fn seeded_random_numbers() {
+        let source = "for (var i = 0; i < 5; i += 1) print [random(), random_int(1, 6)];";
+        let mut a = Interpreter::default();
+        a.seed_rng(5);
+        crate::run_with(&mut a, source).unwrap();
+        let mut b = Interpreter::default();
+        b.seed_rng(5);
+        crate::run_with(&mut b, source).unwrap();
+        assert_eq!(a.stdout, b.stdout);
+
+        let mut interpreter = Interpreter::default();
+        interpreter.seed_rng(0);
+        let source = "var ok = true; for (var i = 0; i < 200; i += 1) { \
+                      var x = random(); var n = random_int(-2, 2); \
+                      ok = ok and x >= 0 and x < 1 and n >= -2 and n <= 2 and floor(n) == n; } \
+                      print ok; print random_int(3, 3);";
+        crate::run_with(&mut interpreter, source).unwrap();
+        assert_eq!(interpreter.stdout, "true\n3\n");
+
+        let err = run("random_int(1, 2.5);").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "random_int expects an integer but got 2.5 on line 1:1."
+        );
+        let err = run("random_int(2, 1);").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "random_int expects lo to be at most hi but got 2 and 1 on line 1:1."
+        );
+    }
+
+    #[test]
+    fn string_natives_reject_other_types() {
+        let err = run("upper(nil);").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "upper expects a string but got nil on line 1:1."
+        );
+        let err = run(r#"index_of("abc", 1);"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "index_of expects a string but got 1 on line 1:1."
+        );
+    }
+
+    #[test]
+    fn native_wrong_arity() {
+        let err = run(r#"replace("abc", "b");"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "replace expects 3 arguments but got 2 on line 1:1."
+        );
+    }
+
+    #[test]
+    fn replace_empty_pattern() {
+        let err = run(r#"print replace("abc", "", "-");"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "replace expects a non-empty pattern on line 1:7."
+        );
+    }
+}
@@ -1,13 +1,46 @@
+use std::rc::Rc;
+
 use crate::{
     cursor::Cursor,
-    expr::{Assign, Binary, Call, Expr, Grouping, Literal, Logical, Unary, Variable},
-    stmt::{Block, Expression, Function, If, Print, Return, Stmt, Var, While},
-    token::{Token, TokenKind},
+    error::LoxError,
+    expr::{
+        Assign, Binary, Call, Expr, Get, Grouping, Index, Interpolation, ListLiteral, Literal,
+        LiteralExpr, Logical, MapLiteral, Set, SetIndex, StringPart, Super, Ternary, This, Unary,
+        Variable,
+    },
+    span::Span,
+    stmt::{
+        Block, Class, DoWhile, Expression, For, ForIn, Function, If, Param, Print, Return, Stmt,
+        Switch, Throw, Try, Var, While,
+    },
+    symbol::Symbol,
+    token::{InterpolationPart, Token, TokenKind},
 };
 
+use std::cell::Cell;
+use std::fmt;
+
 use anyhow::anyhow;
 use anyhow::Result;
 
+/// A syntax error, along with the span of the token where it was detected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+    /// Whether the error was found at the end of the input, meaning that the
+    /// source may just be incomplete (e.g. a block that is not closed yet).
+    pub incomplete: bool,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 #[derive(Debug)]
 pub struct Parser {
     cursor: Cursor<Token>,
@@ -27,17 +60,63 @@ impl Parser {
         parser
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Stmt>> {
+    /// Parses the tokens into a list of statements. If there are syntax
+    /// errors, the parser recovers at the next statement boundary so that
+    /// every error in the source is reported at once.
+    pub fn parse(&mut self) -> std::result::Result<Vec<Stmt>, Vec<ParseError>> {
         let mut statements = vec![];
+        let mut errors = vec![];
         while !self.check(&TokenKind::Eof) {
-            statements.push(self.parse_declaration()?);
+            match self.parse_declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(err) => {
+                    errors.push(ParseError {
+                        message: err.to_string(),
+                        span: self.token.span,
+                        incomplete: self.check(&TokenKind::Eof),
+                    });
+                    self.synchronize();
+                }
+            }
         }
-        Ok(statements)
+        if errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parses the tokens as a single expression, signaling an error if there
+    /// are any tokens left over.
+    pub fn parse_standalone_expression(&mut self) -> Result<Expr> {
+        let result = self.parse_expression().and_then(|expr| {
+            if self.check(&TokenKind::Eof) {
+                Ok(expr)
+            } else {
+                Err(anyhow!(
+                    "Expected end of expression, found token {} on line {}",
+                    self.token.kind,
+                    self.token.location()
+                ))
+            }
+        });
+        result.map_err(|err| {
+            LoxError::Parse {
+                message: err.to_string(),
+                span: self.token.span,
+            }
+            .into()
+        })
     }
 
     fn parse_declaration(&mut self) -> Result<Stmt> {
-        if self.eat(&TokenKind::Fun) {
-            self.parse_function()
+        if self.eat(&TokenKind::Class) {
+            self.parse_class_declaration()
+        } else if self.eat(&TokenKind::Fun) {
+            let start = self.prev_token.span;
+            let mut function = self.parse_function()?;
+            function.span = start.to(function.span);
+            Ok(Stmt::Function(function))
         } else if self.eat(&TokenKind::Var) {
             self.parse_var_declaration()
         } else {
@@ -45,8 +124,43 @@ impl Parser {
         }
     }
 
+    fn parse_class_declaration(&mut self) -> Result<Stmt> {
+        let start = self.prev_token.span;
+        let class_location = self.prev_token.location();
+        let name = self.expect_identifier()?;
+        let superclass = if self.eat(&TokenKind::Less) {
+            Some(Variable {
+                name: self.expect_identifier()?,
+                depth: Cell::new(None),
+                span: self.prev_token.span,
+            })
+        } else {
+            None
+        };
+        self.expect(
+            &TokenKind::LeftBrace,
+            format!("Expected '{{' before class body on line {}", class_location),
+        )?;
+        let mut methods = vec![];
+        while !self.check(&TokenKind::RightBrace) && !self.check(&TokenKind::Eof) {
+            methods.push(self.parse_function()?);
+        }
+        self.expect(
+            &TokenKind::RightBrace,
+            format!("Expected '}}' after class body on line {}", class_location),
+        )?;
+        Ok(Stmt::Class(Class {
+            name,
+            superclass,
+            methods,
+            span: self.span_from(start),
+        }))
+    }
+
     fn parse_statement(&mut self) -> Result<Stmt> {
-        if self.check(&TokenKind::For) {
+        if self.eat(&TokenKind::Do) {
+            self.parse_do_while_statement()
+        } else if self.check(&TokenKind::For) {
             self.parse_for_statement()
         } else if self.check(&TokenKind::If) {
             self.parse_if_statement()
@@ -54,11 +168,20 @@ impl Parser {
             self.parse_print_statement()
         } else if self.eat(&TokenKind::Return) {
             self.parse_return_statement()
+        } else if self.eat(&TokenKind::Switch) {
+            self.parse_switch_statement()
+        } else if self.eat(&TokenKind::Throw) {
+            self.parse_throw_statement()
+        } else if self.eat(&TokenKind::Try) {
+            self.parse_try_statement()
         } else if self.eat(&TokenKind::While) {
             self.parse_while_statement()
         } else if self.eat(&TokenKind::LeftBrace) {
+            let start = self.prev_token.span;
+            let statements = self.parse_block()?;
             Ok(Stmt::Block(Block {
-                statements: self.parse_block()?,
+                statements,
+                span: self.span_from(start),
             }))
         } else {
             self.parse_expression_statement()
@@ -66,16 +189,22 @@ impl Parser {
     }
 
     fn parse_for_statement(&mut self) -> Result<Stmt> {
+        let start = self.token.span;
         self.expect(&TokenKind::For, "Expected 'for' statement.".into())?;
         self.expect(&TokenKind::LeftParen, "Expected '(' after 'for'.".into())?;
-        let initializer = if self.check(&TokenKind::Semicolon) {
+        let next_kind = self.cursor.look_ahead(0).map(|token| &token.kind);
+        if matches!(self.token.kind, TokenKind::Identifier(_)) && next_kind == Some(&TokenKind::In)
+        {
+            return self.parse_for_in_statement(start);
+        }
+        let initializer = if self.eat(&TokenKind::Semicolon) {
             None
         } else if self.eat(&TokenKind::Var) {
-            Some(self.parse_var_declaration()?)
+            Some(Box::new(self.parse_var_declaration()?))
         } else {
-            Some(self.parse_expression_statement()?)
+            Some(Box::new(self.parse_expression_statement()?))
         };
-        let mut condition = if !self.check(&TokenKind::Semicolon) {
+        let condition = if !self.check(&TokenKind::Semicolon) {
             Some(self.parse_expression()?)
         } else {
             None
@@ -84,7 +213,7 @@ impl Parser {
             &TokenKind::Semicolon,
             "Expected ';' after loop condition.".into(),
         )?;
-        let increment = if !self.check(&TokenKind::Semicolon) {
+        let increment = if !self.check(&TokenKind::RightParen) {
             Some(self.parse_expression()?)
         } else {
             None
@@ -93,28 +222,37 @@ impl Parser {
             &TokenKind::RightParen,
             "Expected ')' after for clauses.".into(),
         )?;
-        let mut body = self.parse_statement()?;
-        if let Some(expr) = increment {
-            body = Stmt::Block(Block {
-                statements: vec![body, Stmt::Expression(Expression { expression: expr })],
-            });
-        }
-        if condition.is_none() {
-            condition = Some(Expr::Literal(Literal::Bool(true)));
-        }
-        body = Stmt::While(While {
-            condition: condition.unwrap(),
+        let body = self.parse_statement()?;
+        Ok(Stmt::For(For {
+            initializer,
+            condition,
+            increment,
+            body: Box::new(body),
+            span: self.span_from(start),
+        }))
+    }
+
+    /// Parses the rest of a `for (name in iterable) body` loop, starting at
+    /// the name.
+    fn parse_for_in_statement(&mut self, start: Span) -> Result<Stmt> {
+        let name = self.expect_identifier()?;
+        self.expect(&TokenKind::In, "Expected 'in' after loop variable.".into())?;
+        let iterable = self.parse_expression()?;
+        self.expect(
+            &TokenKind::RightParen,
+            "Expected ')' after for clauses.".into(),
+        )?;
+        let body = self.parse_statement()?;
+        Ok(Stmt::ForIn(ForIn {
+            name,
+            iterable,
             body: body.into(),
-        });
-        if let Some(expr) = initializer {
-            body = Stmt::Block(Block {
-                statements: vec![expr, body],
-            });
-        }
-        Ok(body)
+            span: self.span_from(start),
+        }))
     }
 
     fn parse_if_statement(&mut self) -> Result<Stmt> {
+        let start = self.token.span;
         self.expect(&TokenKind::If, "Expected if statement.".into())?;
         self.expect(&TokenKind::LeftParen, "Expected '(' after 'if'.".into())?;
         let condition = self.parse_expression()?;
@@ -124,7 +262,7 @@ impl Parser {
         )?;
 
         let then_branch = self.parse_statement()?;
-        let else_branch = if self.check(&TokenKind::Else) {
+        let else_branch = if self.eat(&TokenKind::Else) {
             Some(self.parse_statement()?)
         } else {
             None
@@ -133,24 +271,58 @@ impl Parser {
             condition,
             then_branch: then_branch.into(),
             else_branch: else_branch.map(|e| e.into()),
+            span: self.span_from(start),
         }))
     }
 
     fn parse_expression_statement(&mut self) -> Result<Stmt> {
-        let line = self.token.line;
+        let start = self.token.span;
+        let location = self.token.location();
         let expression = self.parse_expression()?;
         if self.eat(&TokenKind::Semicolon) {
-            Ok(Stmt::Expression(Expression { expression }))
+            Ok(Stmt::Expression(Expression {
+                expression,
+                span: self.span_from(start),
+            }))
         } else {
-            Err(anyhow!("Expected ';' after value on line {}", line))
+            Err(anyhow!("Expected ';' after value on line {}", location))
         }
     }
 
+    fn parse_do_while_statement(&mut self) -> Result<Stmt> {
+        let start = self.prev_token.span;
+        let do_location = self.prev_token.location();
+        let body = self.parse_statement()?;
+        self.expect(
+            &TokenKind::While,
+            format!(
+                "Expected 'while' after the body of 'do' on line {}.",
+                do_location
+            ),
+        )?;
+        self.expect(&TokenKind::LeftParen, "Expected '(' after 'while'.".into())?;
+        let condition = self.parse_expression()?;
+        self.expect(
+            &TokenKind::RightParen,
+            "Expected ')' after condition.".into(),
+        )?;
+        self.expect(
+            &TokenKind::Semicolon,
+            format!("Expected ';' after the 'do' loop on line {}.", do_location),
+        )?;
+        Ok(Stmt::DoWhile(DoWhile {
+            body: body.into(),
+            condition,
+            span: self.span_from(start),
+        }))
+    }
+
     fn parse_while_statement(&mut self) -> Result<Stmt> {
-        let while_line = self.prev_token.line;
+        let start = self.prev_token.span;
+        let while_location = self.prev_token.location();
         self.expect(
             &TokenKind::LeftParen,
-            format!("Expected '(' after 'while' on line {}.", while_line),
+            format!("Expected '(' after 'while' on line {}.", while_location),
         )?;
         let condition = self.parse_expression()?;
         self.expect(
@@ -161,12 +333,13 @@ impl Parser {
         Ok(Stmt::While(While {
             condition,
             body: body.into(),
+            span: self.span_from(start),
         }))
     }
 
     fn parse_block(&mut self) -> Result<Vec<Stmt>> {
         let mut statements = vec![];
-        let open_brace_line = self.prev_token.line;
+        let open_brace_location = self.prev_token.location();
         while !self.check(&TokenKind::RightBrace) {
             statements.push(self.parse_declaration()?);
         }
@@ -175,44 +348,187 @@ impl Parser {
         } else {
             Err(anyhow!(
                 "Expected '}}' to match '{{' on line {}",
-                open_brace_line
+                open_brace_location
             ))
         }
     }
 
     fn parse_print_statement(&mut self) -> Result<Stmt> {
-        let value_line = self.token.line;
-        let expression = self.parse_expression()?;
+        let start = self.prev_token.span;
+        let value_location = self.token.location();
+        let mut expressions = vec![self.parse_expression()?];
+        while self.eat(&TokenKind::Comma) {
+            expressions.push(self.parse_expression()?);
+        }
         self.expect(
             &TokenKind::Semicolon,
-            format!("Expected ';' after value on line {}", value_line),
+            format!("Expected ';' after value on line {}", value_location),
         )?;
-        Ok(Stmt::Print(Print { expression }))
+        Ok(Stmt::Print(Print {
+            expressions,
+            span: self.span_from(start),
+        }))
     }
 
     fn parse_return_statement(&mut self) -> Result<Stmt> {
-        let value_line = self.token.line;
+        let start = self.prev_token.span;
+        let value_location = self.token.location();
+        let value = self.parse_expression()?;
+        self.expect(
+            &TokenKind::Semicolon,
+            format!("Expected ';' after return value on line {}", value_location),
+        )?;
+        Ok(Stmt::Return(Return {
+            value,
+            span: self.span_from(start),
+        }))
+    }
+
+    fn parse_switch_statement(&mut self) -> Result<Stmt> {
+        let start = self.prev_token.span;
+        let switch_location = self.prev_token.location();
+        self.expect(
+            &TokenKind::LeftParen,
+            format!("Expected '(' after 'switch' on line {}.", switch_location),
+        )?;
+        let subject = self.parse_expression()?;
+        self.expect(&TokenKind::RightParen, "Expected ')' after subject.".into())?;
+        self.expect(
+            &TokenKind::LeftBrace,
+            format!(
+                "Expected '{{' before switch cases on line {}.",
+                switch_location
+            ),
+        )?;
+
+        let mut cases = vec![];
+        let mut default = None;
+        while !self.eat(&TokenKind::RightBrace) {
+            let case_location = self.token.location();
+            if self.eat(&TokenKind::Case) {
+                let value = self.parse_expression()?;
+                self.expect(
+                    &TokenKind::Colon,
+                    format!("Expected ':' after case value on line {}.", case_location),
+                )?;
+                cases.push((value, self.parse_case_body()?));
+            } else if self.eat(&TokenKind::Default) {
+                if default.is_some() {
+                    return Err(anyhow!(
+                        "Expected at most one 'default' case in the switch on line {}.",
+                        switch_location
+                    ));
+                }
+                self.expect(
+                    &TokenKind::Colon,
+                    format!("Expected ':' after 'default' on line {}.", case_location),
+                )?;
+                default = Some(self.parse_case_body()?);
+            } else if self.check(&TokenKind::Eof) {
+                return Err(anyhow!(
+                    "Expected '}}' to match '{{' of the switch on line {}",
+                    switch_location
+                ));
+            } else {
+                return Err(anyhow!(
+                    "Expected 'case' or 'default', found token {} on line {}",
+                    self.token.kind,
+                    case_location
+                ));
+            }
+        }
+        Ok(Stmt::Switch(Switch {
+            subject,
+            cases,
+            default,
+            span: self.span_from(start),
+        }))
+    }
+
+    /// Parses the statements of a switch case, up to the next case or the
+    /// end of the switch.
+    fn parse_case_body(&mut self) -> Result<Vec<Stmt>> {
+        let mut statements = vec![];
+        while !self.check(&TokenKind::Case)
+            && !self.check(&TokenKind::Default)
+            && !self.check(&TokenKind::RightBrace)
+            && !self.check(&TokenKind::Eof)
+        {
+            statements.push(self.parse_declaration()?);
+        }
+        Ok(statements)
+    }
+
+    fn parse_throw_statement(&mut self) -> Result<Stmt> {
+        let start = self.prev_token.span;
+        let value_location = self.token.location();
         let value = self.parse_expression()?;
         self.expect(
             &TokenKind::Semicolon,
-            format!("Expected ';' after return value on line {}", value_line),
+            format!("Expected ';' after thrown value on line {}", value_location),
         )?;
-        Ok(Stmt::Return(Return { value }))
+        Ok(Stmt::Throw(Throw {
+            value,
+            span: self.span_from(start),
+        }))
+    }
+
+    fn parse_try_statement(&mut self) -> Result<Stmt> {
+        let start = self.prev_token.span;
+        let try_location = self.prev_token.location();
+        self.expect(
+            &TokenKind::LeftBrace,
+            format!("Expected '{{' after 'try' on line {}.", try_location),
+        )?;
+        let body = self.parse_block()?;
+        self.expect(
+            &TokenKind::Catch,
+            format!(
+                "Expected 'catch' after the 'try' block from line {}.",
+                try_location
+            ),
+        )?;
+        let catch_location = self.prev_token.location();
+        self.expect(
+            &TokenKind::LeftParen,
+            format!("Expected '(' after 'catch' on line {}.", catch_location),
+        )?;
+        let catch_name = self.expect_identifier()?;
+        self.expect(
+            &TokenKind::RightParen,
+            "Expected ')' after catch variable.".into(),
+        )?;
+        self.expect(
+            &TokenKind::LeftBrace,
+            format!(
+                "Expected '{{' before catch body on line {}.",
+                catch_location
+            ),
+        )?;
+        let catch_body = self.parse_block()?;
+        Ok(Stmt::Try(Try {
+            body,
+            catch_name,
+            catch_body,
+            span: self.span_from(start),
+        }))
     }
 
     fn parse_var_declaration(&mut self) -> Result<Stmt> {
-        let var_line = self.prev_token.line;
+        let start = self.prev_token.span;
+        let var_location = self.prev_token.location();
         let name = self.expect_identifier()?;
         if !self.eat(&TokenKind::Equal) {
             if self.eat(&TokenKind::Semicolon) {
                 return Ok(Stmt::Var(Var {
                     name,
                     initializer: None,
+                    span: self.span_from(start),
                 }));
             } else {
                 return Err(anyhow!(
                     "Expected ';' after variable declaration on line {}",
-                    var_line
+                    var_location
                 ));
             }
         }
@@ -221,11 +537,12 @@ impl Parser {
             Ok(Stmt::Var(Var {
                 name,
                 initializer: Some(initializer),
+                span: self.span_from(start),
             }))
         } else {
             Err(anyhow!(
                 "Expected ';' after variable declaration on line {}",
-                var_line
+                var_location
             ))
         }
     }
@@ -234,11 +551,16 @@ impl Parser {
         self.parse_assignment()
     }
 
-    fn parse_function(&mut self) -> Result<Stmt> {
+    fn parse_function(&mut self) -> Result<Function> {
+        let start = self.token.span;
         let name = self.expect_identifier()?;
         self.expect(
             &TokenKind::LeftParen,
-            format!("Expected '(' after {} on line {}", name, self.token.line),
+            format!(
+                "Expected '(' after {} on line {}",
+                name,
+                self.token.location()
+            ),
         )?;
         let mut params = vec![];
         if !self.check(&TokenKind::RightParen) {
@@ -246,7 +568,7 @@ impl Parser {
                 if params.len() >= 255 {
                     return Err(anyhow!("Can't have more than 255 parameters."));
                 }
-                params.push(self.expect_identifier()?);
+                params.push(self.parse_param()?);
                 if self.check(&TokenKind::Comma) {
                     self.bump();
                 } else {
@@ -263,55 +585,179 @@ impl Parser {
             "Expected '{' before function body.".into(),
         )?;
         let body = self.parse_block()?;
-        Ok(Stmt::Function(Function { name, params, body }))
+        Ok(Function {
+            name,
+            params,
+            body: Rc::new(body),
+            span: self.span_from(start),
+        })
+    }
+
+    /// Parses a single function parameter, which may be a (possibly nested)
+    /// list pattern such as `[x, y]`.
+    fn parse_param(&mut self) -> Result<Param> {
+        if !self.eat(&TokenKind::LeftBracket) {
+            return Ok(Param::Name(self.expect_identifier()?));
+        }
+        let open_bracket_location = self.prev_token.location();
+        let mut params = vec![];
+        if !self.check(&TokenKind::RightBracket) {
+            loop {
+                params.push(self.parse_param()?);
+                if !self.eat(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+        self.expect(
+            &TokenKind::RightBracket,
+            format!(
+                "Expected ']' to match '[' on line {}",
+                open_bracket_location
+            ),
+        )?;
+        Ok(Param::List(params))
     }
 
     fn parse_assignment(&mut self) -> Result<Expr> {
-        let expr = self.parse_or()?;
+        let start = self.token.span;
+        let expr = self.parse_ternary()?;
         if self.eat(&TokenKind::Equal) {
-            let line = self.token.line;
+            let location = self.token.location();
             let value = self.parse_assignment()?;
             match expr {
-                Expr::Variable(Variable { name }) => Ok(Expr::Assign(Assign {
+                Expr::Variable(Variable { name, .. }) => Ok(Expr::Assign(Assign {
+                    name,
+                    operator: None,
+                    value: Box::from(value),
+                    depth: Cell::new(None),
+                    span: self.span_from(start),
+                })),
+                Expr::Get(Get { object, name, .. }) => Ok(Expr::Set(Set {
+                    object,
                     name,
+                    operator: None,
+                    value: Box::from(value),
+                    span: self.span_from(start),
+                })),
+                Expr::Index(Index { object, index, .. }) => Ok(Expr::SetIndex(SetIndex {
+                    object,
+                    index,
+                    operator: None,
                     value: Box::from(value),
+                    span: self.span_from(start),
                 })),
-                _ => Err(anyhow!("Invalid assignment target on line {}", line)),
+                _ => Err(anyhow!("Invalid assignment target on line {}", location)),
+            }
+        } else if let Some(operator) = self.token.compound_operator() {
+            self.bump();
+            let location = self.token.location();
+            let value = self.parse_assignment()?;
+            let span = self.span_from(start);
+            // the operator is kept for the interpreter to apply, so that the
+            // object and index of a target are only evaluated once
+            match expr {
+                Expr::Variable(Variable { name, .. }) => Ok(Expr::Assign(Assign {
+                    name,
+                    operator: Some(operator),
+                    value: Box::from(value),
+                    depth: Cell::new(None),
+                    span,
+                })),
+                Expr::Get(Get { object, name, .. }) => Ok(Expr::Set(Set {
+                    object,
+                    name,
+                    operator: Some(operator),
+                    value: Box::from(value),
+                    span,
+                })),
+                Expr::Index(Index { object, index, .. }) => Ok(Expr::SetIndex(SetIndex {
+                    object,
+                    index,
+                    operator: Some(operator),
+                    value: Box::from(value),
+                    span,
+                })),
+                _ => Err(anyhow!("Invalid assignment target on line {}", location)),
             }
         } else {
             Ok(expr)
         }
     }
 
+    fn parse_ternary(&mut self) -> Result<Expr> {
+        let start = self.token.span;
+        let condition = self.parse_coalesce()?;
+        if !self.eat(&TokenKind::Question) {
+            return Ok(condition);
+        }
+        let location = self.prev_token.location();
+        let then_expr = self.parse_expression()?;
+        self.expect(
+            &TokenKind::Colon,
+            format!("Expected ':' to match '?' on line {}", location),
+        )?;
+        // the else branch may itself be a conditional, which makes the
+        // operator right-associative
+        let else_expr = self.parse_ternary()?;
+        Ok(Expr::Ternary(Ternary {
+            condition: Box::from(condition),
+            then_expr: Box::from(then_expr),
+            else_expr: Box::from(else_expr),
+            span: self.span_from(start),
+        }))
+    }
+
+    fn parse_coalesce(&mut self) -> Result<Expr> {
+        let start = self.token.span;
+        let mut expr = self.parse_or()?;
+        while self.eat(&TokenKind::QuestionQuestion) {
+            let operator = self.prev_token.kind.clone();
+            let right = self.parse_or()?;
+            expr = Expr::Logical(Logical {
+                left: Box::from(expr),
+                operator,
+                right: Box::from(right),
+                span: self.span_from(start),
+            })
+        }
+        Ok(expr)
+    }
+
     fn parse_or(&mut self) -> Result<Expr> {
+        let start = self.token.span;
         let mut expr = self.parse_and()?;
         while self.eat(&TokenKind::Or) {
             let operator = self.prev_token.kind.clone();
-            let right = self.parse_term()?;
+            let right = self.parse_and()?;
             expr = Expr::Logical(Logical {
                 left: Box::from(expr),
                 operator,
                 right: Box::from(right),
+                span: self.span_from(start),
             })
         }
         Ok(expr)
     }
 
     fn parse_and(&mut self) -> Result<Expr> {
+        let start = self.token.span;
         let mut expr = self.parse_equality()?;
         while self.eat(&TokenKind::And) {
             let operator = self.prev_token.kind.clone();
-            let right = self.parse_term()?;
+            let right = self.parse_equality()?;
             expr = Expr::Logical(Logical {
                 left: Box::from(expr),
                 operator,
                 right: Box::from(right),
+                span: self.span_from(start),
             })
         }
         Ok(expr)
     }
 
     fn parse_equality(&mut self) -> Result<Expr> {
+        let start = self.token.span;
         let mut expr = self.parse_comparison()?;
         while self.token.is_equality() {
             let operator = self.token.kind.clone();
@@ -321,14 +767,83 @@ impl Parser {
                 left: Box::from(expr),
                 operator,
                 right: Box::from(right),
+                span: self.span_from(start),
             })
         }
         Ok(expr)
     }
 
     fn parse_comparison(&mut self) -> Result<Expr> {
-        let mut expr = self.parse_term()?;
+        let start = self.token.span;
+        let mut expr = self.parse_bit_or()?;
         while self.token.is_comparison() {
+            let operator = self.token.kind.clone();
+            self.bump();
+            let right = self.parse_bit_or()?;
+            expr = Expr::Binary(Binary {
+                left: Box::from(expr),
+                operator,
+                right: Box::from(right),
+                span: self.span_from(start),
+            })
+        }
+        Ok(expr)
+    }
+
+    // The bitwise operators bind more tightly than comparisons, so that
+    // `x & 1 == 0` tests the lowest bit of x
+    fn parse_bit_or(&mut self) -> Result<Expr> {
+        let start = self.token.span;
+        let mut expr = self.parse_bit_xor()?;
+        while self.check(&TokenKind::Pipe) {
+            self.bump();
+            let right = self.parse_bit_xor()?;
+            expr = Expr::Binary(Binary {
+                left: Box::from(expr),
+                operator: TokenKind::Pipe,
+                right: Box::from(right),
+                span: self.span_from(start),
+            })
+        }
+        Ok(expr)
+    }
+
+    fn parse_bit_xor(&mut self) -> Result<Expr> {
+        let start = self.token.span;
+        let mut expr = self.parse_bit_and()?;
+        while self.check(&TokenKind::Caret) {
+            self.bump();
+            let right = self.parse_bit_and()?;
+            expr = Expr::Binary(Binary {
+                left: Box::from(expr),
+                operator: TokenKind::Caret,
+                right: Box::from(right),
+                span: self.span_from(start),
+            })
+        }
+        Ok(expr)
+    }
+
+    fn parse_bit_and(&mut self) -> Result<Expr> {
+        let start = self.token.span;
+        let mut expr = self.parse_shift()?;
+        while self.check(&TokenKind::Ampersand) {
+            self.bump();
+            let right = self.parse_shift()?;
+            expr = Expr::Binary(Binary {
+                left: Box::from(expr),
+                operator: TokenKind::Ampersand,
+                right: Box::from(right),
+                span: self.span_from(start),
+            })
+        }
+        Ok(expr)
+    }
+
+    fn parse_shift(&mut self) -> Result<Expr> {
+        let start = self.token.span;
+        let mut expr = self.parse_term()?;
+        while self.token.is_shift() {
             let operator = self.token.kind.clone();
             self.bump();
             let right = self.parse_term()?;
@@ -336,12 +851,14 @@ impl Parser {
                 left: Box::from(expr),
                 operator,
                 right: Box::from(right),
+                span: self.span_from(start),
             })
         }
         Ok(expr)
     }
 
     fn parse_term(&mut self) -> Result<Expr> {
+        let start = self.token.span;
         let mut expr = self.parse_factor()?;
         while self.token.is_term() {
             let operator = self.token.kind.clone();
@@ -351,33 +868,57 @@ impl Parser {
                 left: Box::from(expr),
                 operator,
                 right: Box::from(right),
+                span: self.span_from(start),
             })
         }
         Ok(expr)
     }
 
     fn parse_factor(&mut self) -> Result<Expr> {
-        let mut expr = self.parse_unary()?;
+        let start = self.token.span;
+        let mut expr = self.parse_power()?;
         while self.token.is_factor() {
             let operator = self.token.kind.clone();
-            let right = self.parse_unary()?;
+            self.bump();
+            let right = self.parse_power()?;
             expr = Expr::Binary(Binary {
                 left: Box::from(expr),
                 operator,
                 right: Box::from(right),
+                span: self.span_from(start),
             })
         }
         Ok(expr)
     }
 
+    fn parse_power(&mut self) -> Result<Expr> {
+        let start = self.token.span;
+        let expr = self.parse_unary()?;
+        if self.eat(&TokenKind::StarStar) {
+            // exponentiation is right-associative, so the right operand may
+            // itself be a power
+            let right = self.parse_power()?;
+            Ok(Expr::Binary(Binary {
+                left: Box::from(expr),
+                operator: TokenKind::StarStar,
+                right: Box::from(right),
+                span: self.span_from(start),
+            }))
+        } else {
+            Ok(expr)
+        }
+    }
+
     fn parse_unary(&mut self) -> Result<Expr> {
+        let start = self.token.span;
         if self.token.is_unary() {
-            self.bump();
             let operator = self.token.kind.clone();
+            self.bump();
             let right = self.parse_unary()?;
             Ok(Expr::Unary(Unary {
                 operator,
                 right: Box::from(right),
+                span: self.span_from(start),
             }))
         } else {
             self.parse_call()
@@ -385,12 +926,32 @@ impl Parser {
     }
 
     fn parse_call(&mut self) -> Result<Expr> {
+        let start = self.token.span;
         let mut expr = self.parse_primary()?;
 
         loop {
             if self.check(&TokenKind::LeftParen) {
                 self.bump();
-                expr = self.finish_call(expr)?;
+                expr = self.finish_call(expr, start)?;
+            } else if self.eat(&TokenKind::Dot) {
+                let name = self.expect_identifier()?;
+                expr = Expr::Get(Get {
+                    object: Box::new(expr),
+                    name,
+                    span: self.span_from(start),
+                });
+            } else if self.eat(&TokenKind::LeftBracket) {
+                let location = self.prev_token.location();
+                let index = self.parse_expression()?;
+                self.expect(
+                    &TokenKind::RightBracket,
+                    format!("Expected ']' to match '[' on line {}", location),
+                )?;
+                expr = Expr::Index(Index {
+                    object: Box::new(expr),
+                    index: Box::new(index),
+                    span: self.span_from(start),
+                });
             } else {
                 break;
             }
@@ -399,7 +960,7 @@ impl Parser {
         Ok(expr)
     }
 
-    fn finish_call(&mut self, callee: Expr) -> Result<Expr> {
+    fn finish_call(&mut self, callee: Expr, start: Span) -> Result<Expr> {
         let mut arguments = vec![];
         if !self.check(&TokenKind::RightParen) {
             loop {
@@ -421,33 +982,149 @@ impl Parser {
         Ok(Expr::Call(Call {
             callee: Box::new(callee),
             arguments,
+            span: self.span_from(start),
         }))
     }
 
     fn parse_primary(&mut self) -> Result<Expr> {
+        let literal = |value| {
+            Ok(Expr::Literal(LiteralExpr {
+                value,
+                span: self.token.span,
+            }))
+        };
         let expr = match &self.token.kind {
-            TokenKind::False => Ok(Expr::Literal(Literal::Bool(false))),
-            TokenKind::True => Ok(Expr::Literal(Literal::Bool(true))),
-            TokenKind::Nil => Ok(Expr::Literal(Literal::Nil)),
-            TokenKind::Number(value) => Ok(Expr::Literal(Literal::Number(*value))),
-            TokenKind::String(value) => Ok(Expr::Literal(Literal::String(value.clone()))),
+            TokenKind::False => literal(Literal::Bool(false)),
+            TokenKind::True => literal(Literal::Bool(true)),
+            TokenKind::Nil => literal(Literal::Nil),
+            TokenKind::Number(value) => literal(Literal::Number(*value)),
+            TokenKind::String(value) => literal(Literal::String(value.clone())),
+            TokenKind::Interpolation(parts) => {
+                let mut string_parts = vec![];
+                for part in parts {
+                    string_parts.push(match part {
+                        InterpolationPart::Text(text) => StringPart::Text(text.clone()),
+                        InterpolationPart::Tokens(tokens) => {
+                            StringPart::Expr(parse_interpolated(tokens.clone())?)
+                        }
+                    });
+                }
+                Ok(Expr::Interpolation(Interpolation {
+                    parts: string_parts,
+                    span: self.token.span,
+                }))
+            }
             TokenKind::LeftParen => {
-                let line = self.token.line;
+                let start = self.token.span;
+                let location = self.token.location();
+                self.bump();
                 let expr = self.parse_expression()?;
                 self.expect(
                     &TokenKind::RightParen,
-                    format!("Expected ')' to match '(' on line {}", line),
+                    format!("Expected ')' to match '(' on line {}", location),
                 )?;
-                Ok(Expr::Grouping(Grouping {
+                // the closing paren has already been consumed
+                return Ok(Expr::Grouping(Grouping {
                     expression: Box::from(expr),
+                    span: self.span_from(start),
+                }));
+            }
+            TokenKind::LeftBracket => {
+                let start = self.token.span;
+                let location = self.token.location();
+                self.bump();
+                let mut elements = vec![];
+                if !self.check(&TokenKind::RightBracket) {
+                    loop {
+                        elements.push(self.parse_expression()?);
+                        if !self.eat(&TokenKind::Comma) {
+                            break;
+                        }
+                    }
+                }
+                self.expect(
+                    &TokenKind::RightBracket,
+                    format!("Expected ']' to match '[' on line {}", location),
+                )?;
+                // the closing bracket has already been consumed
+                return Ok(Expr::ListLiteral(ListLiteral {
+                    elements,
+                    span: self.span_from(start),
+                }));
+            }
+            // a brace only starts a map where an expression is expected, since
+            // at the start of a statement it starts a block instead
+            TokenKind::LeftBrace => {
+                let start = self.token.span;
+                let location = self.token.location();
+                self.bump();
+                let mut entries = vec![];
+                if !self.check(&TokenKind::RightBrace) {
+                    loop {
+                        let key = self.parse_expression()?;
+                        self.expect(
+                            &TokenKind::Colon,
+                            format!(
+                                "Expected ':' after map key on line {}",
+                                self.token.location()
+                            ),
+                        )?;
+                        let value = self.parse_expression()?;
+                        entries.push((key, value));
+                        if !self.eat(&TokenKind::Comma) {
+                            break;
+                        }
+                    }
+                }
+                self.expect(
+                    &TokenKind::RightBrace,
+                    format!("Expected '}}' to match '{{' on line {}", location),
+                )?;
+                // the closing brace has already been consumed
+                return Ok(Expr::MapLiteral(MapLiteral {
+                    entries,
+                    span: self.span_from(start),
+                }));
+            }
+            TokenKind::Super => {
+                let start = self.token.span;
+                let location = self.token.location();
+                self.bump();
+                self.expect(
+                    &TokenKind::Dot,
+                    format!("Expected '.' after 'super' on line {}", location),
+                )?;
+                let method = match &self.token.kind {
+                    TokenKind::Identifier(method) => *method,
+                    _ => {
+                        return Err(anyhow!(
+                            "Expected superclass method name on line {}",
+                            location
+                        ))
+                    }
+                };
+                Ok(Expr::Super(Super {
+                    method,
+                    span: start.to(self.token.span),
                 }))
             }
-            TokenKind::Identifier(name) => Ok(Expr::Variable(Variable { name: name.clone() })),
-            _ => Err(anyhow!(
-                "Expected an expression, found token {} on line {}",
-                self.token.kind,
-                self.token.line
-            )),
+            TokenKind::This => Ok(Expr::This(This {
+                span: self.token.span,
+            })),
+            TokenKind::Identifier(name) => Ok(Expr::Variable(Variable {
+                name: *name,
+                depth: Cell::new(None),
+                span: self.token.span,
+            })),
+            // Leave the offending token in place so that error recovery
+            // starts from it
+            _ => {
+                return Err(anyhow!(
+                    "Expected an expression, found token {} on line {}",
+                    self.token.kind,
+                    self.token.location()
+                ))
+            }
         };
         self.bump();
         expr
@@ -455,7 +1132,7 @@ impl Parser {
 
     /// Expects and consumes the token `token`. Signals an error if the next
     /// token is not `token`.
-    pub fn expect(&mut self, token: &TokenKind, message: String) -> Result<()> {
+    fn expect(&mut self, token: &TokenKind, message: String) -> Result<()> {
         if self.token.kind == *token {
             self.bump();
             Ok(())
@@ -466,14 +1143,14 @@ impl Parser {
 
     /// Expects and consumes the token `token` if it is an identifier, and
     /// signals an error otherwise.
-    pub fn expect_identifier(&mut self) -> Result<String> {
+    fn expect_identifier(&mut self) -> Result<Symbol> {
         let value = match &self.token.kind {
-            TokenKind::Identifier(value) => Ok(value.clone()),
+            TokenKind::Identifier(value) => Ok(*value),
             _ => {
                 return Err(anyhow!(
                     "Expected an identifier, found {:?} on line {}",
                     self.token.kind,
-                    self.token.line
+                    self.token.location()
                 ))
             }
         };
@@ -481,17 +1158,49 @@ impl Parser {
         value
     }
 
+    /// Discards tokens until reaching what is likely the start of the next
+    /// statement, so that parsing can resume after an error.
+    fn synchronize(&mut self) {
+        self.bump();
+        while !self.check(&TokenKind::Eof) {
+            if self.prev_token.kind == TokenKind::Semicolon {
+                return;
+            }
+            match self.token.kind {
+                TokenKind::Class
+                | TokenKind::Do
+                | TokenKind::Fun
+                | TokenKind::Var
+                | TokenKind::For
+                | TokenKind::If
+                | TokenKind::While
+                | TokenKind::Print
+                | TokenKind::Return
+                | TokenKind::Switch
+                | TokenKind::Throw
+                | TokenKind::Try => return,
+                _ => self.bump(),
+            }
+        }
+    }
+
     /// Consumes one token (moves the cursor forward by one).
     fn bump(&mut self) {
-        let line = self.token.line;
+        let span = self.token.span;
         self.prev_token = std::mem::replace(
             &mut self.token,
             self.cursor
                 .next()
-                .unwrap_or_else(|| Token::new(TokenKind::Eof, line)),
+                .unwrap_or_else(|| Token::new(TokenKind::Eof, span)),
         );
     }
 
+    /// Returns the span from the start of `start` to the end of the last
+    /// consumed token.
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.prev_token.span)
+    }
+
     /// Checks if the next token is `tok`, and returns `true` if so.
     fn check(&mut self, tok: &TokenKind) -> bool {
         self.token.kind == *tok
@@ -508,23 +1217,398 @@ impl Parser {
     }
 }
 
+/// Parses the tokens of an expression embedded in a string with `${...}`.
+fn parse_interpolated(tokens: Vec<Token>) -> Result<Expr> {
+    let mut parser = Parser::new(tokens);
+    let expr = parser.parse_expression()?;
+    if !parser.check(&TokenKind::Eof) {
+        return Err(anyhow!(
+            "Expected '}}' after interpolated expression, found token {} on line {}",
+            parser.token.kind,
+            parser.token.location()
+        ));
+    }
+    Ok(expr)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{ast_printer::AstPrinter, scanner::Scanner};
 
     #[test]
     fn parse_print_stmt() {
         let tokens = vec![
-            Token::new(TokenKind::Print, 1),
-            Token::new(TokenKind::String("one".into()), 1),
-            Token::new(TokenKind::Semicolon, 1),
-            Token::new(TokenKind::Eof, 2),
+            Token::new(TokenKind::Print, Span::new(1, 1, 1, 6)),
+            Token::new(TokenKind::String("one".into()), Span::new(1, 7, 1, 12)),
+            Token::new(TokenKind::Semicolon, Span::new(1, 12, 1, 13)),
+            Token::new(TokenKind::Eof, Span::new(2, 1, 2, 1)),
         ];
         let mut parser = Parser::new(tokens);
         let result = parser.parse().unwrap();
         let expected = vec![Stmt::Print(Print {
-            expression: Expr::Literal(Literal::String("one".into())),
+            expressions: vec![Expr::Literal(LiteralExpr {
+                value: Literal::String("one".into()),
+                span: Span::new(1, 7, 1, 12),
+            })],
+            span: Span::new(1, 1, 1, 13),
         })];
         assert_eq!(result, expected)
     }
+
+    #[test]
+    fn parse_no_tokens() {
+        let mut parser = Parser::new(vec![]);
+        assert_eq!(parser.parse().unwrap(), vec![]);
+        assert!(Parser::new(vec![]).parse_standalone_expression().is_err());
+    }
+
+    #[test]
+    fn parse_list_pattern_params() {
+        let tokens = Scanner::new("fun dist([x1, y1], [x2, y2]) {}")
+            .scan_tokens()
+            .unwrap();
+        let mut parser = Parser::new(tokens);
+        let result = parser.parse().unwrap();
+        let expected = vec![Stmt::Function(Function {
+            name: "dist".into(),
+            params: vec![
+                Param::List(vec![Param::Name("x1".into()), Param::Name("y1".into())]),
+                Param::List(vec![Param::Name("x2".into()), Param::Name("y2".into())]),
+            ],
+            body: Rc::new(vec![]),
+            span: Span::new(1, 1, 1, 32),
+        })];
+        assert_eq!(result, expected)
+    }
+
+    #[test]
+    fn parse_list_literal_and_index_assignment() {
+        let tokens = Scanner::new("[1, 2][0] = 3;").scan_tokens().unwrap();
+        let result = Parser::new(tokens).parse().unwrap();
+        let number = |value: f64, span| {
+            Box::new(Expr::Literal(LiteralExpr {
+                value: Literal::Number(value),
+                span,
+            }))
+        };
+        let expected = vec![Stmt::Expression(Expression {
+            expression: Expr::SetIndex(SetIndex {
+                object: Box::new(Expr::ListLiteral(ListLiteral {
+                    elements: vec![
+                        *number(1.0, Span::new(1, 2, 1, 3)),
+                        *number(2.0, Span::new(1, 5, 1, 6)),
+                    ],
+                    span: Span::new(1, 1, 1, 7),
+                })),
+                index: number(0.0, Span::new(1, 8, 1, 9)),
+                operator: None,
+                value: number(3.0, Span::new(1, 13, 1, 14)),
+                span: Span::new(1, 1, 1, 14),
+            }),
+            span: Span::new(1, 1, 1, 15),
+        })];
+        assert_eq!(result, expected);
+
+        let tokens = Scanner::new("print [1, 2;").scan_tokens().unwrap();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Expected ']' to match '[' on line 1:7"
+        );
+    }
+
+    #[test]
+    fn parse_map_literal() {
+        let tokens = Scanner::new(r#"print {"a": 1};"#).scan_tokens().unwrap();
+        let result = Parser::new(tokens).parse().unwrap();
+        let expected = vec![Stmt::Print(Print {
+            expressions: vec![Expr::MapLiteral(MapLiteral {
+                entries: vec![(
+                    Expr::Literal(LiteralExpr {
+                        value: Literal::String("a".into()),
+                        span: Span::new(1, 8, 1, 11),
+                    }),
+                    Expr::Literal(LiteralExpr {
+                        value: Literal::Number(1.0),
+                        span: Span::new(1, 13, 1, 14),
+                    }),
+                )],
+                span: Span::new(1, 7, 1, 15),
+            })],
+            span: Span::new(1, 1, 1, 16),
+        })];
+        assert_eq!(result, expected);
+
+        let tokens = Scanner::new(r#"print {"a" 1};"#).scan_tokens().unwrap();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Expected ':' after map key on line 1:12"
+        );
+    }
+
+    #[test]
+    fn parse_switch_errors() {
+        let tokens = Scanner::new("switch (1) { default: default: }")
+            .scan_tokens()
+            .unwrap();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Expected at most one 'default' case in the switch on line 1:1."
+        );
+
+        let tokens = Scanner::new("switch (1) { print 1; }")
+            .scan_tokens()
+            .unwrap();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Expected 'case' or 'default', found token print on line 1:14"
+        );
+    }
+
+    #[test]
+    fn parse_try_statement() {
+        let tokens = Scanner::new("try { throw 1; } catch (e) {}")
+            .scan_tokens()
+            .unwrap();
+        let result = Parser::new(tokens).parse().unwrap();
+        let expected = vec![Stmt::Try(Try {
+            body: vec![Stmt::Throw(Throw {
+                value: Expr::Literal(LiteralExpr {
+                    value: Literal::Number(1.0),
+                    span: Span::new(1, 13, 1, 14),
+                }),
+                span: Span::new(1, 7, 1, 15),
+            })],
+            catch_name: "e".into(),
+            catch_body: vec![],
+            span: Span::new(1, 1, 1, 30),
+        })];
+        assert_eq!(result, expected);
+
+        let tokens = Scanner::new("try { throw 1; }\nprint 2;")
+            .scan_tokens()
+            .unwrap();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Expected 'catch' after the 'try' block from line 1:1."
+        );
+    }
+
+    #[test]
+    fn parse_logical_with_comparison_operand() {
+        let tokens = Scanner::new("false or 1 < 2;").scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let result = parser.parse().unwrap();
+        let expected = vec![Stmt::Expression(Expression {
+            expression: Expr::Logical(Logical {
+                left: Box::new(Expr::Literal(LiteralExpr {
+                    value: Literal::Bool(false),
+                    span: Span::new(1, 1, 1, 6),
+                })),
+                operator: TokenKind::Or,
+                right: Box::new(Expr::Binary(Binary {
+                    left: Box::new(Expr::Literal(LiteralExpr {
+                        value: Literal::Number(1.0),
+                        span: Span::new(1, 10, 1, 11),
+                    })),
+                    operator: TokenKind::Less,
+                    right: Box::new(Expr::Literal(LiteralExpr {
+                        value: Literal::Number(2.0),
+                        span: Span::new(1, 14, 1, 15),
+                    })),
+                    span: Span::new(1, 10, 1, 15),
+                })),
+                span: Span::new(1, 1, 1, 15),
+            }),
+            span: Span::new(1, 1, 1, 16),
+        })];
+        assert_eq!(result, expected)
+    }
+
+    #[test]
+    fn parse_and_binds_tighter_than_or() {
+        let tokens = Scanner::new("a or b and c == d;").scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let result = parser.parse().unwrap();
+        let variable = |name: &str, col: u32| {
+            Box::new(Expr::Variable(Variable {
+                name: name.into(),
+                depth: Cell::new(None),
+                span: Span::new(1, col, 1, col + 1),
+            }))
+        };
+        let expected = vec![Stmt::Expression(Expression {
+            expression: Expr::Logical(Logical {
+                left: variable("a", 1),
+                operator: TokenKind::Or,
+                right: Box::new(Expr::Logical(Logical {
+                    left: variable("b", 6),
+                    operator: TokenKind::And,
+                    right: Box::new(Expr::Binary(Binary {
+                        left: variable("c", 12),
+                        operator: TokenKind::EqualEqual,
+                        right: variable("d", 17),
+                        span: Span::new(1, 12, 1, 18),
+                    })),
+                    span: Span::new(1, 6, 1, 18),
+                })),
+                span: Span::new(1, 1, 1, 18),
+            }),
+            span: Span::new(1, 1, 1, 19),
+        })];
+        assert_eq!(result, expected)
+    }
+
+    #[test]
+    fn parse_errors_report_columns() {
+        let tokens = Scanner::new("print 1;\nprint 1 2;").scan_tokens().unwrap();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Expected ';' after value on line 2:7"
+        );
+    }
+
+    #[test]
+    fn parse_spans_across_lines() {
+        let tokens = Scanner::new("var x = foo(1,\n  2);").scan_tokens().unwrap();
+        let result = Parser::new(tokens).parse().unwrap();
+        assert_eq!(result[0].span(), Span::new(1, 1, 2, 6));
+        match &result[0] {
+            Stmt::Var(Var {
+                initializer: Some(call),
+                ..
+            }) => assert_eq!(call.span(), Span::new(1, 9, 2, 5)),
+            stmt => panic!("Expected a variable declaration, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn parse_reports_every_error() {
+        let source = "var = 1;\nprint 1;\nprint ;\nvar y = 2 3\nfun f() {}";
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|err| err.to_string())
+                .collect::<Vec<String>>(),
+            [
+                "Expected an identifier, found Equal on line 1:5",
+                "Expected an expression, found token ; on line 3:7",
+                "Expected ';' after variable declaration on line 4:1",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_power_is_right_associative() {
+        let tokens = Scanner::new("2 ** 3 ** 2;").scan_tokens().unwrap();
+        let result = Parser::new(tokens).parse().unwrap();
+        let number = |value: f64, span| {
+            Box::new(Expr::Literal(LiteralExpr {
+                value: Literal::Number(value),
+                span,
+            }))
+        };
+        let expected = vec![Stmt::Expression(Expression {
+            expression: Expr::Binary(Binary {
+                left: number(2.0, Span::new(1, 1, 1, 2)),
+                operator: TokenKind::StarStar,
+                right: Box::new(Expr::Binary(Binary {
+                    left: number(3.0, Span::new(1, 6, 1, 7)),
+                    operator: TokenKind::StarStar,
+                    right: number(2.0, Span::new(1, 11, 1, 12)),
+                    span: Span::new(1, 6, 1, 12),
+                })),
+                span: Span::new(1, 1, 1, 12),
+            }),
+            span: Span::new(1, 1, 1, 13),
+        })];
+        assert_eq!(result, expected)
+    }
+
+    #[test]
+    fn parse_ternary_is_right_associative() {
+        let tokens = Scanner::new("a ? 1 : b ? 2 : 3;").scan_tokens().unwrap();
+        let result = Parser::new(tokens).parse().unwrap();
+        let number = |value: f64, span| {
+            Box::new(Expr::Literal(LiteralExpr {
+                value: Literal::Number(value),
+                span,
+            }))
+        };
+        let variable = |name: &str, span| {
+            Box::new(Expr::Variable(Variable {
+                name: name.into(),
+                depth: Cell::new(None),
+                span,
+            }))
+        };
+        let expected = vec![Stmt::Expression(Expression {
+            expression: Expr::Ternary(Ternary {
+                condition: variable("a", Span::new(1, 1, 1, 2)),
+                then_expr: number(1.0, Span::new(1, 5, 1, 6)),
+                else_expr: Box::new(Expr::Ternary(Ternary {
+                    condition: variable("b", Span::new(1, 9, 1, 10)),
+                    then_expr: number(2.0, Span::new(1, 13, 1, 14)),
+                    else_expr: number(3.0, Span::new(1, 17, 1, 18)),
+                    span: Span::new(1, 9, 1, 18),
+                })),
+                span: Span::new(1, 1, 1, 18),
+            }),
+            span: Span::new(1, 1, 1, 19),
+        })];
+        assert_eq!(result, expected)
+    }
+
+    #[test]
+    fn parse_compound_assignment() {
+        let tokens = Scanner::new("x += 2;").scan_tokens().unwrap();
+        let result = Parser::new(tokens).parse().unwrap();
+        let expected = vec![Stmt::Expression(Expression {
+            expression: Expr::Assign(Assign {
+                name: "x".into(),
+                operator: Some(TokenKind::Plus),
+                value: Box::new(Expr::Literal(LiteralExpr {
+                    value: Literal::Number(2.0),
+                    span: Span::new(1, 6, 1, 7),
+                })),
+                depth: Cell::new(None),
+                span: Span::new(1, 1, 1, 7),
+            }),
+            span: Span::new(1, 1, 1, 8),
+        })];
+        assert_eq!(result, expected);
+
+        // the object and index of other targets are only evaluated once
+        let tokens = Scanner::new("a[i()] -= 1;").scan_tokens().unwrap();
+        let result = Parser::new(tokens).parse().unwrap();
+        assert_eq!(
+            AstPrinter.print_program(&result),
+            "(; (-= (index a (call i)) 1))\n"
+        );
+
+        let tokens = Scanner::new("1 += 2;").scan_tokens().unwrap();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Invalid assignment target on line 1:6"
+        );
+    }
+
+    #[test]
+    fn parse_ternary_without_colon() {
+        let tokens = Scanner::new("a ? 1;").scan_tokens().unwrap();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Expected ':' to match '?' on line 1:3"
+        );
+    }
 }
@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::Result;
+
+use crate::{
+    error::LoxError,
+    expr::{Assign, Variable},
+    span::Span,
+    stmt::{Block, Class, For, ForIn, Function, Param, Return, Stmt, Switch, Try, Var},
+    symbol::Symbol,
+    visitor::{self, Visit},
+};
+
+/// Resolves every variable reference in the program to the scope that
+/// declares it, recording on each `Variable` and `Assign` node how many
+/// scopes out from the reference that is. References that are not declared
+/// in any local scope are left unresolved, and are looked up as globals.
+///
+/// The scopes tracked here must mirror the environments the interpreter
+/// creates: one per block or `for` loop, one holding a function's
+/// parameters and body, and for methods, one binding `this` (plus one
+/// binding `super` in a subclass).
+///
+/// Mistakes that can be found without running the program, such as a
+/// `return` outside of any function, are reported as errors. Likely
+/// mistakes that don't stop the program from running, such as local
+/// variables that are never read, are returned as warnings, in the order
+/// they appear in the source.
+pub fn resolve(stmts: &[Stmt]) -> Result<Vec<Warning>> {
+    let mut resolver = Resolver::default();
+    for stmt in stmts {
+        resolver.visit_stmt(stmt);
+    }
+    match LoxError::combine(resolver.errors) {
+        Some(err) => Err(err.into()),
+        None => {
+            let mut warnings = resolver.warnings;
+            warnings.sort_by_key(|warning| (warning.span.start_line, warning.span.start_col));
+            Ok(warnings)
+        }
+    }
+}
+
+/// A likely mistake in a program that doesn't stop it from running.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// What the resolver knows about a local variable.
+struct Local {
+    // false while the variable's own initializer is being resolved
+    ready: bool,
+    read: bool,
+    // where a `var` declared the variable; other kinds of locals (such as
+    // parameters) are never warned about, so they have no span
+    declared_at: Option<Span>,
+}
+
+impl Local {
+    fn new() -> Self {
+        Local {
+            ready: true,
+            read: false,
+            declared_at: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Resolver {
+    // the names declared in each local scope, innermost last
+    scopes: Vec<HashMap<Symbol, Local>>,
+    // how many function bodies the code being resolved is nested inside
+    function_depth: usize,
+    errors: Vec<LoxError>,
+    warnings: Vec<Warning>,
+}
+
+impl Resolver {
+    fn error(&mut self, message: String, span: Span) {
+        self.errors.push(LoxError::Parse { message, span });
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            for (name, local) in scope {
+                self.check_read(name, &local);
+            }
+        }
+    }
+
+    /// Warns if `local` was declared by a `var` but never read. Names
+    /// starting with an underscore are exempt, to mark that this is
+    /// intended.
+    fn check_read(&mut self, name: Symbol, local: &Local) {
+        if let Some(span) = local.declared_at {
+            if !local.read && !name.as_str().starts_with('_') {
+                self.warnings.push(Warning {
+                    message: format!("Local variable {} is never read on line {}.", name, span),
+                    span,
+                });
+            }
+        }
+    }
+
+    fn declare(&mut self, name: Symbol) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, Local::new());
+        }
+    }
+
+    fn declare_param(&mut self, param: &Param) {
+        match param {
+            Param::Name(name) => self.declare(*name),
+            Param::List(params) => {
+                for param in params {
+                    self.declare_param(param);
+                }
+            }
+        }
+    }
+
+    fn resolve_local(&self, name: Symbol) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name))
+    }
+
+    /// Resolves a read of the variable `name`, marking it as read, and
+    /// returns its depth.
+    fn read(&mut self, name: Symbol, span: Span) -> Option<usize> {
+        if let Some(Local { ready: false, .. }) =
+            self.scopes.last().and_then(|scope| scope.get(&name))
+        {
+            self.error(
+                format!(
+                    "Can't read local variable {} in its own initializer on line {}.",
+                    name, span
+                ),
+                span,
+            );
+        }
+        let depth = self.resolve_local(name);
+        if let Some(depth) = depth {
+            let scope = self.scopes.len() - 1 - depth;
+            if let Some(local) = self.scopes[scope].get_mut(&name) {
+                local.read = true;
+            }
+        }
+        depth
+    }
+
+    fn resolve_block(&mut self, stmts: &[Stmt]) {
+        self.begin_scope();
+        for stmt in stmts {
+            self.visit_stmt(stmt);
+        }
+        self.end_scope();
+    }
+
+    fn resolve_function(&mut self, function: &Function) {
+        self.begin_scope();
+        self.function_depth += 1;
+        for param in &function.params {
+            self.declare_param(param);
+        }
+        for stmt in function.body.iter() {
+            self.visit_stmt(stmt);
+        }
+        self.function_depth -= 1;
+        self.end_scope();
+    }
+}
+
+impl<'ast> Visit<'ast> for Resolver {
+    fn visit_stmt_block(&mut self, s: &'ast Block) {
+        self.begin_scope();
+        visitor::visit_stmt_block(self, s);
+        self.end_scope();
+    }
+
+    fn visit_stmt_class(&mut self, s: &'ast Class) {
+        if let Some(superclass) = &s.superclass {
+            self.visit_expr_variable(superclass);
+        }
+        self.declare(s.name);
+
+        if s.superclass.is_some() {
+            self.begin_scope();
+            self.declare(Symbol::SUPER);
+        }
+        self.begin_scope();
+        self.declare(Symbol::THIS);
+        for method in &s.methods {
+            self.resolve_function(method);
+        }
+        self.end_scope();
+        if s.superclass.is_some() {
+            self.end_scope();
+        }
+    }
+
+    fn visit_stmt_for(&mut self, s: &'ast For) {
+        // the initializer declares its variable in a scope around the loop
+        self.begin_scope();
+        visitor::visit_stmt_for(self, s);
+        self.end_scope();
+    }
+
+    fn visit_stmt_for_in(&mut self, s: &'ast ForIn) {
+        self.visit_expr(&s.iterable);
+        // each iteration binds the loop variable in a scope of its own
+        self.begin_scope();
+        self.declare(s.name);
+        self.visit_stmt(&s.body);
+        self.end_scope();
+    }
+
+    fn visit_stmt_function(&mut self, s: &'ast Function) {
+        // the name is bound before the body runs so that recursion works
+        self.declare(s.name);
+        self.resolve_function(s);
+    }
+
+    fn visit_stmt_return(&mut self, s: &'ast Return) {
+        if self.function_depth == 0 {
+            self.error(
+                format!("Can't return from top-level code on line {}.", s.span),
+                s.span,
+            );
+        }
+        visitor::visit_stmt_return(self, s);
+    }
+
+    fn visit_stmt_switch(&mut self, s: &'ast Switch) {
+        self.visit_expr(&s.subject);
+        // each case's statements run in a scope of their own
+        for (value, body) in &s.cases {
+            self.visit_expr(value);
+            self.resolve_block(body);
+        }
+        if let Some(default) = &s.default {
+            self.resolve_block(default);
+        }
+    }
+
+    fn visit_stmt_try(&mut self, s: &'ast Try) {
+        self.resolve_block(&s.body);
+
+        self.begin_scope();
+        self.declare(s.catch_name);
+        for stmt in &s.catch_body {
+            self.visit_stmt(stmt);
+        }
+        self.end_scope();
+    }
+
+    fn visit_stmt_var(&mut self, s: &'ast Var) {
+        // mark a new local as not ready yet, so that its initializer can't
+        // refer to it (redeclaring an existing local is fine, though)
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.entry(s.name).or_insert(Local {
+                ready: false,
+                ..Local::new()
+            });
+        }
+        visitor::visit_stmt_var(self, s);
+        if let Some(scope) = self.scopes.last_mut() {
+            let local = Local {
+                declared_at: Some(s.span),
+                ..Local::new()
+            };
+            if let Some(previous) = scope.insert(s.name, local) {
+                self.check_read(s.name, &previous);
+            }
+        }
+    }
+
+    fn visit_expr_assign(&mut self, e: &'ast Assign) {
+        // a compound assignment such as `name += value` reads the variable
+        // before the value is evaluated
+        if e.operator.is_some() {
+            self.read(e.name, e.span);
+        }
+        visitor::visit_expr_assign(self, e);
+        e.depth.set(self.resolve_local(e.name));
+    }
+
+    fn visit_expr_variable(&mut self, e: &'ast Variable) {
+        e.depth.set(self.read(e.name, e.span));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{expr::Expr, parser::Parser, scanner::Scanner, stmt::Print};
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    // Returns the resolved depth of every variable that is printed, in order.
+    fn printed_depths(stmts: &[Stmt]) -> Vec<Option<usize>> {
+        #[derive(Default)]
+        struct PrintedDepths(Vec<Option<usize>>);
+
+        impl<'ast> Visit<'ast> for PrintedDepths {
+            fn visit_stmt_print(&mut self, s: &'ast Print) {
+                for expression in &s.expressions {
+                    if let Expr::Variable(variable) = expression {
+                        self.0.push(variable.depth.get());
+                    }
+                }
+            }
+        }
+
+        let mut visitor = PrintedDepths::default();
+        for stmt in stmts {
+            visitor.visit_stmt(stmt);
+        }
+        visitor.0
+    }
+
+    #[test]
+    fn resolves_scope_depths() {
+        let stmts = parse(
+            "var a = 1;
+            {
+                var b = 2;
+                fun f(c) {
+                    { print c; print b; print a; }
+                }
+            }",
+        );
+        resolve(&stmts).unwrap();
+        assert_eq!(printed_depths(&stmts), [Some(1), Some(2), None]);
+    }
+
+    #[test]
+    fn closures_see_the_binding_in_effect_at_definition() {
+        let stmts = parse(
+            "var a = 1;
+            {
+                fun show() { print a; }
+                var a = 2;
+            }",
+        );
+        resolve(&stmts).unwrap();
+        assert_eq!(printed_depths(&stmts), [None]);
+    }
+
+    #[test]
+    fn variable_in_own_initializer() {
+        // without the error, the inner `a` would silently be initialized
+        // from the outer one
+        let err = resolve(&parse("var a = \"outer\";\n{\n  var a = a;\n}")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Can't read local variable a in its own initializer on line 3:11."
+        );
+        let err = resolve(&parse("fun f() { var x = [1, x]; }")).unwrap_err();
+        assert_eq!(err.downcast::<LoxError>().unwrap().line(), 1);
+
+        // globals may refer to their previous value, and locals may be
+        // redeclared in terms of their old value
+        resolve(&parse(
+            "var a = 1; var a = a + 1; { var b = 1; var b = b + 1; }",
+        ))
+        .unwrap();
+    }
+
+    fn unused(source: &str) -> Vec<String> {
+        let warnings = resolve(&parse(source)).unwrap();
+        warnings.iter().map(|warning| warning.to_string()).collect()
+    }
+
+    #[test]
+    fn warns_about_unused_locals() {
+        assert_eq!(
+            unused(
+                "var global = 1;
+                fun f(param) {
+                    var used = 1;
+                    var unused = 2;
+                    var _ignored = 3;
+                    { var assigned = 4; assigned = 5; }
+                    return used;
+                }"
+            ),
+            [
+                "Local variable unused is never read on line 4:21.",
+                "Local variable assigned is never read on line 6:23."
+            ]
+        );
+        // reading a variable from a closure or a redeclaration counts
+        assert!(unused(
+            "{ var a = 1; fun f() { print a; } f(); var b = 1; var b = b + 1; print b; }"
+        )
+        .is_empty());
+        assert_eq!(
+            unused("{ var c = 1; var c = 2; print c; }"),
+            ["Local variable c is never read on line 1:3."]
+        );
+    }
+
+    #[test]
+    fn return_outside_function() {
+        let err = resolve(&parse("print 1;\n{ return 1; }")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Can't return from top-level code on line 2:3."
+        );
+        let err = err.downcast::<LoxError>().unwrap();
+        assert!(matches!(err, LoxError::Parse { .. }));
+        assert_eq!(err.line(), 2);
+
+        resolve(&parse(
+            "fun f() { return 1; } class A { m() { return this; } }",
+        ))
+        .unwrap();
+    }
+}
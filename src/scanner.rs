@@ -1,114 +1,329 @@
+use std::cell::Cell;
+use std::fmt;
+use std::ops::Range;
 use std::str::CharIndices;
 
 use anyhow::Result;
 use anyhow::{anyhow, Context};
 use itertools::{Itertools, MultiPeek};
 
-use crate::token::{Token, TokenKind};
+use crate::error::LoxError;
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::token::{InterpolationPart, Token, TokenKind};
 
 // TODO: refactor scanner logic to use the "Cursor" class?
 
 type CharIter<'a> = MultiPeek<CharIndices<'a>>;
+type SpannedToken = (Token, Range<usize>);
+
+/// A lexical error, along with the span of the text that caused it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ScanError {}
 
 pub struct Scanner<'a> {
     source: &'a str,
+    // the byte offset and column of the last position `column` found, so
+    // that finding the next one only counts the characters in between
+    last_column: Cell<(usize, u32)>,
 }
 
 impl<'a> Scanner<'a> {
     pub fn new(source: &'a str) -> Self {
-        Scanner { source }
+        Scanner {
+            source,
+            last_column: Cell::new((0, 1)),
+        }
     }
 
+    /// Scans the source into tokens, stopping with the first lexical error
+    /// if there are any.
     pub fn scan_tokens(&self) -> Result<Vec<Token>> {
+        let (tokens, errors) = self.scan_all();
+        match errors.into_iter().next() {
+            Some(err) => Err(LoxError::from(err).into()),
+            None => Ok(tokens),
+        }
+    }
+
+    /// Scans the source into tokens, skipping past any lexical errors so that
+    /// all of them can be reported at once.
+    pub fn scan_all(&self) -> (Vec<Token>, Vec<ScanError>) {
+        let (spanned, errors, line) = self.scan_spanned(false);
+        let mut tokens: Vec<Token> = spanned.into_iter().map(|(token, _)| token).collect();
+
+        let column = self.column(self.source.len());
+        tokens.push(Token::new(
+            TokenKind::Eof,
+            Span::new(line, column, line, column),
+        ));
+
+        (tokens, errors)
+    }
+
+    /// Scans the source into the kind of each token along with the range of
+    /// bytes it spans, which is useful for syntax highlighting. Comments are
+    /// included as `TokenKind::Comment` tokens if `keep_comments` is set.
+    pub fn highlight(&self, keep_comments: bool) -> Result<Vec<(TokenKind, Range<usize>)>> {
+        let (spanned, errors, _) = self.scan_spanned(keep_comments);
+        if let Some(err) = errors.into_iter().next() {
+            return Err(err.into());
+        }
+        Ok(spanned
+            .into_iter()
+            .map(|(token, span)| (token.kind, span))
+            .collect())
+    }
+
+    /// Scans the source for its comments, including those in expressions
+    /// embedded in strings, as `TokenKind::Comment` tokens in the order they
+    /// appear.
+    pub fn comments(&self) -> Vec<Token> {
+        fn collect(tokens: Vec<Token>, comments: &mut Vec<Token>) {
+            for token in tokens {
+                match token.kind {
+                    TokenKind::Comment(_) => comments.push(token),
+                    TokenKind::Interpolation(parts) => {
+                        for part in parts {
+                            if let InterpolationPart::Tokens(tokens) = part {
+                                collect(tokens, comments);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let (spanned, _, _) = self.scan_spanned(true);
+        let mut comments = vec![];
+        collect(
+            spanned.into_iter().map(|(token, _)| token).collect(),
+            &mut comments,
+        );
+        comments
+    }
+
+    /// Scans all of the tokens in the source along with their byte ranges,
+    /// returning them, any errors encountered, and the final line number.
+    fn scan_spanned(&self, keep_comments: bool) -> (Vec<SpannedToken>, Vec<ScanError>, u32) {
         let mut iter = self.source.char_indices().multipeek();
-        let mut tokens: Vec<Token> = vec![];
+        let mut tokens = vec![];
+        let mut errors = vec![];
         let mut line: u32 = 1;
 
-        while let Some(token) = self.scan_token(&mut iter, &mut line)? {
-            tokens.push(token);
+        loop {
+            match self.scan_token(&mut iter, &mut line, keep_comments) {
+                Ok(Some(token)) => tokens.push(token),
+                Ok(None) => break,
+                // The erroneous text has been consumed, so carry on from
+                // just after it
+                Err(err) => errors.push(err),
+            }
         }
 
-        tokens.push(Token::new(TokenKind::Eof, line));
-
-        Ok(tokens)
+        (tokens, errors, line)
     }
 
-    fn scan_token(&self, iter: &mut CharIter, line: &mut u32) -> Result<Option<Token>> {
+    fn scan_token(
+        &self,
+        iter: &mut CharIter,
+        line: &mut u32,
+        keep_comments: bool,
+    ) -> std::result::Result<Option<SpannedToken>, ScanError> {
         loop {
             iter.reset_peek(); // reset the "peek" cursor
 
             if let Some(pair) = iter.next() {
+                let start = pair.0;
+                let start_line = *line;
+                let column = self.column(start);
                 // in most cases we want to break and return, but if we encounter
                 // a newline or comment, we continue the loop instead
-                break match pair {
-                    (_, '(') => self.create_token(TokenKind::LeftParen, line),
-                    (_, ')') => self.create_token(TokenKind::RightParen, line),
-                    (_, '{') => self.create_token(TokenKind::LeftBrace, line),
-                    (_, '}') => self.create_token(TokenKind::RightBrace, line),
-                    (_, ',') => self.create_token(TokenKind::Comma, line),
-                    (_, '.') => self.create_token(TokenKind::Dot, line),
-                    (_, '-') => self.create_token(TokenKind::Minus, line),
-                    (_, '+') => self.create_token(TokenKind::Plus, line),
-                    (_, ';') => self.create_token(TokenKind::Semicolon, line),
-                    (_, '*') => self.create_token(TokenKind::Star, line),
-                    (_, '!') => {
+                let token = match pair {
+                    (_, '(') => self.create_token(TokenKind::LeftParen),
+                    (_, ')') => self.create_token(TokenKind::RightParen),
+                    (_, '{') => self.create_token(TokenKind::LeftBrace),
+                    (_, '}') => self.create_token(TokenKind::RightBrace),
+                    (_, '[') => self.create_token(TokenKind::LeftBracket),
+                    (_, ']') => self.create_token(TokenKind::RightBracket),
+                    (_, ':') => self.create_token(TokenKind::Colon),
+                    (_, ',') => self.create_token(TokenKind::Comma),
+                    (_, '.') => self.create_token(TokenKind::Dot),
+                    (_, '-') => {
                         if self.peek_match(iter, |ch| ch == '=') {
                             iter.next();
-                            self.create_token(TokenKind::BangEqual, line)
+                            self.create_token(TokenKind::MinusEqual)
                         } else {
-                            self.create_token(TokenKind::Bang, line)
+                            self.create_token(TokenKind::Minus)
                         }
                     }
-                    (_, '=') => {
+                    (_, '+') => {
                         if self.peek_match(iter, |ch| ch == '=') {
                             iter.next();
-                            self.create_token(TokenKind::EqualEqual, line)
+                            self.create_token(TokenKind::PlusEqual)
                         } else {
-                            self.create_token(TokenKind::Equal, line)
+                            self.create_token(TokenKind::Plus)
                         }
                     }
-                    (_, '<') => {
-                        if self.peek_match(iter, |ch| ch == '=') {
+                    (_, '?') => {
+                        if self.peek_match(iter, |ch| ch == '?') {
+                            iter.next();
+                            self.create_token(TokenKind::QuestionQuestion)
+                        } else {
+                            self.create_token(TokenKind::Question)
+                        }
+                    }
+                    (_, '^') => self.create_token(TokenKind::Caret),
+                    (_, '~') => self.create_token(TokenKind::Tilde),
+                    // Lox spells the logical operators "and" and "or", so a
+                    // doubled "&" or "|" is almost certainly a mistake
+                    (_, char @ ('&' | '|')) => {
+                        if self.peek_match(iter, |ch| ch == char) {
                             iter.next();
-                            self.create_token(TokenKind::LessEqual, line)
+                            let keyword = if char == '&' { "and" } else { "or" };
+                            Err(anyhow!(
+                                "unexpected \"{}{}\" on line {}:{}, use \"{}\" instead",
+                                char,
+                                char,
+                                line,
+                                column,
+                                keyword
+                            ))
+                        } else if char == '&' {
+                            self.create_token(TokenKind::Ampersand)
                         } else {
-                            self.create_token(TokenKind::Less, line)
+                            self.create_token(TokenKind::Pipe)
                         }
                     }
-                    (_, '>') => {
+                    (_, ';') => self.create_token(TokenKind::Semicolon),
+                    (_, '*') => match iter.peek() {
+                        Some((_, '*')) => {
+                            iter.next();
+                            self.create_token(TokenKind::StarStar)
+                        }
+                        Some((_, '=')) => {
+                            iter.next();
+                            self.create_token(TokenKind::StarEqual)
+                        }
+                        _ => self.create_token(TokenKind::Star),
+                    },
+                    (_, '!') => {
                         if self.peek_match(iter, |ch| ch == '=') {
                             iter.next();
-                            self.create_token(TokenKind::GreaterEqual, line)
+                            self.create_token(TokenKind::BangEqual)
                         } else {
-                            self.create_token(TokenKind::Greater, line)
+                            self.create_token(TokenKind::Bang)
                         }
                     }
-                    (_, '/') => {
-                        if self.peek_match(iter, |ch| ch == '/') {
+                    (_, '=') => {
+                        if self.peek_match(iter, |ch| ch == '=') {
                             iter.next();
-                            // A comment goes until the end of the line
-                            self.read_to_end_of_line(iter);
-                            continue;
+                            self.create_token(TokenKind::EqualEqual)
                         } else {
-                            self.create_token(TokenKind::Slash, line)
+                            self.create_token(TokenKind::Equal)
                         }
                     }
-                    (_, '"') => self.parse_string(iter, line),
-                    (_, ' ' | '\r' | '\t') => continue,
-                    (_, '\n') => {
-                        *line += 1;
+                    (_, '<') => match iter.peek() {
+                        Some((_, '=')) => {
+                            iter.next();
+                            self.create_token(TokenKind::LessEqual)
+                        }
+                        Some((_, '<')) => {
+                            iter.next();
+                            self.create_token(TokenKind::LessLess)
+                        }
+                        _ => self.create_token(TokenKind::Less),
+                    },
+                    (_, '>') => match iter.peek() {
+                        Some((_, '=')) => {
+                            iter.next();
+                            self.create_token(TokenKind::GreaterEqual)
+                        }
+                        Some((_, '>')) => {
+                            iter.next();
+                            self.create_token(TokenKind::GreaterGreater)
+                        }
+                        _ => self.create_token(TokenKind::Greater),
+                    },
+                    (_, '/') => {
+                        let is_comment = match iter.peek() {
+                            Some((_, '/')) => {
+                                iter.next();
+                                // A comment goes until the end of the line
+                                self.read_to_end_of_line(iter);
+                                Ok(true)
+                            }
+                            Some((_, '*')) => {
+                                iter.next();
+                                self.read_block_comment(iter, line, column).map(|_| true)
+                            }
+                            _ => {
+                                iter.reset_peek();
+                                Ok(false)
+                            }
+                        };
+                        match is_comment {
+                            Ok(false) if self.peek_match(iter, |ch| ch == '=') => {
+                                iter.next();
+                                self.create_token(TokenKind::SlashEqual)
+                            }
+                            Ok(false) => self.create_token(TokenKind::Slash),
+                            Ok(true) if !keep_comments => continue,
+                            Ok(true) => {
+                                let comment = &self.source[start..self.offset(iter)];
+                                self.create_token(TokenKind::Comment(comment.to_owned()))
+                            }
+                            Err(err) => Err(err),
+                        }
+                    }
+                    (_, '"') => self.parse_string(iter, line, column, keep_comments),
+                    (_, ' ' | '\t') => continue,
+                    (_, char @ ('\n' | '\r')) => {
+                        if self.ends_line(iter, char) {
+                            *line += 1;
+                        }
                         continue;
                     }
                     (idx, char) => {
                         if char.is_ascii_digit() {
-                            self.parse_number(iter, idx, line)
+                            self.parse_number(iter, idx, line, column)
                         } else if char.is_ascii_alphabetic() || char == '_' {
-                            self.parse_identifer(iter, idx, line)
+                            self.parse_identifer(iter, idx)
                         } else {
-                            Err(anyhow!("unexpected character {:?} on line {}", char, line))
+                            Err(anyhow!(
+                                "unexpected character {:?} on line {}:{}",
+                                char,
+                                line,
+                                column
+                            ))
                         }
                     }
                 };
+                let end = self.offset(iter);
+                let span = Span::new(start_line, column, *line, self.column(end));
+                break match token {
+                    Ok(kind) => Ok(kind.map(|kind| (Token::new(kind, span), start..end))),
+                    // errors inside a string, e.g. in an escape, carry their own span
+                    Err(err) => Err(match err.downcast::<ScanError>() {
+                        Ok(err) => err,
+                        Err(err) => ScanError {
+                            message: err.to_string(),
+                            span,
+                        },
+                    }),
+                };
             } else {
                 // No more tokens left.
                 return Ok(None);
@@ -117,8 +332,39 @@ impl<'a> Scanner<'a> {
     }
 
     // helper method
-    fn create_token(&self, typ: TokenKind, line: &u32) -> Result<Option<Token>> {
-        Ok(Some(Token::new(typ, *line)))
+    fn create_token(&self, typ: TokenKind) -> Result<Option<TokenKind>> {
+        Ok(Some(typ))
+    }
+
+    /// Returns the column of the character at the given byte offset, counting
+    /// characters from 1 at the start of its line.
+    fn column(&self, offset: usize) -> u32 {
+        // positions are mostly found in order, so counting from the last one
+        // keeps scanning a long line linear; otherwise count from the start
+        // of the line
+        let (from, column) = match self.last_column.get() {
+            (last_offset, column) if last_offset <= offset => (last_offset, column),
+            _ => {
+                let line_start = self.source[..offset]
+                    .rfind(['\n', '\r'])
+                    .map_or(0, |idx| idx + 1);
+                (line_start, 1)
+            }
+        };
+        let text = &self.source[from..offset];
+        let column = match text.rfind(['\n', '\r']) {
+            Some(idx) => text[idx + 1..].chars().count() as u32 + 1,
+            None => column + text.chars().count() as u32,
+        };
+        self.last_column.set((offset, column));
+        column
+    }
+
+    /// Returns the byte offset of the next character, or the length of the
+    /// source if there are no characters left.
+    fn offset(&self, iter: &mut CharIter) -> usize {
+        iter.reset_peek();
+        iter.peek().map_or(self.source.len(), |pair| pair.0)
     }
 
     /// Returns true if there is another character to peek which matches the
@@ -134,28 +380,184 @@ impl<'a> Scanner<'a> {
         }
     }
 
+    /// Returns whether `char`, which has just been consumed, ends a line.
+    /// "\n", "\r\n" and a lone "\r" each count as one line ending, so a
+    /// "\r" only ends the line if it isn't followed by "\n".
+    fn ends_line(&self, iter: &mut CharIter, char: char) -> bool {
+        match char {
+            '\n' => true,
+            '\r' => {
+                iter.reset_peek();
+                let ends_line = !self.peek_match(iter, |ch| ch == '\n');
+                iter.reset_peek();
+                ends_line
+            }
+            _ => false,
+        }
+    }
+
     fn read_to_end_of_line(&self, iter: &mut CharIter) {
-        while self.peek_match(iter, |ch| ch != '\n') {
+        while self.peek_match(iter, |ch| ch != '\n' && ch != '\r') {
             iter.next();
         }
     }
 
-    fn parse_string(&self, iter: &mut CharIter, line: &mut u32) -> Result<Option<Token>> {
+    /// Consumes a block comment, assuming the opening "/*" has already been
+    /// consumed. Block comments can be nested.
+    fn read_block_comment(&self, iter: &mut CharIter, line: &mut u32, column: u32) -> Result<()> {
+        let start_line = *line;
+        let mut depth = 1;
+        while depth > 0 {
+            match iter.next() {
+                Some((_, '/')) if self.peek_match(iter, |ch| ch == '*') => {
+                    iter.next();
+                    depth += 1;
+                }
+                Some((_, '*')) if self.peek_match(iter, |ch| ch == '/') => {
+                    iter.next();
+                    depth -= 1;
+                }
+                Some((_, char)) if self.ends_line(iter, char) => *line += 1,
+                Some(_) => {}
+                None => {
+                    return Err(anyhow!(
+                        "end of file while scanning block comment starting on line {}:{}",
+                        start_line,
+                        column
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_string(
+        &self,
+        iter: &mut CharIter,
+        line: &mut u32,
+        column: u32,
+        keep_comments: bool,
+    ) -> Result<Option<TokenKind>> {
+        let start_line = *line;
         let mut lexeme = String::new();
-        while self.peek_match(iter, |ch| ch != '"') {
-            let (_, char) = iter.next().unwrap();
-            if char == '\n' {
-                *line += 1;
+        let mut parts = vec![];
+        // An invalid escape or embedded expression is reported once the end
+        // of the string is found, so that scanning can resume after it
+        let mut deferred_error = None;
+        loop {
+            match iter.next() {
+                Some((_, '"')) => {
+                    if let Some(err) = deferred_error {
+                        return Err(err);
+                    }
+                    if parts.is_empty() {
+                        return self.create_token(TokenKind::String(lexeme));
+                    }
+                    if !lexeme.is_empty() {
+                        parts.push(InterpolationPart::Text(lexeme));
+                    }
+                    return self.create_token(TokenKind::Interpolation(parts));
+                }
+                Some((idx, '\\')) => match self.parse_escape(iter, idx, line) {
+                    Ok(char) => lexeme.push(char),
+                    Err(err) => {
+                        deferred_error.get_or_insert(err);
+                    }
+                },
+                Some((idx, '$')) if self.peek_match(iter, |ch| ch == '{') => {
+                    iter.next();
+                    if !lexeme.is_empty() {
+                        parts.push(InterpolationPart::Text(std::mem::take(&mut lexeme)));
+                    }
+                    match self.scan_interpolated(iter, idx, line, keep_comments) {
+                        Ok(tokens) => parts.push(InterpolationPart::Tokens(tokens)),
+                        Err(err) => {
+                            deferred_error.get_or_insert(err);
+                        }
+                    }
+                }
+                Some((_, char)) => {
+                    if self.ends_line(iter, char) {
+                        *line += 1;
+                    }
+                    lexeme.push(char);
+                }
+                None => {
+                    return Err(anyhow!(
+                        "end of line while scanning string literal starting on line {}:{}",
+                        start_line,
+                        column
+                    ))
+                }
             }
-            lexeme.push(char);
         }
+    }
 
-        // next character is the quote
+    /// Scans the tokens of an expression embedded in a string, assuming the
+    /// "${" starting at byte offset `idx` has already been consumed. The
+    /// closing brace is replaced with an `Eof` token, so that the tokens can
+    /// be parsed on their own.
+    fn scan_interpolated(
+        &self,
+        iter: &mut CharIter,
+        idx: usize,
+        line: &mut u32,
+        keep_comments: bool,
+    ) -> Result<Vec<Token>> {
+        let start_line = *line;
+        let mut tokens = vec![];
+        // braces of map literals inside the expression
+        let mut depth = 0;
+        loop {
+            let token = match self.scan_token(iter, line, keep_comments) {
+                Ok(Some((token, _))) => token,
+                Ok(None) => {
+                    return Err(anyhow!(
+                        "end of file while scanning interpolated expression starting on line {}:{}",
+                        start_line,
+                        self.column(idx)
+                    ))
+                }
+                Err(err) => return Err(err.into()),
+            };
+            match token.kind {
+                TokenKind::LeftBrace => depth += 1,
+                TokenKind::RightBrace if depth == 0 => {
+                    tokens.push(Token::new(TokenKind::Eof, token.span));
+                    return Ok(tokens);
+                }
+                TokenKind::RightBrace => depth -= 1,
+                _ => {}
+            }
+            tokens.push(token);
+        }
+    }
+
+    /// Returns the character denoted by an escape sequence, assuming the
+    /// backslash at byte offset `idx` has already been consumed. An invalid
+    /// escape is reported as a `ScanError` spanning just the escape.
+    fn parse_escape(&self, iter: &mut CharIter, idx: usize, line: &u32) -> Result<char> {
+        let column = self.column(idx);
         match iter.next() {
-            Some(_) => self.create_token(TokenKind::String(lexeme), line),
+            Some((_, 'n')) => Ok('\n'),
+            Some((_, 't')) => Ok('\t'),
+            Some((_, 'r')) => Ok('\r'),
+            Some((_, '0')) => Ok('\0'),
+            Some((_, '\\')) => Ok('\\'),
+            Some((_, '"')) => Ok('"'),
+            Some((_, '$')) => Ok('$'),
+            Some((_, char)) => Err(ScanError {
+                message: format!(
+                    "unknown escape sequence \\{} on line {}:{}",
+                    char, line, column
+                ),
+                span: Span::new(*line, column, *line, self.column(self.offset(iter))),
+            }
+            .into()),
             None => Err(anyhow!(
-                "end of line while scanning string literal on line {}",
-                line
+                "end of line while scanning string literal on line {}:{}",
+                line,
+                column
             )),
         }
     }
@@ -164,12 +566,34 @@ impl<'a> Scanner<'a> {
         &self,
         iter: &mut CharIter,
         idx: usize,
-        line: &mut u32,
-    ) -> Result<Option<Token>> {
-        let mut len = 1;
+        line: &u32,
+        column: u32,
+    ) -> Result<Option<TokenKind>> {
+        // Look for a radix prefix, such as the "x" in "0xFF"
+        if self.source[idx..].starts_with('0') {
+            iter.reset_peek();
+            let radix = match iter.peek() {
+                Some((_, 'x')) => Some(16),
+                Some((_, 'o')) => Some(8),
+                Some((_, 'b')) => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                iter.next();
+                // take any letters too, so that they are reported as invalid
+                // digits rather than starting an identifier
+                while self.peek_match(iter, |ch| ch.is_ascii_alphanumeric()) {
+                    iter.next();
+                }
+                let lexeme = &self.source[idx..self.offset(iter)];
+                let value = parse_radix_lexeme(lexeme, radix, *line, column)?;
+                return self.create_token(TokenKind::Number(value));
+            }
+            iter.reset_peek();
+        }
+
         while self.peek_match(iter, |ch| ch.is_ascii_digit()) {
             iter.next();
-            len += 1;
         }
 
         // Look for a fractional part
@@ -177,57 +601,114 @@ impl<'a> Scanner<'a> {
         if matches!(iter.peek(), Some((_, '.'))) && matches!(iter.peek(), Some((_, '0'..='9'))) {
             // consume the ".", reset peek lookahead
             iter.next();
-            len += 1;
 
             while self.peek_match(iter, |ch| ch.is_ascii_digit()) {
                 iter.next();
-                len += 1;
             }
         }
 
-        let value: f64 = self.source[idx..idx + len]
-            .parse()
-            .with_context(|| format!("unable to parse number on line {}", line))
-            .unwrap();
-        self.create_token(TokenKind::Number(value), line)
+        // Look for an exponent, such as the "e-4" in "2.5e-4"
+        iter.reset_peek();
+        if matches!(iter.peek(), Some((_, 'e' | 'E'))) {
+            iter.next();
+            if self.peek_match(iter, |ch| ch == '+' || ch == '-') {
+                iter.next();
+            }
+            iter.reset_peek();
+            if !self.peek_match(iter, |ch| ch.is_ascii_digit()) {
+                return Err(anyhow!(
+                    "expected digits in the exponent of number {:?} on line {}:{}",
+                    &self.source[idx..self.offset(iter)],
+                    line,
+                    column
+                ));
+            }
+            iter.reset_peek();
+            while self.peek_match(iter, |ch| ch.is_ascii_digit()) {
+                iter.next();
+            }
+        }
+
+        let value = parse_number_lexeme(&self.source[idx..self.offset(iter)], *line, column)?;
+        self.create_token(TokenKind::Number(value))
     }
 
-    fn parse_identifer(
-        &self,
-        iter: &mut CharIter,
-        idx: usize,
-        line: &mut u32,
-    ) -> Result<Option<Token>> {
-        let mut len = 1;
+    fn parse_identifer(&self, iter: &mut CharIter, idx: usize) -> Result<Option<TokenKind>> {
         while self.peek_match(iter, |ch| ch.is_alphanumeric() || ch == '_') {
             iter.next();
-            len += 1;
         }
 
-        let typ = match &self.source[idx..idx + len] {
+        let lexeme = &self.source[idx..self.offset(iter)];
+        let typ = match lexeme {
             "and" => TokenKind::And,
+            "case" => TokenKind::Case,
+            "catch" => TokenKind::Catch,
             "class" => TokenKind::Class,
+            "default" => TokenKind::Default,
+            "do" => TokenKind::Do,
             "else" => TokenKind::Else,
             "false" => TokenKind::False,
             "for" => TokenKind::For,
             "fun" => TokenKind::Fun,
             "if" => TokenKind::If,
+            "in" => TokenKind::In,
+            "is" => TokenKind::Is,
             "nil" => TokenKind::Nil,
             "or" => TokenKind::Or,
             "print" => TokenKind::Print,
             "return" => TokenKind::Return,
             "super" => TokenKind::Super,
+            "switch" => TokenKind::Switch,
             "this" => TokenKind::This,
+            "throw" => TokenKind::Throw,
             "true" => TokenKind::True,
+            "try" => TokenKind::Try,
             "var" => TokenKind::Var,
             "while" => TokenKind::While,
-            _ => TokenKind::Identifier(self.source[idx..idx + len].to_owned()),
+            _ => TokenKind::Identifier(Symbol::intern(lexeme)),
         };
 
-        self.create_token(typ, line)
+        self.create_token(typ)
     }
 }
 
+/// Converts the lexeme of a number literal into its value.
+fn parse_number_lexeme(lexeme: &str, line: u32, column: u32) -> Result<f64> {
+    lexeme.parse().with_context(|| {
+        format!(
+            "unable to parse number {:?} on line {}:{}",
+            lexeme, line, column
+        )
+    })
+}
+
+/// Converts the lexeme of a number literal with a radix prefix, such as
+/// `0x1F`, `0o17` or `0b101`, into its value.
+fn parse_radix_lexeme(lexeme: &str, radix: u32, line: u32, column: u32) -> Result<f64> {
+    let digits = &lexeme[2..];
+    if digits.is_empty() {
+        return Err(anyhow!(
+            "expected digits after {} on line {}:{}",
+            lexeme,
+            line,
+            column
+        ));
+    }
+    if let Some(digit) = digits.chars().find(|ch| !ch.is_digit(radix)) {
+        return Err(anyhow!(
+            "invalid digit {:?} in base {} number {} on line {}:{}",
+            digit,
+            radix,
+            lexeme,
+            line,
+            column
+        ));
+    }
+    let value = u64::from_str_radix(digits, radix)
+        .with_context(|| format!("number {} is too large on line {}:{}", lexeme, line, column))?;
+    Ok(value as f64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -263,6 +744,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_scans_compound_assignment_operators() {
+        let scanner = Scanner::new("+= -= *= /= ** * / //=");
+        let tokens = scanner.scan_tokens().unwrap();
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|tok| tok.kind.clone())
+                .collect::<Vec<TokenKind>>(),
+            [
+                TokenKind::PlusEqual,
+                TokenKind::MinusEqual,
+                TokenKind::StarEqual,
+                TokenKind::SlashEqual,
+                TokenKind::StarStar,
+                TokenKind::Star,
+                TokenKind::Slash,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn it_scans_bitwise_operators() {
+        let scanner = Scanner::new("& | ^ ~ << >> <= >= < >");
+        let tokens = scanner.scan_tokens().unwrap();
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|tok| tok.kind.clone())
+                .collect::<Vec<TokenKind>>(),
+            [
+                TokenKind::Ampersand,
+                TokenKind::Pipe,
+                TokenKind::Caret,
+                TokenKind::Tilde,
+                TokenKind::LessLess,
+                TokenKind::GreaterGreater,
+                TokenKind::LessEqual,
+                TokenKind::GreaterEqual,
+                TokenKind::Less,
+                TokenKind::Greater,
+                TokenKind::Eof,
+            ]
+        );
+        let err = Scanner::new("a && b").scan_tokens().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unexpected \"&&\" on line 1:3, use \"and\" instead"
+        );
+    }
+
     #[test]
     fn it_ignores_comments() {
         let scanner = Scanner::new("() // hello\n// last line");
@@ -275,4 +808,324 @@ mod tests {
             [TokenKind::LeftParen, TokenKind::RightParen, TokenKind::Eof,]
         );
     }
+
+    #[test]
+    fn it_highlights_token_ranges() {
+        let source = "var cafe = \"hi\"; // note\nprint cafe;";
+        let scanner = Scanner::new(source);
+        let lexemes = |keep_comments| {
+            scanner
+                .highlight(keep_comments)
+                .unwrap()
+                .into_iter()
+                .map(|(_, range)| &source[range])
+                .collect::<Vec<&str>>()
+        };
+        assert_eq!(
+            lexemes(false),
+            ["var", "cafe", "=", "\"hi\"", ";", "print", "cafe", ";"]
+        );
+        assert_eq!(
+            lexemes(true),
+            ["var", "cafe", "=", "\"hi\"", ";", "// note", "print", "cafe", ";"]
+        );
+    }
+
+    #[test]
+    fn it_ignores_block_comments() {
+        let scanner = Scanner::new("( /* one\n/* two */\n*/ )\n/**/;");
+        let tokens = scanner.scan_tokens().unwrap();
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|tok| (tok.kind.clone(), tok.span.start_line))
+                .collect::<Vec<(TokenKind, u32)>>(),
+            [
+                (TokenKind::LeftParen, 1),
+                (TokenKind::RightParen, 3),
+                (TokenKind::Semicolon, 4),
+                (TokenKind::Eof, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_errors_on_unterminated_block_comments() {
+        let scanner = Scanner::new("1;\n/* outer /* inner */\n2;");
+        let err = scanner.scan_tokens().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "end of file while scanning block comment starting on line 2:1"
+        );
+    }
+
+    #[test]
+    fn it_parses_escape_sequences() {
+        let scanner = Scanner::new(r#""a\nb\tc\rd\\e\"f\0""#);
+        let tokens = scanner.scan_tokens().unwrap();
+        assert_eq!(
+            tokens[0].kind,
+            TokenKind::String("a\nb\tc\rd\\e\"f\0".to_string())
+        );
+        assert_eq!(tokens[0].span, Span::new(1, 1, 1, 21));
+    }
+
+    #[test]
+    fn it_errors_on_unknown_escape_sequences() {
+        let scanner = Scanner::new("\n\"a\\qb\"");
+        let err = scanner.scan_tokens().unwrap_err();
+        assert_eq!(err.to_string(), "unknown escape sequence \\q on line 2:3");
+        let (_, errors) = scanner.scan_all();
+        assert_eq!(errors[0].span, Span::new(2, 3, 2, 5));
+    }
+
+    #[test]
+    fn it_scans_interpolated_strings() {
+        let scanner = Scanner::new(r#""a ${x + 1} b${"c"}" "\${x}""#);
+        let tokens = scanner.scan_tokens().unwrap();
+        let parts = match &tokens[0].kind {
+            TokenKind::Interpolation(parts) => parts,
+            kind => panic!("expected an interpolation, found {:?}", kind),
+        };
+        let kinds = |part: &InterpolationPart| match part {
+            InterpolationPart::Tokens(tokens) => {
+                tokens.iter().map(|token| token.kind.clone()).collect()
+            }
+            InterpolationPart::Text(_) => vec![],
+        };
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0], InterpolationPart::Text("a ".into()));
+        assert_eq!(
+            kinds(&parts[1]),
+            vec![
+                TokenKind::Identifier(Symbol::intern("x")),
+                TokenKind::Plus,
+                TokenKind::Number(1.0),
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!(parts[2], InterpolationPart::Text(" b".into()));
+        assert_eq!(
+            kinds(&parts[3]),
+            vec![TokenKind::String("c".into()), TokenKind::Eof]
+        );
+        assert_eq!(tokens[0].span, Span::new(1, 1, 1, 21));
+        // an escaped "$" doesn't start an embedded expression
+        assert_eq!(tokens[1].kind, TokenKind::String("${x}".into()));
+    }
+
+    #[test]
+    fn it_errors_on_unterminated_interpolations() {
+        let err = Scanner::new(r#"print "a ${x";"#).scan_tokens().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "end of line while scanning string literal starting on line 1:7"
+        );
+        let err = Scanner::new("print \"${1 # 2}\";")
+            .scan_tokens()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "unexpected character '#' on line 1:12");
+    }
+
+    #[test]
+    fn it_counts_each_kind_of_line_ending() {
+        let scanner = Scanner::new("a\nb\r\nc\rd /* \r\n\r */\r\"\r\n\"// x\r\r\n\n  e");
+        let tokens = scanner.scan_tokens().unwrap();
+        let lines: Vec<u32> = tokens.iter().map(|token| token.span.start_line).collect();
+        assert_eq!(lines, vec![1, 2, 3, 4, 7, 11, 11]);
+        assert_eq!(tokens.last().unwrap().span, Span::new(11, 4, 11, 4));
+    }
+
+    #[test]
+    fn it_scans_unicode_identifiers() {
+        let scanner = Scanner::new("var café = 1;");
+        let tokens = scanner.scan_tokens().unwrap();
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|tok| tok.kind.clone())
+                .collect::<Vec<TokenKind>>(),
+            [
+                TokenKind::Var,
+                TokenKind::Identifier(Symbol::intern("café")),
+                TokenKind::Equal,
+                TokenKind::Number(1.0),
+                TokenKind::Semicolon,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn it_scans_numbers_with_a_radix() {
+        let scanner = Scanner::new("0xFF 0b1010 0o17 0xdeadbeef 0.5 07");
+        let kinds: Vec<TokenKind> = scanner
+            .scan_tokens()
+            .unwrap()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Number(255.0),
+                TokenKind::Number(10.0),
+                TokenKind::Number(15.0),
+                TokenKind::Number(3735928559.0),
+                TokenKind::Number(0.5),
+                TokenKind::Number(7.0),
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reports_invalid_digits_for_the_radix() {
+        let err = Scanner::new("\n 0xG").scan_tokens().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid digit 'G' in base 16 number 0xG on line 2:2"
+        );
+        let err = Scanner::new("0b102").scan_tokens().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid digit '2' in base 2 number 0b102 on line 1:1"
+        );
+        let err = Scanner::new("0x;").scan_tokens().unwrap_err();
+        assert_eq!(err.to_string(), "expected digits after 0x on line 1:1");
+    }
+
+    #[test]
+    fn it_scans_scientific_notation() {
+        let scanner = Scanner::new("1e3 6.022e23 2E-2 1e+2");
+        let kinds: Vec<TokenKind> = scanner
+            .scan_tokens()
+            .unwrap()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Number(1000.0),
+                TokenKind::Number(6.022e23),
+                TokenKind::Number(0.02),
+                TokenKind::Number(100.0),
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reports_exponents_without_digits() {
+        let err = Scanner::new("x = 1e;").scan_tokens().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected digits in the exponent of number \"1e\" on line 1:5"
+        );
+        let err = Scanner::new("2.5e-").scan_tokens().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected digits in the exponent of number \"2.5e-\" on line 1:1"
+        );
+    }
+
+    #[test]
+    fn it_reports_malformed_numbers() {
+        assert_eq!(parse_number_lexeme("12.5", 1, 1).unwrap(), 12.5);
+        let err = parse_number_lexeme("1.2.3", 4, 7).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unable to parse number \"1.2.3\" on line 4:7"
+        );
+    }
+
+    #[test]
+    fn it_tracks_token_columns() {
+        let scanner = Scanner::new("var café = \"hi\";\n  print café;");
+        let tokens = scanner.scan_tokens().unwrap();
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|tok| tok.location())
+                .collect::<Vec<String>>(),
+            ["1:1", "1:5", "1:10", "1:12", "1:16", "2:3", "2:9", "2:13", "2:14"]
+        );
+    }
+
+    #[test]
+    fn it_tracks_columns_on_long_lines() {
+        // finding each column used to count from the start of the line, which
+        // took minutes for a long line of minified code
+        let source = "print 1;".repeat(80_000);
+        let tokens = Scanner::new(&source).scan_tokens().unwrap();
+        assert_eq!(tokens.len(), 3 * 80_000 + 1);
+        assert_eq!(tokens[tokens.len() - 2].location(), "1:640000");
+        assert_eq!(tokens[tokens.len() - 1].location(), "1:640001");
+
+        let source = "print 1;\r\nprint \"é\";\rprint 3;\n  print 4;";
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|tok| tok.location())
+                .collect::<Vec<String>>(),
+            [
+                "1:1", "1:7", "1:8", "2:1", "2:7", "2:10", "3:1", "3:7", "3:8", "4:3", "4:9",
+                "4:10", "4:11"
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reports_columns_in_errors() {
+        let scanner = Scanner::new("1;\nprint 2 @ 3;");
+        let err = scanner.scan_tokens().unwrap_err();
+        assert_eq!(err.to_string(), "unexpected character '@' on line 2:9");
+    }
+
+    #[test]
+    fn it_collects_every_scan_error() {
+        let scanner = Scanner::new("print 1 @ 2;\nprint \"\\q\" # 3;\nprint 4;");
+        let (tokens, errors) = scanner.scan_all();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|err| (err.to_string(), err.span.to_string()))
+                .collect::<Vec<(String, String)>>(),
+            [
+                (
+                    "unexpected character '@' on line 1:9".to_string(),
+                    "1:9".to_string()
+                ),
+                (
+                    "unknown escape sequence \\q on line 2:8".to_string(),
+                    "2:8".to_string()
+                ),
+                (
+                    "unexpected character '#' on line 2:12".to_string(),
+                    "2:12".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|tok| tok.kind.clone())
+                .collect::<Vec<TokenKind>>(),
+            [
+                TokenKind::Print,
+                TokenKind::Number(1.0),
+                TokenKind::Number(2.0),
+                TokenKind::Semicolon,
+                TokenKind::Print,
+                TokenKind::Number(3.0),
+                TokenKind::Semicolon,
+                TokenKind::Print,
+                TokenKind::Number(4.0),
+                TokenKind::Semicolon,
+                TokenKind::Eof,
+            ]
+        );
+    }
 }
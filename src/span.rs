@@ -0,0 +1,40 @@
+use std::fmt;
+
+/// A region of source code. Lines and columns count from 1, and the end
+/// position is the one just past the last character in the region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start_line: u32,
+    pub start_col: u32,
+    pub end_line: u32,
+    pub end_col: u32,
+}
+
+impl Span {
+    pub fn new(start_line: u32, start_col: u32, end_line: u32, end_col: u32) -> Self {
+        Span {
+            start_line,
+            start_col,
+            end_line,
+            end_col,
+        }
+    }
+
+    /// Returns the span starting where `self` starts and ending where `other`
+    /// ends.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end_line: other.end_line,
+            end_col: other.end_col,
+            ..self
+        }
+    }
+}
+
+/// Formats the start of the span as "line:column".
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.start_line, self.start_col)
+    }
+}
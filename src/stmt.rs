@@ -1,59 +1,211 @@
-use crate::expr::Expr;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::expr::{Expr, Variable};
+use crate::span::Span;
+use crate::symbol::Symbol;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Stmt {
     Block(Block),
+    Class(Class),
+    DoWhile(DoWhile),
     Expression(Expression),
+    For(For),
+    ForIn(ForIn),
     Function(Function),
     If(If),
     Print(Print),
     Return(Return),
+    Switch(Switch),
+    Throw(Throw),
+    Try(Try),
     Var(Var),
     While(While),
 }
 
+impl Stmt {
+    /// Returns the region of source code the statement was parsed from.
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Block(block) => block.span,
+            Stmt::Class(class) => class.span,
+            Stmt::DoWhile(do_while) => do_while.span,
+            Stmt::Expression(expression) => expression.span,
+            Stmt::For(for_) => for_.span,
+            Stmt::ForIn(for_in) => for_in.span,
+            Stmt::Function(function) => function.span,
+            Stmt::If(if_) => if_.span,
+            Stmt::Print(print) => print.span,
+            Stmt::Return(return_) => return_.span,
+            Stmt::Switch(switch) => switch.span,
+            Stmt::Throw(throw) => throw.span,
+            Stmt::Try(try_) => try_.span,
+            Stmt::Var(var) => var.span,
+            Stmt::While(while_) => while_.span,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block {
     pub statements: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Class {
+    pub name: Symbol,
+    pub superclass: Option<Variable>,
+    pub methods: Vec<Function>,
+    pub span: Span,
 }
 
+/// `do body while (condition);`, which runs the body before checking the
+/// condition for the first time.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DoWhile {
+    pub body: Box<Stmt>,
+    pub condition: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Expression {
     pub expression: Expr,
+    pub span: Span,
+}
+
+/// `for (initializer; condition; increment) body`. The initializer, which
+/// may declare a variable, runs once in a scope of its own around the loop,
+/// and a missing condition counts as true.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct For {
+    pub initializer: Option<Box<Stmt>>,
+    pub condition: Option<Expr>,
+    pub increment: Option<Expr>,
+    pub body: Box<Stmt>,
+    pub span: Span,
+}
+
+/// `for (name in iterable) body`, which runs the body once for each element
+/// of a list or character of a string.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ForIn {
+    pub name: Symbol,
+    pub iterable: Expr,
+    pub body: Box<Stmt>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Function {
-    pub name: String,
-    pub params: Vec<String>,
-    pub body: Vec<Stmt>,
+    pub name: Symbol,
+    pub params: Vec<Param>,
+    /// Shared with every closure created from this declaration, so that
+    /// defining a function doesn't copy its body.
+    pub body: Rc<Vec<Stmt>>,
+    pub span: Span,
 }
 
+/// A function parameter, which is either a plain name or a pattern that
+/// destructures a list argument into several names.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Param {
+    Name(Symbol),
+    List(Vec<Param>),
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Param::Name(name) => write!(f, "{}", name),
+            Param::List(params) => {
+                write!(f, "[")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct If {
     pub condition: Expr,
     pub then_branch: Box<Stmt>,
     pub else_branch: Option<Box<Stmt>>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Print {
-    pub expression: Expr,
+    pub expressions: Vec<Expr>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Return {
     pub value: Expr,
+    pub span: Span,
+}
+
+/// `switch (subject) { case value: ... default: ... }`. Only the first
+/// case whose value equals the subject runs; there is no fallthrough.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Switch {
+    pub subject: Expr,
+    pub cases: Vec<(Expr, Vec<Stmt>)>,
+    pub default: Option<Vec<Stmt>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Throw {
+    pub value: Expr,
+    pub span: Span,
+}
+
+/// `try { body } catch (catch_name) { catch_body }`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Try {
+    pub body: Vec<Stmt>,
+    pub catch_name: Symbol,
+    pub catch_body: Vec<Stmt>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Var {
-    pub name: String,
+    pub name: Symbol,
     pub initializer: Option<Expr>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct While {
     pub condition: Expr,
     pub body: Box<Stmt>,
+    pub span: Span,
 }
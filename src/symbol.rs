@@ -0,0 +1,120 @@
+//! Interned identifiers. Names are interned while parsing, so that the
+//! interpreter can look up variables and properties by symbol without
+//! touching the names themselves, and without locking the interner.
+//!
+//! Interned names are leaked: they stay allocated until the process exits,
+//! even once no syntax tree uses them. Programs only ever have as many
+//! distinct names as their source contains, so this is bounded by the
+//! source that has been parsed.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{LazyLock, Mutex, MutexGuard};
+
+/// An interned identifier. Symbols for the same name are equal, and can be
+/// compared and hashed without looking at the name itself, which makes
+/// them cheap keys for scopes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/// Maps names to symbols and back. Names are never freed, since any symbol
+/// for them may still be in use. There is one interner for the whole
+/// process, so that syntax trees can be moved between threads.
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+// names the interpreter looks up itself, in the order of their symbols
+const PREDEFINED: [&str; 3] = ["this", "super", "init"];
+
+static INTERNER: LazyLock<Mutex<Interner>> = LazyLock::new(|| {
+    let names = PREDEFINED.to_vec();
+    let symbols = (0..).map(Symbol).zip(names.iter());
+    Mutex::new(Interner {
+        symbols: symbols.map(|(symbol, name)| (*name, symbol)).collect(),
+        names,
+    })
+});
+
+fn interner() -> MutexGuard<'static, Interner> {
+    // the interner is never left half-updated, so a panic elsewhere while
+    // it was locked doesn't matter
+    INTERNER.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+impl Symbol {
+    pub const THIS: Symbol = Symbol(0);
+    pub const SUPER: Symbol = Symbol(1);
+    pub const INIT: Symbol = Symbol(2);
+
+    /// Returns the symbol for `name`, interning it if it hasn't been seen
+    /// before.
+    pub fn intern(name: &str) -> Symbol {
+        let mut interner = interner();
+        if let Some(symbol) = interner.symbols.get(name) {
+            return *symbol;
+        }
+        let name: &'static str = Box::leak(name.into());
+        let symbol = Symbol(interner.names.len() as u32);
+        interner.names.push(name);
+        interner.symbols.insert(name, symbol);
+        symbol
+    }
+
+    /// Returns the name the symbol was interned from.
+    pub fn as_str(self) -> &'static str {
+        interner().names[self.0 as usize]
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// symbols are only meaningful alongside the interner, so show the name
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Symbol::intern(&name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intern_names() {
+        let x = Symbol::intern("x");
+        assert_eq!(x, Symbol::intern("x"));
+        assert_ne!(x, Symbol::intern("y"));
+        assert_eq!(x.as_str(), "x");
+        assert_eq!(Symbol::intern("y").to_string(), "y");
+        assert_eq!(Symbol::intern("this"), Symbol::THIS);
+        assert_eq!(Symbol::SUPER.as_str(), "super");
+        assert_eq!(Symbol::intern("init"), Symbol::INIT);
+    }
+}
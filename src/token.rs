@@ -1,25 +1,44 @@
 use std::fmt::{Display, Formatter, Result};
 
+use itertools::Itertools;
+
+use crate::span::Span;
+use crate::symbol::Symbol;
+
+/// A token produced by the scanner: what kind of token it is, and where in
+/// the source it was found.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token {
     pub kind: TokenKind,
-    pub line: u32,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(typ: TokenKind, line: u32) -> Self {
-        Token { kind: typ, line }
+    pub fn new(typ: TokenKind, span: Span) -> Self {
+        Token { kind: typ, span }
     }
 
-    pub fn dummy() -> Token {
+    /// Returns a placeholder end-of-file token on line 0, used to seed the
+    /// parser before it has read any real tokens. It never appears in the
+    /// scanner's output.
+    pub(crate) fn dummy() -> Token {
         Token {
-            kind: TokenKind::Semicolon,
-            line: 0,
+            kind: TokenKind::Eof,
+            span: Span::default(),
         }
     }
 
+    /// Returns the location of the start of the token formatted as
+    /// "line:column".
+    pub fn location(&self) -> String {
+        self.span.to_string()
+    }
+
     pub fn is_unary(&self) -> bool {
-        self.kind == TokenKind::Bang || self.kind == TokenKind::Minus
+        self.kind == TokenKind::Bang
+            || self.kind == TokenKind::Minus
+            || self.kind == TokenKind::Tilde
     }
 
     pub fn is_equality(&self) -> bool {
@@ -31,6 +50,11 @@ impl Token {
             || self.kind == TokenKind::GreaterEqual
             || self.kind == TokenKind::Less
             || self.kind == TokenKind::LessEqual
+            || self.kind == TokenKind::Is
+    }
+
+    pub fn is_shift(&self) -> bool {
+        self.kind == TokenKind::LessLess || self.kind == TokenKind::GreaterGreater
     }
 
     pub fn is_term(&self) -> bool {
@@ -40,22 +64,46 @@ impl Token {
     pub fn is_factor(&self) -> bool {
         self.kind == TokenKind::Slash || self.kind == TokenKind::Star
     }
+
+    /// Returns the binary operator a compound assignment such as `+=`
+    /// applies, or `None` if this is not a compound assignment.
+    pub fn compound_operator(&self) -> Option<TokenKind> {
+        match self.kind {
+            TokenKind::PlusEqual => Some(TokenKind::Plus),
+            TokenKind::MinusEqual => Some(TokenKind::Minus),
+            TokenKind::StarEqual => Some(TokenKind::Star),
+            TokenKind::SlashEqual => Some(TokenKind::Slash),
+            _ => None,
+        }
+    }
 }
 
+/// The kinds of token in Lox. Literal values (numbers, strings and
+/// identifiers) are carried inside their variants, so the parser can get
+/// at them by pattern matching.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenKind {
     // Single-character tokens
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
     Comma,
     Dot,
     Minus,
     Plus,
+    Question,
     Semicolon,
     Slash,
     Star,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or two character tokens
     Bang,
@@ -66,33 +114,64 @@ pub enum TokenKind {
     GreaterEqual,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
+    MinusEqual,
+    PlusEqual,
+    SlashEqual,
+    StarEqual,
+    StarStar,
+    QuestionQuestion,
 
     // Literals
-    Identifier(String), // TODO: string interning?
+    Identifier(Symbol),
     String(String),
     Number(f64),
+    Interpolation(Vec<InterpolationPart>),
+
+    // Only produced when scanning for syntax highlighting
+    Comment(String),
 
     // Keywords
     And,
+    Case,
+    Catch,
     Class,
+    Default,
+    Do,
     Else,
     False,
     Fun,
     For,
     If,
+    In,
+    Is,
     Nil,
     Or,
     Print,
     Return,
     Super,
+    Switch,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
     Eof,
 }
 
+/// A piece of a string literal containing `${...}`: either literal text, or
+/// the tokens of an embedded expression, which end with an `Eof` token in
+/// place of the closing brace.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InterpolationPart {
+    Text(String),
+    Tokens(Vec<Token>),
+}
+
 impl Display for TokenKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
@@ -101,13 +180,21 @@ impl Display for TokenKind {
             TokenKind::RightParen => write!(f, ")"),
             TokenKind::LeftBrace => write!(f, "{{"),
             TokenKind::RightBrace => write!(f, "}}"),
+            TokenKind::LeftBracket => write!(f, "["),
+            TokenKind::RightBracket => write!(f, "]"),
+            TokenKind::Colon => write!(f, ":"),
             TokenKind::Comma => write!(f, ","),
             TokenKind::Dot => write!(f, "."),
             TokenKind::Minus => write!(f, "-"),
             TokenKind::Plus => write!(f, "+"),
+            TokenKind::Question => write!(f, "?"),
             TokenKind::Semicolon => write!(f, ";"),
             TokenKind::Slash => write!(f, "/"),
             TokenKind::Star => write!(f, "*"),
+            TokenKind::Ampersand => write!(f, "&"),
+            TokenKind::Pipe => write!(f, "|"),
+            TokenKind::Caret => write!(f, "^"),
+            TokenKind::Tilde => write!(f, "~"),
 
             // One or two character tokens
             TokenKind::Bang => write!(f, "!"),
@@ -118,27 +205,58 @@ impl Display for TokenKind {
             TokenKind::GreaterEqual => write!(f, ">="),
             TokenKind::Less => write!(f, "<"),
             TokenKind::LessEqual => write!(f, "<="),
+            TokenKind::LessLess => write!(f, "<<"),
+            TokenKind::GreaterGreater => write!(f, ">>"),
+            TokenKind::MinusEqual => write!(f, "-="),
+            TokenKind::PlusEqual => write!(f, "+="),
+            TokenKind::SlashEqual => write!(f, "/="),
+            TokenKind::StarEqual => write!(f, "*="),
+            TokenKind::StarStar => write!(f, "**"),
+            TokenKind::QuestionQuestion => write!(f, "??"),
 
             // Literals
             TokenKind::Identifier(value) => write!(f, "{}", value),
             TokenKind::String(value) => write!(f, "{}", value),
             TokenKind::Number(value) => write!(f, "{}", value),
+            TokenKind::Interpolation(parts) => {
+                for part in parts {
+                    match part {
+                        InterpolationPart::Text(text) => write!(f, "{}", text)?,
+                        InterpolationPart::Tokens(tokens) => {
+                            let tokens = tokens.iter().filter(|token| token.kind != TokenKind::Eof);
+                            write!(f, "${{{}}}", tokens.map(|token| &token.kind).join(" "))?
+                        }
+                    }
+                }
+                Ok(())
+            }
+
+            TokenKind::Comment(value) => write!(f, "{}", value),
 
             // Keywords
             TokenKind::And => write!(f, "and"),
+            TokenKind::Case => write!(f, "case"),
+            TokenKind::Catch => write!(f, "catch"),
             TokenKind::Class => write!(f, "class"),
+            TokenKind::Default => write!(f, "default"),
+            TokenKind::Do => write!(f, "do"),
             TokenKind::Else => write!(f, "else"),
             TokenKind::False => write!(f, "false"),
             TokenKind::Fun => write!(f, "fun"),
             TokenKind::For => write!(f, "for"),
             TokenKind::If => write!(f, "if"),
+            TokenKind::In => write!(f, "in"),
+            TokenKind::Is => write!(f, "is"),
             TokenKind::Nil => write!(f, "nil"),
             TokenKind::Or => write!(f, "or"),
             TokenKind::Print => write!(f, "print"),
             TokenKind::Return => write!(f, "return"),
             TokenKind::Super => write!(f, "super"),
+            TokenKind::Switch => write!(f, "switch"),
             TokenKind::This => write!(f, "this"),
+            TokenKind::Throw => write!(f, "throw"),
             TokenKind::True => write!(f, "true"),
+            TokenKind::Try => write!(f, "try"),
             TokenKind::Var => write!(f, "var"),
             TokenKind::While => write!(f, "while"),
 
@@ -146,3 +264,105 @@ impl Display for TokenKind {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(kind: TokenKind) -> Token {
+        Token::new(kind, Span::default())
+    }
+
+    // every operator the classification methods could plausibly accept
+    fn operators() -> Vec<TokenKind> {
+        vec![
+            TokenKind::Bang,
+            TokenKind::BangEqual,
+            TokenKind::Equal,
+            TokenKind::EqualEqual,
+            TokenKind::Greater,
+            TokenKind::GreaterEqual,
+            TokenKind::Less,
+            TokenKind::LessEqual,
+            TokenKind::Minus,
+            TokenKind::MinusEqual,
+            TokenKind::Plus,
+            TokenKind::PlusEqual,
+            TokenKind::Slash,
+            TokenKind::SlashEqual,
+            TokenKind::Star,
+            TokenKind::StarEqual,
+            TokenKind::StarStar,
+            TokenKind::Ampersand,
+            TokenKind::Pipe,
+            TokenKind::Caret,
+            TokenKind::Tilde,
+            TokenKind::LessLess,
+            TokenKind::GreaterGreater,
+            TokenKind::Is,
+            TokenKind::And,
+            TokenKind::Or,
+        ]
+    }
+
+    fn classified_as(is: fn(&Token) -> bool) -> Vec<TokenKind> {
+        operators()
+            .into_iter()
+            .filter(|kind| is(&token(kind.clone())))
+            .collect()
+    }
+
+    #[test]
+    fn equality_operators() {
+        assert_eq!(
+            classified_as(Token::is_equality),
+            [TokenKind::BangEqual, TokenKind::EqualEqual]
+        );
+    }
+
+    #[test]
+    fn comparison_operators() {
+        assert_eq!(
+            classified_as(Token::is_comparison),
+            [
+                TokenKind::Greater,
+                TokenKind::GreaterEqual,
+                TokenKind::Less,
+                TokenKind::LessEqual,
+                TokenKind::Is
+            ]
+        );
+    }
+
+    #[test]
+    fn shift_operators() {
+        assert_eq!(
+            classified_as(Token::is_shift),
+            [TokenKind::LessLess, TokenKind::GreaterGreater]
+        );
+    }
+
+    #[test]
+    fn term_operators() {
+        assert_eq!(
+            classified_as(Token::is_term),
+            [TokenKind::Minus, TokenKind::Plus]
+        );
+    }
+
+    #[test]
+    fn factor_operators() {
+        assert_eq!(
+            classified_as(Token::is_factor),
+            [TokenKind::Slash, TokenKind::Star]
+        );
+    }
+
+    #[test]
+    fn unary_operators() {
+        assert_eq!(
+            classified_as(Token::is_unary),
+            [TokenKind::Bang, TokenKind::Minus, TokenKind::Tilde]
+        );
+    }
+}
@@ -0,0 +1,581 @@
+use std::rc::Rc;
+
+use crate::{
+    expr::{
+        Assign, Binary, Call, Expr, Get, Grouping, Index, Interpolation, ListLiteral, LiteralExpr,
+        Logical, MapLiteral, Set, SetIndex, StringPart, Super, Ternary, This, Unary, Variable,
+    },
+    stmt::{
+        Block, Class, DoWhile, Expression, For, ForIn, Function, If, Print, Return, Stmt, Switch,
+        Throw, Try, Var, While,
+    },
+};
+
+/// Like `Visit`, but with mutable access to the nodes, for passes that
+/// transform the syntax tree in place.
+pub trait VisitMut {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        visit_expr_mut(self, e);
+    }
+    fn visit_expr_assign_mut(&mut self, e: &mut Assign) {
+        visit_expr_assign_mut(self, e);
+    }
+    fn visit_expr_binary_mut(&mut self, e: &mut Binary) {
+        visit_expr_binary_mut(self, e);
+    }
+    fn visit_expr_call_mut(&mut self, e: &mut Call) {
+        visit_expr_call_mut(self, e);
+    }
+    fn visit_expr_get_mut(&mut self, e: &mut Get) {
+        visit_expr_get_mut(self, e);
+    }
+    fn visit_expr_grouping_mut(&mut self, e: &mut Grouping) {
+        visit_expr_grouping_mut(self, e);
+    }
+    fn visit_expr_index_mut(&mut self, e: &mut Index) {
+        visit_expr_index_mut(self, e);
+    }
+    fn visit_expr_interpolation_mut(&mut self, e: &mut Interpolation) {
+        visit_expr_interpolation_mut(self, e);
+    }
+    fn visit_expr_list_literal_mut(&mut self, e: &mut ListLiteral) {
+        visit_expr_list_literal_mut(self, e);
+    }
+    fn visit_expr_literal_mut(&mut self, e: &mut LiteralExpr) {
+        visit_expr_literal_mut(self, e);
+    }
+    fn visit_expr_logical_mut(&mut self, e: &mut Logical) {
+        visit_expr_logical_mut(self, e);
+    }
+    fn visit_expr_map_literal_mut(&mut self, e: &mut MapLiteral) {
+        visit_expr_map_literal_mut(self, e);
+    }
+    fn visit_expr_set_mut(&mut self, e: &mut Set) {
+        visit_expr_set_mut(self, e);
+    }
+    fn visit_expr_set_index_mut(&mut self, e: &mut SetIndex) {
+        visit_expr_set_index_mut(self, e);
+    }
+    fn visit_expr_super_mut(&mut self, e: &mut Super) {
+        visit_expr_super_mut(self, e);
+    }
+    fn visit_expr_ternary_mut(&mut self, e: &mut Ternary) {
+        visit_expr_ternary_mut(self, e);
+    }
+    fn visit_expr_this_mut(&mut self, e: &mut This) {
+        visit_expr_this_mut(self, e);
+    }
+    fn visit_expr_variable_mut(&mut self, e: &mut Variable) {
+        visit_expr_variable_mut(self, e);
+    }
+    fn visit_expr_unary_mut(&mut self, e: &mut Unary) {
+        visit_expr_unary_mut(self, e);
+    }
+    fn visit_stmt_mut(&mut self, s: &mut Stmt) {
+        visit_stmt_mut(self, s);
+    }
+    fn visit_stmt_block_mut(&mut self, s: &mut Block) {
+        visit_stmt_block_mut(self, s);
+    }
+    fn visit_stmt_class_mut(&mut self, s: &mut Class) {
+        visit_stmt_class_mut(self, s);
+    }
+    fn visit_stmt_do_while_mut(&mut self, s: &mut DoWhile) {
+        visit_stmt_do_while_mut(self, s);
+    }
+    fn visit_stmt_expression_mut(&mut self, s: &mut Expression) {
+        visit_stmt_expression_mut(self, s);
+    }
+    fn visit_stmt_for_mut(&mut self, s: &mut For) {
+        visit_stmt_for_mut(self, s);
+    }
+    fn visit_stmt_for_in_mut(&mut self, s: &mut ForIn) {
+        visit_stmt_for_in_mut(self, s);
+    }
+    fn visit_stmt_function_mut(&mut self, s: &mut Function) {
+        visit_stmt_function_mut(self, s);
+    }
+    fn visit_stmt_if_mut(&mut self, s: &mut If) {
+        visit_stmt_if_mut(self, s);
+    }
+    fn visit_stmt_print_mut(&mut self, s: &mut Print) {
+        visit_stmt_print_mut(self, s);
+    }
+    fn visit_stmt_return_mut(&mut self, s: &mut Return) {
+        visit_stmt_return_mut(self, s);
+    }
+    fn visit_stmt_switch_mut(&mut self, s: &mut Switch) {
+        visit_stmt_switch_mut(self, s);
+    }
+    fn visit_stmt_throw_mut(&mut self, s: &mut Throw) {
+        visit_stmt_throw_mut(self, s);
+    }
+    fn visit_stmt_try_mut(&mut self, s: &mut Try) {
+        visit_stmt_try_mut(self, s);
+    }
+    fn visit_stmt_var_mut(&mut self, s: &mut Var) {
+        visit_stmt_var_mut(self, s);
+    }
+    fn visit_stmt_while_mut(&mut self, s: &mut While) {
+        visit_stmt_while_mut(self, s);
+    }
+}
+
+pub fn visit_expr_mut<V>(v: &mut V, node: &mut Expr)
+where
+    V: VisitMut + ?Sized,
+{
+    match node {
+        Expr::Assign(assign) => {
+            v.visit_expr_assign_mut(assign);
+        }
+        Expr::Binary(binary) => {
+            v.visit_expr_binary_mut(binary);
+        }
+        Expr::Call(call) => {
+            v.visit_expr_call_mut(call);
+        }
+        Expr::Get(get) => {
+            v.visit_expr_get_mut(get);
+        }
+        Expr::Grouping(grouping) => {
+            v.visit_expr_grouping_mut(grouping);
+        }
+        Expr::Index(index) => {
+            v.visit_expr_index_mut(index);
+        }
+        Expr::Interpolation(interpolation) => {
+            v.visit_expr_interpolation_mut(interpolation);
+        }
+        Expr::ListLiteral(list_literal) => {
+            v.visit_expr_list_literal_mut(list_literal);
+        }
+        Expr::Literal(literal) => {
+            v.visit_expr_literal_mut(literal);
+        }
+        Expr::Logical(logical) => {
+            v.visit_expr_logical_mut(logical);
+        }
+        Expr::MapLiteral(map_literal) => {
+            v.visit_expr_map_literal_mut(map_literal);
+        }
+        Expr::Set(set) => {
+            v.visit_expr_set_mut(set);
+        }
+        Expr::SetIndex(set_index) => {
+            v.visit_expr_set_index_mut(set_index);
+        }
+        Expr::Super(super_) => {
+            v.visit_expr_super_mut(super_);
+        }
+        Expr::Ternary(ternary) => {
+            v.visit_expr_ternary_mut(ternary);
+        }
+        Expr::This(this) => {
+            v.visit_expr_this_mut(this);
+        }
+        Expr::Variable(variable) => {
+            v.visit_expr_variable_mut(variable);
+        }
+        Expr::Unary(unary) => {
+            v.visit_expr_unary_mut(unary);
+        }
+    }
+}
+
+pub fn visit_expr_assign_mut<V>(v: &mut V, node: &mut Assign)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.value);
+}
+
+pub fn visit_expr_binary_mut<V>(v: &mut V, node: &mut Binary)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.left);
+    v.visit_expr_mut(&mut node.right);
+}
+
+pub fn visit_expr_call_mut<V>(v: &mut V, node: &mut Call)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.callee);
+    for arg in &mut node.arguments {
+        v.visit_expr_mut(arg);
+    }
+}
+
+pub fn visit_expr_get_mut<V>(v: &mut V, node: &mut Get)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.object);
+}
+
+pub fn visit_expr_grouping_mut<V>(v: &mut V, node: &mut Grouping)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.expression);
+}
+
+pub fn visit_expr_index_mut<V>(v: &mut V, node: &mut Index)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.object);
+    v.visit_expr_mut(&mut node.index);
+}
+
+pub fn visit_expr_interpolation_mut<V>(v: &mut V, node: &mut Interpolation)
+where
+    V: VisitMut + ?Sized,
+{
+    for part in &mut node.parts {
+        if let StringPart::Expr(expr) = part {
+            v.visit_expr_mut(expr);
+        }
+    }
+}
+
+pub fn visit_expr_list_literal_mut<V>(v: &mut V, node: &mut ListLiteral)
+where
+    V: VisitMut + ?Sized,
+{
+    for element in &mut node.elements {
+        v.visit_expr_mut(element);
+    }
+}
+
+pub fn visit_expr_literal_mut<V>(_: &mut V, _: &mut LiteralExpr)
+where
+    V: VisitMut + ?Sized,
+{
+}
+
+pub fn visit_expr_logical_mut<V>(v: &mut V, node: &mut Logical)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.left);
+    v.visit_expr_mut(&mut node.right);
+}
+
+pub fn visit_expr_map_literal_mut<V>(v: &mut V, node: &mut MapLiteral)
+where
+    V: VisitMut + ?Sized,
+{
+    for (key, value) in &mut node.entries {
+        v.visit_expr_mut(key);
+        v.visit_expr_mut(value);
+    }
+}
+
+pub fn visit_expr_set_mut<V>(v: &mut V, node: &mut Set)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.object);
+    v.visit_expr_mut(&mut node.value);
+}
+
+pub fn visit_expr_set_index_mut<V>(v: &mut V, node: &mut SetIndex)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.object);
+    v.visit_expr_mut(&mut node.index);
+    v.visit_expr_mut(&mut node.value);
+}
+
+pub fn visit_expr_super_mut<V>(_: &mut V, _: &mut Super)
+where
+    V: VisitMut + ?Sized,
+{
+}
+
+pub fn visit_expr_ternary_mut<V>(v: &mut V, node: &mut Ternary)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.condition);
+    v.visit_expr_mut(&mut node.then_expr);
+    v.visit_expr_mut(&mut node.else_expr);
+}
+
+pub fn visit_expr_this_mut<V>(_: &mut V, _: &mut This)
+where
+    V: VisitMut + ?Sized,
+{
+}
+
+pub fn visit_expr_variable_mut<V>(_: &mut V, _: &mut Variable)
+where
+    V: VisitMut + ?Sized,
+{
+}
+
+pub fn visit_expr_unary_mut<V>(v: &mut V, node: &mut Unary)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.right);
+}
+
+pub fn visit_stmt_mut<V>(v: &mut V, node: &mut Stmt)
+where
+    V: VisitMut + ?Sized,
+{
+    match node {
+        Stmt::Block(block) => {
+            v.visit_stmt_block_mut(block);
+        }
+        Stmt::Class(class) => {
+            v.visit_stmt_class_mut(class);
+        }
+        Stmt::DoWhile(do_while) => {
+            v.visit_stmt_do_while_mut(do_while);
+        }
+        Stmt::Expression(expression) => {
+            v.visit_stmt_expression_mut(expression);
+        }
+        Stmt::For(for_) => {
+            v.visit_stmt_for_mut(for_);
+        }
+        Stmt::ForIn(for_in) => {
+            v.visit_stmt_for_in_mut(for_in);
+        }
+        Stmt::Function(function) => {
+            v.visit_stmt_function_mut(function);
+        }
+        Stmt::If(if_) => {
+            v.visit_stmt_if_mut(if_);
+        }
+        Stmt::Print(print) => {
+            v.visit_stmt_print_mut(print);
+        }
+        Stmt::Return(return_) => {
+            v.visit_stmt_return_mut(return_);
+        }
+        Stmt::Switch(switch) => {
+            v.visit_stmt_switch_mut(switch);
+        }
+        Stmt::Throw(throw) => {
+            v.visit_stmt_throw_mut(throw);
+        }
+        Stmt::Try(try_) => {
+            v.visit_stmt_try_mut(try_);
+        }
+        Stmt::Var(var) => {
+            v.visit_stmt_var_mut(var);
+        }
+        Stmt::While(while_) => {
+            v.visit_stmt_while_mut(while_);
+        }
+    }
+}
+
+pub fn visit_stmt_block_mut<V>(v: &mut V, node: &mut Block)
+where
+    V: VisitMut + ?Sized,
+{
+    for stmt in &mut node.statements {
+        v.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn visit_stmt_class_mut<V>(v: &mut V, node: &mut Class)
+where
+    V: VisitMut + ?Sized,
+{
+    if let Some(superclass) = &mut node.superclass {
+        v.visit_expr_variable_mut(superclass);
+    }
+    for method in &mut node.methods {
+        v.visit_stmt_function_mut(method);
+    }
+}
+
+pub fn visit_stmt_do_while_mut<V>(v: &mut V, node: &mut DoWhile)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_stmt_mut(&mut node.body);
+    v.visit_expr_mut(&mut node.condition);
+}
+
+pub fn visit_stmt_expression_mut<V>(v: &mut V, node: &mut Expression)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.expression);
+}
+
+pub fn visit_stmt_for_mut<V>(v: &mut V, node: &mut For)
+where
+    V: VisitMut + ?Sized,
+{
+    if let Some(initializer) = &mut node.initializer {
+        v.visit_stmt_mut(initializer);
+    }
+    if let Some(condition) = &mut node.condition {
+        v.visit_expr_mut(condition);
+    }
+    if let Some(increment) = &mut node.increment {
+        v.visit_expr_mut(increment);
+    }
+    v.visit_stmt_mut(&mut node.body);
+}
+
+pub fn visit_stmt_for_in_mut<V>(v: &mut V, node: &mut ForIn)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.iterable);
+    v.visit_stmt_mut(&mut node.body);
+}
+
+pub fn visit_stmt_function_mut<V>(v: &mut V, node: &mut Function)
+where
+    V: VisitMut + ?Sized,
+{
+    // the body is only copied if something else shares it
+    for stmt in Rc::make_mut(&mut node.body) {
+        v.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn visit_stmt_if_mut<V>(v: &mut V, node: &mut If)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.condition);
+    v.visit_stmt_mut(&mut node.then_branch);
+    if let Some(else_branch) = &mut node.else_branch {
+        v.visit_stmt_mut(else_branch);
+    }
+}
+
+pub fn visit_stmt_print_mut<V>(v: &mut V, node: &mut Print)
+where
+    V: VisitMut + ?Sized,
+{
+    for expression in &mut node.expressions {
+        v.visit_expr_mut(expression);
+    }
+}
+
+pub fn visit_stmt_return_mut<V>(v: &mut V, node: &mut Return)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.value);
+}
+
+pub fn visit_stmt_switch_mut<V>(v: &mut V, node: &mut Switch)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.subject);
+    for (value, body) in &mut node.cases {
+        v.visit_expr_mut(value);
+        for stmt in body {
+            v.visit_stmt_mut(stmt);
+        }
+    }
+    for stmt in node.default.iter_mut().flatten() {
+        v.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn visit_stmt_throw_mut<V>(v: &mut V, node: &mut Throw)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.value);
+}
+
+pub fn visit_stmt_try_mut<V>(v: &mut V, node: &mut Try)
+where
+    V: VisitMut + ?Sized,
+{
+    for stmt in &mut node.body {
+        v.visit_stmt_mut(stmt);
+    }
+    for stmt in &mut node.catch_body {
+        v.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn visit_stmt_var_mut<V>(v: &mut V, node: &mut Var)
+where
+    V: VisitMut + ?Sized,
+{
+    if let Some(initializer) = &mut node.initializer {
+        v.visit_expr_mut(initializer);
+    }
+}
+
+pub fn visit_stmt_while_mut<V>(v: &mut V, node: &mut While)
+where
+    V: VisitMut + ?Sized,
+{
+    v.visit_expr_mut(&mut node.condition);
+    v.visit_stmt_mut(&mut node.body);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast_printer::AstPrinter, parser::Parser, scanner::Scanner, symbol::Symbol};
+
+    // Renames every use of one variable, as a minimal transformation pass.
+    struct Rename {
+        from: Symbol,
+        to: Symbol,
+    }
+
+    impl VisitMut for Rename {
+        fn visit_expr_assign_mut(&mut self, e: &mut Assign) {
+            if e.name == self.from {
+                e.name = self.to;
+            }
+            visit_expr_assign_mut(self, e);
+        }
+
+        fn visit_expr_variable_mut(&mut self, e: &mut Variable) {
+            if e.name == self.from {
+                e.name = self.to;
+            }
+        }
+
+        fn visit_stmt_var_mut(&mut self, s: &mut Var) {
+            if s.name == self.from {
+                s.name = self.to;
+            }
+            visit_stmt_var_mut(self, s);
+        }
+    }
+
+    #[test]
+    fn rename_variable() {
+        let tokens = Scanner::new("var a = 1; { a = a + 1; print [a, b]; } fun f() { return a; }")
+            .scan_tokens()
+            .unwrap();
+        let mut stmts = Parser::new(tokens).parse().unwrap();
+        let mut rename = Rename {
+            from: "a".into(),
+            to: "z".into(),
+        };
+        for stmt in &mut stmts {
+            rename.visit_stmt_mut(stmt);
+        }
+        assert_eq!(
+            AstPrinter.print_program(&stmts),
+            "(var z 1)\n(block (; (= z (+ z 1))) (print (list z b)))\n(fun f () (return z))\n"
+        );
+    }
+}
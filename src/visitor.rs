@@ -1,28 +1,35 @@
 use crate::{
-    expr::{Assign, Binary, Call, Expr, Grouping, Literal, Logical, Unary, Variable},
-    stmt::{Block, Expression, Function, If, Print, Return, Stmt, Var, While},
+    expr::{
+        Assign, Binary, Call, Expr, Get, Grouping, Index, Interpolation, ListLiteral, LiteralExpr,
+        Logical, MapLiteral, Set, SetIndex, StringPart, Super, Ternary, This, Unary, Variable,
+    },
+    stmt::{
+        Block, Class, DoWhile, Expression, For, ForIn, Function, If, Print, Return, Stmt, Switch,
+        Throw, Try, Var, While,
+    },
 };
 
 pub trait ExprVisitor {
     type ExprResult;
     fn visit_expr(&mut self, expr: &Expr) -> Self::ExprResult {
-        match expr {
-            Expr::Assign(assign) => self.visit_expr_assign(assign),
-            Expr::Binary(binary) => self.visit_expr_binary(binary),
-            Expr::Call(call) => self.visit_expr_call(call),
-            Expr::Grouping(grouping) => self.visit_expr_grouping(grouping),
-            Expr::Literal(literal) => self.visit_expr_literal(literal),
-            Expr::Logical(logical) => self.visit_expr_logical(logical),
-            Expr::Variable(variable) => self.visit_expr_variable(variable),
-            Expr::Unary(unary) => self.visit_expr_unary(unary),
-        }
+        dispatch_expr(self, expr)
     }
     fn visit_expr_assign(&mut self, assign: &Assign) -> Self::ExprResult;
     fn visit_expr_binary(&mut self, binary: &Binary) -> Self::ExprResult;
     fn visit_expr_call(&mut self, call: &Call) -> Self::ExprResult;
+    fn visit_expr_get(&mut self, get: &Get) -> Self::ExprResult;
     fn visit_expr_grouping(&mut self, grouping: &Grouping) -> Self::ExprResult;
-    fn visit_expr_literal(&mut self, literal: &Literal) -> Self::ExprResult;
+    fn visit_expr_index(&mut self, index: &Index) -> Self::ExprResult;
+    fn visit_expr_interpolation(&mut self, interpolation: &Interpolation) -> Self::ExprResult;
+    fn visit_expr_list_literal(&mut self, list_literal: &ListLiteral) -> Self::ExprResult;
+    fn visit_expr_literal(&mut self, literal: &LiteralExpr) -> Self::ExprResult;
     fn visit_expr_logical(&mut self, logical: &Logical) -> Self::ExprResult;
+    fn visit_expr_map_literal(&mut self, map_literal: &MapLiteral) -> Self::ExprResult;
+    fn visit_expr_set(&mut self, set: &Set) -> Self::ExprResult;
+    fn visit_expr_set_index(&mut self, set_index: &SetIndex) -> Self::ExprResult;
+    fn visit_expr_super(&mut self, super_: &Super) -> Self::ExprResult;
+    fn visit_expr_ternary(&mut self, ternary: &Ternary) -> Self::ExprResult;
+    fn visit_expr_this(&mut self, this: &This) -> Self::ExprResult;
     fn visit_expr_variable(&mut self, variable: &Variable) -> Self::ExprResult;
     fn visit_expr_unary(&mut self, unary: &Unary) -> Self::ExprResult;
 }
@@ -30,27 +37,79 @@ pub trait ExprVisitor {
 pub trait StmtVisitor {
     type StmtResult;
     fn visit_stmt(&mut self, stmt: &Stmt) -> Self::StmtResult {
-        match stmt {
-            Stmt::Block(block) => self.visit_stmt_block(block),
-            Stmt::Expression(expression) => self.visit_stmt_expression(expression),
-            Stmt::Function(function) => self.visit_stmt_function(function),
-            Stmt::If(if_) => self.visit_stmt_if(if_),
-            Stmt::Print(print) => self.visit_stmt_print(print),
-            Stmt::Return(return_) => self.visit_stmt_return(return_),
-            Stmt::Var(var) => self.visit_stmt_var(var),
-            Stmt::While(while_) => self.visit_stmt_while(while_),
-        }
+        dispatch_stmt(self, stmt)
     }
     fn visit_stmt_block(&mut self, block: &Block) -> Self::StmtResult;
+    fn visit_stmt_class(&mut self, class: &Class) -> Self::StmtResult;
+    fn visit_stmt_do_while(&mut self, do_while: &DoWhile) -> Self::StmtResult;
     fn visit_stmt_expression(&mut self, expression: &Expression) -> Self::StmtResult;
+    fn visit_stmt_for(&mut self, for_: &For) -> Self::StmtResult;
+    fn visit_stmt_for_in(&mut self, for_in: &ForIn) -> Self::StmtResult;
     fn visit_stmt_function(&mut self, function: &Function) -> Self::StmtResult;
     fn visit_stmt_if(&mut self, if_: &If) -> Self::StmtResult;
     fn visit_stmt_print(&mut self, print: &Print) -> Self::StmtResult;
     fn visit_stmt_return(&mut self, return_: &Return) -> Self::StmtResult;
+    fn visit_stmt_switch(&mut self, switch: &Switch) -> Self::StmtResult;
+    fn visit_stmt_throw(&mut self, throw: &Throw) -> Self::StmtResult;
+    fn visit_stmt_try(&mut self, try_: &Try) -> Self::StmtResult;
     fn visit_stmt_var(&mut self, var: &Var) -> Self::StmtResult;
     fn visit_stmt_while(&mut self, while_: &While) -> Self::StmtResult;
 }
 
+/// Calls the `ExprVisitor` method for the kind of expression `expr` is. This
+/// is what `ExprVisitor::visit_expr` does by default, and is useful for
+/// visitors that override it to do extra work for every expression.
+pub fn dispatch_expr<V>(v: &mut V, expr: &Expr) -> V::ExprResult
+where
+    V: ExprVisitor + ?Sized,
+{
+    match expr {
+        Expr::Assign(assign) => v.visit_expr_assign(assign),
+        Expr::Binary(binary) => v.visit_expr_binary(binary),
+        Expr::Call(call) => v.visit_expr_call(call),
+        Expr::Get(get) => v.visit_expr_get(get),
+        Expr::Grouping(grouping) => v.visit_expr_grouping(grouping),
+        Expr::Index(index) => v.visit_expr_index(index),
+        Expr::Interpolation(interpolation) => v.visit_expr_interpolation(interpolation),
+        Expr::ListLiteral(list_literal) => v.visit_expr_list_literal(list_literal),
+        Expr::Literal(literal) => v.visit_expr_literal(literal),
+        Expr::Logical(logical) => v.visit_expr_logical(logical),
+        Expr::MapLiteral(map_literal) => v.visit_expr_map_literal(map_literal),
+        Expr::Set(set) => v.visit_expr_set(set),
+        Expr::SetIndex(set_index) => v.visit_expr_set_index(set_index),
+        Expr::Super(super_) => v.visit_expr_super(super_),
+        Expr::Ternary(ternary) => v.visit_expr_ternary(ternary),
+        Expr::This(this) => v.visit_expr_this(this),
+        Expr::Variable(variable) => v.visit_expr_variable(variable),
+        Expr::Unary(unary) => v.visit_expr_unary(unary),
+    }
+}
+
+/// Calls the `StmtVisitor` method for the kind of statement `stmt` is. This
+/// is what `StmtVisitor::visit_stmt` does by default.
+pub fn dispatch_stmt<V>(v: &mut V, stmt: &Stmt) -> V::StmtResult
+where
+    V: StmtVisitor + ?Sized,
+{
+    match stmt {
+        Stmt::Block(block) => v.visit_stmt_block(block),
+        Stmt::Class(class) => v.visit_stmt_class(class),
+        Stmt::DoWhile(do_while) => v.visit_stmt_do_while(do_while),
+        Stmt::Expression(expression) => v.visit_stmt_expression(expression),
+        Stmt::For(for_) => v.visit_stmt_for(for_),
+        Stmt::ForIn(for_in) => v.visit_stmt_for_in(for_in),
+        Stmt::Function(function) => v.visit_stmt_function(function),
+        Stmt::If(if_) => v.visit_stmt_if(if_),
+        Stmt::Print(print) => v.visit_stmt_print(print),
+        Stmt::Return(return_) => v.visit_stmt_return(return_),
+        Stmt::Switch(switch) => v.visit_stmt_switch(switch),
+        Stmt::Throw(throw) => v.visit_stmt_throw(throw),
+        Stmt::Try(try_) => v.visit_stmt_try(try_),
+        Stmt::Var(var) => v.visit_stmt_var(var),
+        Stmt::While(while_) => v.visit_stmt_while(while_),
+    }
+}
+
 pub trait Visit<'ast> {
     fn visit_expr(&mut self, e: &'ast Expr) {
         visit_expr(self, e);
@@ -64,15 +123,45 @@ pub trait Visit<'ast> {
     fn visit_expr_call(&mut self, e: &'ast Call) {
         visit_expr_call(self, e);
     }
+    fn visit_expr_get(&mut self, e: &'ast Get) {
+        visit_expr_get(self, e);
+    }
     fn visit_expr_grouping(&mut self, e: &'ast Grouping) {
         visit_expr_grouping(self, e);
     }
-    fn visit_expr_literal(&mut self, e: &'ast Literal) {
+    fn visit_expr_index(&mut self, e: &'ast Index) {
+        visit_expr_index(self, e);
+    }
+    fn visit_expr_interpolation(&mut self, e: &'ast Interpolation) {
+        visit_expr_interpolation(self, e);
+    }
+    fn visit_expr_list_literal(&mut self, e: &'ast ListLiteral) {
+        visit_expr_list_literal(self, e);
+    }
+    fn visit_expr_literal(&mut self, e: &'ast LiteralExpr) {
         visit_expr_literal(self, e);
     }
     fn visit_expr_logical(&mut self, e: &'ast Logical) {
         visit_expr_logical(self, e);
     }
+    fn visit_expr_map_literal(&mut self, e: &'ast MapLiteral) {
+        visit_expr_map_literal(self, e);
+    }
+    fn visit_expr_set(&mut self, e: &'ast Set) {
+        visit_expr_set(self, e);
+    }
+    fn visit_expr_set_index(&mut self, e: &'ast SetIndex) {
+        visit_expr_set_index(self, e);
+    }
+    fn visit_expr_super(&mut self, e: &'ast Super) {
+        visit_expr_super(self, e);
+    }
+    fn visit_expr_ternary(&mut self, e: &'ast Ternary) {
+        visit_expr_ternary(self, e);
+    }
+    fn visit_expr_this(&mut self, e: &'ast This) {
+        visit_expr_this(self, e);
+    }
     fn visit_expr_variable(&mut self, e: &'ast Variable) {
         visit_expr_variable(self, e);
     }
@@ -85,9 +174,21 @@ pub trait Visit<'ast> {
     fn visit_stmt_block(&mut self, s: &'ast Block) {
         visit_stmt_block(self, s);
     }
+    fn visit_stmt_class(&mut self, s: &'ast Class) {
+        visit_stmt_class(self, s);
+    }
+    fn visit_stmt_do_while(&mut self, s: &'ast DoWhile) {
+        visit_stmt_do_while(self, s);
+    }
     fn visit_stmt_expression(&mut self, s: &'ast Expression) {
         visit_stmt_expression(self, s);
     }
+    fn visit_stmt_for(&mut self, s: &'ast For) {
+        visit_stmt_for(self, s);
+    }
+    fn visit_stmt_for_in(&mut self, s: &'ast ForIn) {
+        visit_stmt_for_in(self, s);
+    }
     fn visit_stmt_function(&mut self, s: &'ast Function) {
         visit_stmt_function(self, s);
     }
@@ -100,6 +201,15 @@ pub trait Visit<'ast> {
     fn visit_stmt_return(&mut self, s: &'ast Return) {
         visit_stmt_return(self, s);
     }
+    fn visit_stmt_switch(&mut self, s: &'ast Switch) {
+        visit_stmt_switch(self, s);
+    }
+    fn visit_stmt_throw(&mut self, s: &'ast Throw) {
+        visit_stmt_throw(self, s);
+    }
+    fn visit_stmt_try(&mut self, s: &'ast Try) {
+        visit_stmt_try(self, s);
+    }
     fn visit_stmt_var(&mut self, s: &'ast Var) {
         visit_stmt_var(self, s);
     }
@@ -122,15 +232,45 @@ where
         Expr::Call(call) => {
             v.visit_expr_call(call);
         }
+        Expr::Get(get) => {
+            v.visit_expr_get(get);
+        }
         Expr::Grouping(grouping) => {
             v.visit_expr_grouping(grouping);
         }
+        Expr::Index(index) => {
+            v.visit_expr_index(index);
+        }
+        Expr::Interpolation(interpolation) => {
+            v.visit_expr_interpolation(interpolation);
+        }
+        Expr::ListLiteral(list_literal) => {
+            v.visit_expr_list_literal(list_literal);
+        }
         Expr::Literal(literal) => {
             v.visit_expr_literal(literal);
         }
         Expr::Logical(logical) => {
             v.visit_expr_logical(logical);
         }
+        Expr::MapLiteral(map_literal) => {
+            v.visit_expr_map_literal(map_literal);
+        }
+        Expr::Set(set) => {
+            v.visit_expr_set(set);
+        }
+        Expr::SetIndex(set_index) => {
+            v.visit_expr_set_index(set_index);
+        }
+        Expr::Super(super_) => {
+            v.visit_expr_super(super_);
+        }
+        Expr::Ternary(ternary) => {
+            v.visit_expr_ternary(ternary);
+        }
+        Expr::This(this) => {
+            v.visit_expr_this(this);
+        }
         Expr::Variable(variable) => {
             v.visit_expr_variable(variable);
         }
@@ -165,6 +305,13 @@ where
     }
 }
 
+pub fn visit_expr_get<'ast, V>(v: &mut V, node: &'ast Get)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    v.visit_expr(&node.object);
+}
+
 pub fn visit_expr_grouping<'ast, V>(v: &mut V, node: &'ast Grouping)
 where
     V: Visit<'ast> + ?Sized,
@@ -172,7 +319,35 @@ where
     v.visit_expr(&node.expression);
 }
 
-pub fn visit_expr_literal<'ast, V>(_: &mut V, _: &'ast Literal)
+pub fn visit_expr_index<'ast, V>(v: &mut V, node: &'ast Index)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    v.visit_expr(&node.object);
+    v.visit_expr(&node.index);
+}
+
+pub fn visit_expr_interpolation<'ast, V>(v: &mut V, node: &'ast Interpolation)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    for part in &node.parts {
+        if let StringPart::Expr(expr) = part {
+            v.visit_expr(expr);
+        }
+    }
+}
+
+pub fn visit_expr_list_literal<'ast, V>(v: &mut V, node: &'ast ListLiteral)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    for element in &node.elements {
+        v.visit_expr(element);
+    }
+}
+
+pub fn visit_expr_literal<'ast, V>(_: &mut V, _: &'ast LiteralExpr)
 where
     V: Visit<'ast> + ?Sized,
 {
@@ -186,6 +361,54 @@ where
     v.visit_expr(&node.right);
 }
 
+pub fn visit_expr_map_literal<'ast, V>(v: &mut V, node: &'ast MapLiteral)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    for (key, value) in &node.entries {
+        v.visit_expr(key);
+        v.visit_expr(value);
+    }
+}
+
+pub fn visit_expr_set<'ast, V>(v: &mut V, node: &'ast Set)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    v.visit_expr(&node.object);
+    v.visit_expr(&node.value);
+}
+
+pub fn visit_expr_set_index<'ast, V>(v: &mut V, node: &'ast SetIndex)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    v.visit_expr(&node.object);
+    v.visit_expr(&node.index);
+    v.visit_expr(&node.value);
+}
+
+pub fn visit_expr_super<'ast, V>(_: &mut V, _: &'ast Super)
+where
+    V: Visit<'ast> + ?Sized,
+{
+}
+
+pub fn visit_expr_ternary<'ast, V>(v: &mut V, node: &'ast Ternary)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    v.visit_expr(&node.condition);
+    v.visit_expr(&node.then_expr);
+    v.visit_expr(&node.else_expr);
+}
+
+pub fn visit_expr_this<'ast, V>(_: &mut V, _: &'ast This)
+where
+    V: Visit<'ast> + ?Sized,
+{
+}
+
 pub fn visit_expr_variable<'ast, V>(_: &mut V, _: &'ast Variable)
 where
     V: Visit<'ast> + ?Sized,
@@ -207,9 +430,21 @@ where
         Stmt::Block(block) => {
             v.visit_stmt_block(block);
         }
+        Stmt::Class(class) => {
+            v.visit_stmt_class(class);
+        }
+        Stmt::DoWhile(do_while) => {
+            v.visit_stmt_do_while(do_while);
+        }
         Stmt::Expression(expression) => {
             v.visit_stmt_expression(expression);
         }
+        Stmt::For(for_) => {
+            v.visit_stmt_for(for_);
+        }
+        Stmt::ForIn(for_in) => {
+            v.visit_stmt_for_in(for_in);
+        }
         Stmt::Function(function) => {
             v.visit_stmt_function(function);
         }
@@ -222,6 +457,15 @@ where
         Stmt::Return(return_) => {
             v.visit_stmt_return(return_);
         }
+        Stmt::Switch(switch) => {
+            v.visit_stmt_switch(switch);
+        }
+        Stmt::Throw(throw) => {
+            v.visit_stmt_throw(throw);
+        }
+        Stmt::Try(try_) => {
+            v.visit_stmt_try(try_);
+        }
         Stmt::Var(var) => {
             v.visit_stmt_var(var);
         }
@@ -240,6 +484,26 @@ where
     }
 }
 
+pub fn visit_stmt_class<'ast, V>(v: &mut V, node: &'ast Class)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    if let Some(superclass) = &node.superclass {
+        v.visit_expr_variable(superclass);
+    }
+    for method in &node.methods {
+        v.visit_stmt_function(method);
+    }
+}
+
+pub fn visit_stmt_do_while<'ast, V>(v: &mut V, node: &'ast DoWhile)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    v.visit_stmt(&node.body);
+    v.visit_expr(&node.condition);
+}
+
 pub fn visit_stmt_expression<'ast, V>(v: &mut V, node: &'ast Expression)
 where
     V: Visit<'ast> + ?Sized,
@@ -247,12 +511,36 @@ where
     v.visit_expr(&node.expression);
 }
 
+pub fn visit_stmt_for<'ast, V>(v: &mut V, node: &'ast For)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    if let Some(initializer) = &node.initializer {
+        v.visit_stmt(initializer);
+    }
+    if let Some(condition) = &node.condition {
+        v.visit_expr(condition);
+    }
+    if let Some(increment) = &node.increment {
+        v.visit_expr(increment);
+    }
+    v.visit_stmt(&node.body);
+}
+
+pub fn visit_stmt_for_in<'ast, V>(v: &mut V, node: &'ast ForIn)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    v.visit_expr(&node.iterable);
+    v.visit_stmt(&node.body);
+}
+
 pub fn visit_stmt_function<'ast, V>(v: &mut V, node: &'ast Function)
 where
     V: Visit<'ast> + ?Sized,
 {
-    for stmt in &node.body {
-        v.visit_stmt(&stmt);
+    for stmt in node.body.iter() {
+        v.visit_stmt(stmt);
     }
 }
 
@@ -271,7 +559,9 @@ pub fn visit_stmt_print<'ast, V>(v: &mut V, node: &'ast Print)
 where
     V: Visit<'ast> + ?Sized,
 {
-    v.visit_expr(&node.expression);
+    for expression in &node.expressions {
+        v.visit_expr(expression);
+    }
 }
 
 pub fn visit_stmt_return<'ast, V>(v: &mut V, node: &'ast Return)
@@ -281,6 +571,41 @@ where
     v.visit_expr(&node.value);
 }
 
+pub fn visit_stmt_switch<'ast, V>(v: &mut V, node: &'ast Switch)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    v.visit_expr(&node.subject);
+    for (value, body) in &node.cases {
+        v.visit_expr(value);
+        for stmt in body {
+            v.visit_stmt(stmt);
+        }
+    }
+    for stmt in node.default.iter().flatten() {
+        v.visit_stmt(stmt);
+    }
+}
+
+pub fn visit_stmt_throw<'ast, V>(v: &mut V, node: &'ast Throw)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    v.visit_expr(&node.value);
+}
+
+pub fn visit_stmt_try<'ast, V>(v: &mut V, node: &'ast Try)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    for stmt in &node.body {
+        v.visit_stmt(stmt);
+    }
+    for stmt in &node.catch_body {
+        v.visit_stmt(stmt);
+    }
+}
+
 pub fn visit_stmt_var<'ast, V>(v: &mut V, node: &'ast Var)
 where
     V: Visit<'ast> + ?Sized,